@@ -0,0 +1,190 @@
+//! Tar archive export: one entry per response body plus an `index.json` describing them.
+//!
+//! Archives are written as plain (uncompressed) POSIX ustar and can be compressed
+//! afterwards with standard tools.
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use serde::Serialize;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::{Error, Event};
+
+const BLOCK: usize = 512;
+
+/// Metadata written to `index.json` for each archived body.
+#[derive(Clone, Debug, Serialize)]
+pub struct IndexEntry {
+    /// Path of the body inside the archive.
+    pub file: String,
+    pub url: String,
+    pub method: Option<String>,
+    pub status: Option<u16>,
+    #[serde(rename = "contentType")]
+    pub content_type: Option<String>,
+    pub size: usize,
+    #[serde(rename = "startedAt")]
+    pub started_at: Option<f64>,
+}
+
+/// Streams bodies into a tar archive as events arrive; [`finish`](Self::finish) appends the index.
+pub struct TarArchiveWriter<W> {
+    writer: W,
+    index: Vec<IndexEntry>,
+}
+
+impl<W: AsyncWrite + Unpin> TarArchiveWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            index: Vec::new(),
+        }
+    }
+
+    /// Append the event body as `bodies/<n>.<ext>`, with the extension taken from its content-type.
+    /// Base64-encoded bodies are stored decoded.
+    pub async fn add_event(&mut self, event: &Event) -> Result<(), Error> {
+        let file = format!(
+            "bodies/{:06}.{}",
+            self.index.len() + 1,
+            extension_for(event.content_type.as_deref())
+        );
+        let mtime = event.started_at.map_or(0, |ms| (ms / 1000.0) as u64);
+        let body = event.bytes()?;
+        self.write_entry(&file, &body, mtime).await?;
+        self.index.push(IndexEntry {
+            file,
+            url: event.url.clone(),
+            method: event.method.clone(),
+            status: event.status,
+            content_type: event.content_type.clone(),
+            size: body.len(),
+            started_at: event.started_at,
+        });
+        Ok(())
+    }
+
+    /// Write `index.json` and the end-of-archive marker, returning the underlying writer.
+    pub async fn finish(mut self) -> Result<W, Error> {
+        let index = serde_json::to_vec_pretty(&self.index).map_err(Error::SerializeJson)?;
+        self.write_entry("index.json", &index, 0).await?;
+        self.writer
+            .write_all(&[0u8; BLOCK * 2])
+            .await
+            .map_err(Error::Io)?;
+        self.writer.flush().await.map_err(Error::Io)?;
+        Ok(self.writer)
+    }
+
+    async fn write_entry(&mut self, name: &str, data: &[u8], mtime: u64) -> Result<(), Error> {
+        self.writer
+            .write_all(&ustar_header(name, data.len() as u64, mtime))
+            .await
+            .map_err(Error::Io)?;
+        self.writer.write_all(data).await.map_err(Error::Io)?;
+        let padding = (BLOCK - data.len() % BLOCK) % BLOCK;
+        self.writer
+            .write_all(&vec![0u8; padding])
+            .await
+            .map_err(Error::Io)
+    }
+}
+
+/// Archive every event from the receiver until the stream closes, then write the index.
+/// Returns the number of archived bodies.
+pub async fn write_tar_archive<W: AsyncWrite + Unpin>(
+    rx: &mut mpsc::UnboundedReceiver<Event>,
+    writer: W,
+) -> Result<usize, Error> {
+    let mut archive = TarArchiveWriter::new(writer);
+    while let Some(event) = rx.next().await {
+        archive.add_event(&event).await?;
+    }
+    let count = archive.index.len();
+    archive.finish().await?;
+    Ok(count)
+}
+
+fn extension_for(content_type: Option<&str>) -> &'static str {
+    let ct = content_type.unwrap_or_default();
+    [
+        ("json", "json"),
+        ("html", "html"),
+        ("svg", "svg"),
+        ("xml", "xml"),
+        ("javascript", "js"),
+        ("css", "css"),
+        ("csv", "csv"),
+    ]
+    .iter()
+    .find(|(needle, _)| ct.contains(needle))
+    .map_or("txt", |(_, ext)| ext)
+}
+
+fn ustar_header(name: &str, size: u64, mtime: u64) -> [u8; BLOCK] {
+    let mut header = [0u8; BLOCK];
+    let name = name.as_bytes();
+    header[..name.len().min(100)].copy_from_slice(&name[..name.len().min(100)]);
+    write_octal(&mut header[100..108], 0o644);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[124..136], size);
+    write_octal(&mut header[136..148], mtime);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // The checksum is computed with its own field filled with spaces.
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+    let digits = format!("{checksum:06o}\0 ");
+    header[148..156].copy_from_slice(digits.as_bytes());
+    header
+}
+
+// Zero-padded octal digits followed by a NUL terminator.
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{value:0width$o}\0", width = field.len() - 1);
+    field.copy_from_slice(&digits.as_bytes()[digits.len() - field.len()..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ustar_header_fields_and_checksum() {
+        let header = ustar_header("bodies/0001.json", 1234, 1_700_000_000);
+        assert_eq!(&header[..16], b"bodies/0001.json");
+        assert_eq!(header[16], 0);
+        assert_eq!(&header[100..108], b"0000644\0");
+        assert_eq!(&header[124..136], b"00000002322\0");
+        assert_eq!(&header[136..148], b"14524770400\0");
+        assert_eq!(header[156], b'0');
+        assert_eq!(&header[257..265], b"ustar\x0000");
+
+        let mut blank = header;
+        blank[148..156].fill(b' ');
+        let sum: u32 = blank.iter().map(|&b| u32::from(b)).sum();
+        assert_eq!(&header[148..156], format!("{sum:06o}\0 ").as_bytes());
+    }
+
+    #[test]
+    fn long_names_are_cut_to_the_name_field() {
+        let name = "x".repeat(150);
+        let header = ustar_header(&name, 0, 0);
+        assert!(header[..100].iter().all(|&b| b == b'x'));
+        assert_eq!(&header[100..108], b"0000644\0");
+    }
+
+    #[test]
+    fn extensions_follow_the_content_type() {
+        assert_eq!(
+            extension_for(Some("application/json; charset=utf-8")),
+            "json"
+        );
+        assert_eq!(extension_for(Some("image/svg+xml")), "svg");
+        assert_eq!(extension_for(Some("text/javascript")), "js");
+        assert_eq!(extension_for(None), "txt");
+    }
+}
@@ -0,0 +1,91 @@
+use std::pin::pin;
+use std::time::Duration;
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use futures::future::{self, Either};
+
+use crate::{Event, wait};
+
+/// When [`batch_events`] hands a batch to the consumer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchPolicy {
+    /// Send as soon as a batch holds this many events. `0` is treated as `1`.
+    pub max_events: usize,
+    /// Send a non-empty batch once its first event has waited this long.
+    pub flush_interval: Duration,
+}
+
+impl Default for BatchPolicy {
+    fn default() -> Self {
+        Self {
+            max_events: 100,
+            flush_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Group the events from `rx` into `Vec<Event>` chunks according to `policy`.
+///
+/// Batches are never empty. When `rx` closes, the pending partial batch is sent and the returned
+/// receiver closes.
+pub fn batch_events(
+    mut rx: mpsc::UnboundedReceiver<Event>,
+    policy: BatchPolicy,
+) -> mpsc::UnboundedReceiver<Vec<Event>> {
+    let (tx, out) = mpsc::unbounded();
+    let max_events = policy.max_events.max(1);
+
+    tokio::spawn(async move {
+        let mut batch = Vec::new();
+        loop {
+            let Some(first) = rx.next().await else {
+                return;
+            };
+            batch.push(first);
+
+            let deadline = wait::later(tokio::time::Instant::now(), policy.flush_interval);
+            let mut closed = false;
+            while batch.len() < max_events {
+                let sleep = pin!(tokio::time::sleep_until(deadline));
+                match future::select(rx.next(), sleep).await {
+                    Either::Left((Some(event), _)) => batch.push(event),
+                    Either::Left((None, _)) => {
+                        closed = true;
+                        break;
+                    }
+                    Either::Right(_) => break, // flush interval elapsed
+                }
+            }
+
+            let full = std::mem::replace(&mut batch, Vec::with_capacity(max_events));
+            if tx.unbounded_send(full).is_err() || closed {
+                return; // receiver dropped or source closed
+            }
+        }
+    });
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn huge_flush_intervals_still_batch() {
+        let (tx, rx) = mpsc::unbounded();
+        let policy = BatchPolicy {
+            max_events: 2,
+            flush_interval: Duration::MAX,
+        };
+        let mut batches = batch_events(rx, policy);
+        for url in ["a", "b", "c"] {
+            tx.unbounded_send(Event::builder().url(url).build())
+                .unwrap();
+        }
+        drop(tx);
+        let sizes: Vec<usize> = batches.by_ref().map(|batch| batch.len()).collect().await;
+        assert_eq!(sizes, [2, 1]);
+    }
+}
@@ -0,0 +1,133 @@
+//! `event-stream`: print the network responses of a running Chrome as JSON lines.
+//!
+//! Built with the `cli` feature. Connects to the DevTools endpoint given on the command line,
+//! captures from the first open tab (or a new one with `--open`) and writes every event to
+//! stdout until the page closes.
+
+use std::process::ExitCode;
+
+use chromiumoxide::browser::Browser;
+use chromiumoxide_event_stream::{EventStreamConfig, start_event_stream, write_jsonl};
+use futures::StreamExt;
+use tokio::io::BufWriter;
+
+const USAGE: &str = "\
+usage: event-stream [options] <endpoint>
+
+Print the network responses of a running Chrome as JSON lines.
+
+<endpoint> is the DevTools websocket URL (ws://...) or the HTTP debugging
+address (http://127.0.0.1:9222) of a browser started with
+--remote-debugging-port.
+
+options:
+  --url-filter <text>       only capture URLs containing <text>
+  --content-type <text>     only capture content types containing <text>
+  --exclude <text>          skip URLs containing <text>; repeatable
+  --same-origin             only capture requests to the page's own origin
+  --failed                  also emit requests that failed without a response
+  --open <url>              open <url> in a new tab and capture from it
+  --poll-ms <ms>            how often to drain the page, default 100
+  -h, --help                show this help";
+
+struct Args {
+    endpoint: String,
+    open: Option<String>,
+    config: EventStreamConfig,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut endpoint = None;
+    let mut open = None;
+    let mut config = EventStreamConfig {
+        poll_interval_ms: 100,
+        ..Default::default()
+    };
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+        match arg.as_str() {
+            "-h" | "--help" => return Err(String::new()),
+            "--url-filter" => config.url_substring_filter = Some(value(&arg)?),
+            "--content-type" => config.content_type_substring_filter = Some(value(&arg)?),
+            "--exclude" => config.url_exclude_filters.push(value(&arg)?),
+            "--same-origin" => config.same_origin_only = true,
+            "--failed" => config.capture_failed_requests = true,
+            "--open" => open = Some(value(&arg)?),
+            "--poll-ms" => {
+                config.poll_interval_ms = value(&arg)?
+                    .parse()
+                    .map_err(|_| "--poll-ms needs a number of milliseconds".to_string())?;
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown option {flag}")),
+            _ if endpoint.is_none() => endpoint = Some(arg),
+            _ => return Err(format!("unexpected argument {arg}")),
+        }
+    }
+    Ok(Args {
+        endpoint: endpoint.ok_or("missing <endpoint>")?,
+        open,
+        config,
+    })
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) if message.is_empty() => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("event-stream: {message}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("event-stream: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: Args) -> Result<(), String> {
+    let (mut browser, mut handler) = Browser::connect(args.endpoint.as_str())
+        .await
+        .map_err(|e| format!("connecting to {}: {e}", args.endpoint))?;
+    tokio::spawn(async move { while handler.next().await.is_some() {} });
+
+    let page = match args.open {
+        Some(url) => browser
+            .new_page(url.as_str())
+            .await
+            .map_err(|e| format!("opening {url}: {e}"))?,
+        None => {
+            // Tabs that were open before we connected have to be fetched, and are attached
+            // shortly after.
+            browser
+                .fetch_targets()
+                .await
+                .map_err(|e| format!("listing tabs: {e}"))?;
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            let pages = browser
+                .pages()
+                .await
+                .map_err(|e| format!("listing tabs: {e}"))?;
+            pages
+                .into_iter()
+                .next()
+                .ok_or("the browser has no open tab; pass --open <url>")?
+        }
+    };
+
+    let mut rx = start_event_stream(page, args.config)
+        .await
+        .map_err(|e| format!("starting capture: {e}"))?;
+    let mut stdout = BufWriter::new(tokio::io::stdout());
+    write_jsonl(&mut rx, &mut stdout)
+        .await
+        .map_err(|e| format!("writing events: {e}"))?;
+    Ok(())
+}
@@ -0,0 +1,101 @@
+//! Requests the browser refused to load.
+//!
+//! With [`EventStreamConfig::report_blocked_requests`](crate::EventStreamConfig::report_blocked_requests)
+//! set, the stream follows CDP `Network.loadingFailed` and reports every request blocked by a
+//! content security policy, mixed-content rules, CORS, an extension or the like as a
+//! [`BlockedRequest`] on the receiver from
+//! [`EventStreamHandle::take_blocked_requests`](crate::EventStreamHandle::take_blocked_requests).
+//! Unlike captured events this covers every resource type, scripts and images included, since
+//! the page never sees these requests fail.
+
+use std::sync::Arc;
+
+use chromiumoxide::cdp::browser_protocol::network::EventLoadingFailed;
+use serde::{Deserialize, Serialize};
+
+use crate::watch::{Follower, Notice, Request};
+use crate::{CaptureFilter, EventStreamConfig, handle};
+
+/// A request the browser refused to load, reported with
+/// [`EventStreamConfig::report_blocked_requests`](crate::EventStreamConfig::report_blocked_requests) set.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlockedRequest {
+    pub url: String,
+    pub method: String,
+    /// CDP resource type, e.g. `Script`, `Image` or `Fetch`.
+    #[serde(rename = "resourceType")]
+    pub resource_type: String,
+    /// Why the browser blocked it, as CDP names it: `csp`, `mixed-content`, `inspector`,
+    /// `subresource-filter`, `content-type` and so on, or `cors` for CORS failures.
+    pub reason: String,
+    /// The CORS check that failed, e.g. `PreflightInvalidStatus`, for `cors` blocks.
+    #[serde(rename = "corsError", default)]
+    pub cors_error: Option<String>,
+    /// The browser's network error, e.g. `net::ERR_BLOCKED_BY_CLIENT`.
+    #[serde(rename = "errorText")]
+    pub error_text: String,
+    /// URL of the document that made the request.
+    #[serde(rename = "pageUrl", default)]
+    pub page_url: Option<String>,
+    /// Wall-clock time the request was issued, in milliseconds since the Unix epoch.
+    #[serde(rename = "startedAt", default)]
+    pub started_at: Option<f64>,
+    /// The [`EventStreamConfig::label`] of the stream.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// Reports the blocked requests among those that failed.
+pub(crate) struct BlockedFollower {
+    pub(crate) config: Arc<EventStreamConfig>,
+    pub(crate) filter: Arc<CaptureFilter>,
+    pub(crate) shared: Arc<handle::Shared>,
+}
+
+impl Follower for BlockedFollower {
+    fn follow(&mut self, notice: &Notice, request: Option<&Request>) {
+        let (Notice::Failed(failed), Some(request)) = (notice, request) else {
+            return;
+        };
+        // Filter on the URLs as sent; redaction may have masked what a pattern matches.
+        let page_url = Some(request.document_url.as_str()).filter(|u| !u.is_empty());
+        if !self.filter.matches_url(&request.url, page_url) {
+            return;
+        }
+        let Some(blocked) = blocked_request(request, failed, &self.config) else {
+            return;
+        };
+        tracing::debug!(url = %blocked.url, reason = %blocked.reason, "request blocked");
+        self.shared.send_blocked_request(blocked);
+    }
+}
+
+// `None` for failures that are not blocks, e.g. DNS errors or cancellations.
+fn blocked_request(
+    request: &Request,
+    failed: &EventLoadingFailed,
+    config: &EventStreamConfig,
+) -> Option<BlockedRequest> {
+    let cors_error = failed
+        .cors_error_status
+        .as_ref()
+        .map(|status| status.cors_error.as_ref().to_string());
+    let reason = match (&failed.blocked_reason, &cors_error) {
+        (Some(reason), _) => reason.as_ref().to_string(),
+        (None, Some(_)) => "cors".to_string(),
+        (None, None) => return None,
+    };
+    let redaction = &config.redaction;
+    Some(BlockedRequest {
+        url: redaction.redact_url(&request.url),
+        method: request.method.clone(),
+        resource_type: failed.r#type.as_ref().to_string(),
+        reason,
+        cors_error,
+        error_text: failed.error_text.clone(),
+        page_url: (!request.document_url.is_empty())
+            .then(|| redaction.redact_url(&request.document_url)),
+        started_at: Some(request.started_at),
+        label: config.label.clone(),
+    })
+}
@@ -0,0 +1,174 @@
+use std::borrow::Cow;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::de::DeserializeOwned;
+
+use crate::{Error, Event};
+
+// Content types whose bytes are text even without a charset parameter, besides `text/*` and
+// the base64 `-text` variants such as `application/grpc-web-text`. The hooks read the same set
+// as text.
+const TEXT_TYPES: [&str; 5] = [
+    "json",
+    "xml",
+    "javascript",
+    "x-www-form-urlencoded",
+    "multipart/form-data",
+];
+
+impl Event {
+    /// Whether the body is binary data rather than text: its content type is not a text type
+    /// and declares no charset, or, without a content type, it arrived
+    /// [base64-encoded](Self::base64_encoded). [`text`](Self::text) fails for such bodies; use
+    /// [`bytes`](Self::bytes).
+    pub fn is_binary(&self) -> bool {
+        match self
+            .content_type
+            .as_deref()
+            .filter(|ct| !ct.trim().is_empty())
+        {
+            Some(content_type) => !is_text_type(content_type),
+            None => self.base64_encoded,
+        }
+    }
+
+    /// Length of the body in bytes, the decoded length for [base64](Self::base64_encoded)
+    /// bodies. Cheaper than `bytes()?.len()`, as nothing is decoded.
+    pub fn body_len(&self) -> usize {
+        if !self.base64_encoded {
+            return self.body.len();
+        }
+        let body = self.body.trim_end();
+        let padding = body.bytes().rev().take_while(|&b| b == b'=').count().min(2);
+        (body.len() * 3 / 4).saturating_sub(padding)
+    }
+
+    /// The raw body bytes, base64-decoded if needed.
+    pub fn bytes(&self) -> Result<Cow<'_, [u8]>, Error> {
+        if self.base64_encoded {
+            let bytes = STANDARD.decode(&self.body).map_err(Error::DecodeBase64)?;
+            Ok(Cow::Owned(bytes))
+        } else {
+            Ok(Cow::Borrowed(self.body.as_bytes()))
+        }
+    }
+
+    /// The body as text. Bodies the page read as text were already decoded by the browser;
+    /// base64-encoded ones are decoded here by the charset of their content type, UTF-8 unless
+    /// it names US-ASCII or ISO-8859-1. Fails with [`Error::BinaryBody`] if the body
+    /// [is binary](Self::is_binary), and with [`Error::DecodeText`] if it is not valid in its
+    /// charset or the charset is another one.
+    pub fn text(&self) -> Result<Cow<'_, str>, Error> {
+        if self.is_binary() {
+            return Err(Error::BinaryBody {
+                url: self.url.clone(),
+            });
+        }
+        if !self.base64_encoded {
+            return Ok(Cow::Borrowed(&self.body));
+        }
+        let bytes = self.bytes()?;
+        let decode_error = |message: String| Error::DecodeText {
+            url: self.url.clone(),
+            message,
+        };
+        let charset = self.content_type.as_deref().and_then(charset);
+        let text = match charset.as_deref() {
+            None | Some("utf-8" | "utf8") => {
+                String::from_utf8(bytes.into_owned()).map_err(|e| decode_error(e.to_string()))?
+            }
+            Some("us-ascii" | "ascii") if bytes.is_ascii() => {
+                String::from_utf8(bytes.into_owned()).expect("ASCII is UTF-8")
+            }
+            Some("us-ascii" | "ascii") => return Err(decode_error("not ASCII".to_string())),
+            Some("iso-8859-1" | "latin1") => bytes.iter().map(|&b| char::from(b)).collect(),
+            Some(other) => return Err(decode_error(format!("unsupported charset {other}"))),
+        };
+        Ok(Cow::Owned(text))
+    }
+
+    /// The response body parsed as JSON into `T`, e.g. a struct of the consumer's own. Parse
+    /// errors carry the event's URL; base64-encoded bodies are decoded first.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        serde_json::from_slice(&self.bytes()?).map_err(|source| Error::ParseBody {
+            url: self.url.clone(),
+            source,
+        })
+    }
+}
+
+fn is_text_type(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence.starts_with("text/")
+        || essence.ends_with("-text")
+        || TEXT_TYPES.iter().any(|t| essence.contains(t))
+        || charset(content_type).is_some()
+}
+
+// The lower-cased `charset` parameter of a content type.
+fn charset(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"').to_ascii_lowercase())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+    #[test]
+    fn png_body_is_binary() {
+        let event = Event::builder()
+            .content_type("image/png")
+            .bytes_body(PNG)
+            .build();
+        assert!(event.is_binary());
+        assert_eq!(event.bytes().unwrap().as_ref(), PNG);
+        assert!(matches!(event.text(), Err(Error::BinaryBody { .. })));
+    }
+
+    #[test]
+    fn body_len_counts_decoded_bytes() {
+        for len in 0..8 {
+            let event = Event::builder().bytes_body(&PNG[..len]).build();
+            assert_eq!(event.body_len(), len, "{:?}", event.body);
+        }
+        assert_eq!(Event::builder().body("héllo").build().body_len(), 6);
+    }
+
+    #[test]
+    fn content_type_decides_over_the_flag() {
+        let mangled = Event::builder()
+            .content_type("image/png")
+            .body("\u{fffd}PNG")
+            .build();
+        assert!(mangled.is_binary());
+        let text = Event::builder()
+            .content_type("application/grpc-web-text")
+            .body("AAAA")
+            .build();
+        assert!(!text.is_binary());
+        let untyped = Event::builder().bytes_body(PNG).build();
+        assert!(untyped.is_binary());
+    }
+
+    #[test]
+    fn text_decodes_by_charset() {
+        let event = Event::builder()
+            .content_type("text/plain; charset=ISO-8859-1")
+            .bytes_body(b"caf\xe9")
+            .build();
+        assert_eq!(event.text().unwrap(), "café");
+    }
+}
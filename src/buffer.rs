@@ -0,0 +1,222 @@
+//! A rolling window of recent events to look back at.
+//!
+//! [`CaptureBuffer`] keeps the last N events, the events of the last few minutes, or both, and
+//! answers simple queries over them. For interactive debugging, [`tee`] fills a shared buffer
+//! from a stream while passing the events on, so you can inspect what led up to a failure.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+
+use crate::Event;
+
+#[derive(Clone, Debug, Default)]
+pub struct CaptureBuffer {
+    events: VecDeque<(Instant, Event)>,
+    max_events: Option<usize>,
+    max_age: Option<Duration>,
+}
+
+impl CaptureBuffer {
+    /// Keep at most the last `max_events` events.
+    pub fn new(max_events: usize) -> Self {
+        Self {
+            max_events: Some(max_events),
+            ..Self::default()
+        }
+    }
+
+    /// Keep only events pushed within the last `max_age`, however many there are.
+    pub fn with_max_age(max_age: Duration) -> Self {
+        Self {
+            max_age: Some(max_age),
+            ..Self::default()
+        }
+    }
+
+    /// Also drop events older than `max_age`.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Add `event`, evicting the oldest events past either limit.
+    pub fn push(&mut self, event: Event) {
+        self.events.push_back((Instant::now(), event));
+        if let Some(max) = self.max_events {
+            while self.events.len() > max {
+                self.events.pop_front();
+            }
+        }
+        self.evict_expired();
+    }
+
+    fn evict_expired(&mut self) {
+        let Some(max_age) = self.max_age else {
+            return;
+        };
+        while self
+            .events
+            .front()
+            .is_some_and(|(pushed, _)| pushed.elapsed() > max_age)
+        {
+            self.events.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// The retained events, oldest first. Events past `max_age` are skipped even if no push has
+    /// evicted them yet.
+    pub fn iter(&self) -> impl Iterator<Item = &Event> {
+        let max_age = self.max_age;
+        self.events
+            .iter()
+            .filter(move |(pushed, _)| max_age.is_none_or(|age| pushed.elapsed() <= age))
+            .map(|(_, event)| event)
+    }
+
+    pub fn by_url_contains(&self, needle: &str) -> Vec<&Event> {
+        self.iter().filter(|ev| ev.url.contains(needle)).collect()
+    }
+
+    pub fn by_status(&self, status: u16) -> Vec<&Event> {
+        self.iter().filter(|ev| ev.status == Some(status)).collect()
+    }
+
+    /// Events whose response arrived at or after `timestamp`, in milliseconds since the Unix
+    /// epoch (see [`Event::received_at`]). Events without a receive time fall back to
+    /// [`Event::started_at`]; events with neither are left out.
+    pub fn since(&self, timestamp: f64) -> Vec<&Event> {
+        self.iter()
+            .filter(|ev| {
+                ev.received_at
+                    .or(ev.started_at)
+                    .is_some_and(|t| t >= timestamp)
+            })
+            .collect()
+    }
+}
+
+/// Forward every event from `rx`, keeping a copy in `buffer`.
+pub fn tee(
+    mut rx: mpsc::UnboundedReceiver<Event>,
+    buffer: Arc<Mutex<CaptureBuffer>>,
+) -> mpsc::UnboundedReceiver<Event> {
+    let (tx, out) = mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some(event) = rx.next().await {
+            buffer
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(event.clone());
+            if tx.unbounded_send(event).is_err() {
+                break; // receiver dropped
+            }
+        }
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(url: &str, status: u16) -> Event {
+        Event::builder().url(url).status(status).build()
+    }
+
+    fn urls(events: Vec<&Event>) -> Vec<&str> {
+        events.into_iter().map(|ev| ev.url.as_str()).collect()
+    }
+
+    #[test]
+    fn keeps_the_last_events_and_queries_them() {
+        let mut buffer = CaptureBuffer::new(3);
+        for (i, status) in [200, 404, 200, 500].into_iter().enumerate() {
+            buffer.push(event(&format!("https://example.com/{i}"), status));
+        }
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(
+            urls(buffer.by_url_contains("example.com")),
+            [
+                "https://example.com/1",
+                "https://example.com/2",
+                "https://example.com/3"
+            ]
+        );
+        assert_eq!(urls(buffer.by_status(200)), ["https://example.com/2"]);
+        buffer.clear();
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn since_falls_back_to_the_start_time() {
+        let mut buffer = CaptureBuffer::new(10);
+        let mut received = event("https://example.com/received", 200);
+        received.received_at = Some(2_000.0);
+        received.started_at = Some(500.0);
+        buffer.push(received);
+        buffer.push(
+            Event::builder()
+                .url("https://example.com/started")
+                .started_at(1_500.0)
+                .build(),
+        );
+        buffer.push(event("https://example.com/untimed", 200));
+        assert_eq!(
+            urls(buffer.since(1_000.0)),
+            [
+                "https://example.com/received",
+                "https://example.com/started"
+            ]
+        );
+        assert_eq!(
+            urls(buffer.since(1_800.0)),
+            ["https://example.com/received"]
+        );
+    }
+
+    #[test]
+    fn expired_events_are_hidden_then_evicted() {
+        let mut buffer = CaptureBuffer::with_max_age(Duration::from_millis(50));
+        buffer.push(event("https://example.com/old", 200));
+        std::thread::sleep(Duration::from_millis(80));
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(buffer.iter().count(), 0);
+        buffer.push(event("https://example.com/new", 200));
+        assert_eq!(urls(buffer.iter().collect()), ["https://example.com/new"]);
+        assert_eq!(buffer.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn tee_forwards_and_keeps_a_copy() {
+        let (tx, rx) = mpsc::unbounded();
+        let buffer = Arc::new(Mutex::new(CaptureBuffer::new(10)));
+        let mut out = tee(rx, buffer.clone());
+        tx.unbounded_send(event("https://example.com/a", 200))
+            .unwrap();
+        tx.unbounded_send(event("https://example.com/b", 204))
+            .unwrap();
+        drop(tx);
+        let forwarded: Vec<_> = out.by_ref().map(|ev| ev.url).collect().await;
+        assert_eq!(
+            forwarded,
+            ["https://example.com/a", "https://example.com/b"]
+        );
+        assert_eq!(buffer.lock().unwrap().by_status(204).len(), 1);
+    }
+}
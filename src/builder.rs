@@ -0,0 +1,126 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::Serialize;
+
+use crate::{Event, Header};
+
+impl Event {
+    /// Start building an event by hand, e.g. in a unit test. Fields not set stay empty.
+    pub fn builder() -> EventBuilder {
+        EventBuilder::default()
+    }
+}
+
+/// Builds an [`Event`] field by field, see [`Event::builder`]. Fields added to `Event` later
+/// start out empty, so code using the builder keeps compiling.
+#[derive(Clone, Debug, Default)]
+pub struct EventBuilder {
+    event: Event,
+}
+
+impl EventBuilder {
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.event.url = url.into();
+        self
+    }
+
+    pub fn method(mut self, method: impl Into<String>) -> Self {
+        self.event.method = Some(method.into());
+        self
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.event.status = Some(status);
+        self
+    }
+
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.event.content_type = Some(content_type.into());
+        self
+    }
+
+    /// A text response body.
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.event.body = body.into();
+        self.event.base64_encoded = false;
+        self
+    }
+
+    /// A binary response body, stored base64-encoded as the page does.
+    pub fn bytes_body(mut self, body: impl AsRef<[u8]>) -> Self {
+        self.event.body = STANDARD.encode(body);
+        self.event.base64_encoded = true;
+        self
+    }
+
+    /// `value` serialized as the response body, with an `application/json` content type unless
+    /// one was set.
+    ///
+    /// # Panics
+    ///
+    /// If `value` cannot be serialized, e.g. a map with non-string keys.
+    pub fn json_body(mut self, value: impl Serialize) -> Self {
+        self.event.body = serde_json::to_string(&value).expect("json_body: value is not JSON");
+        self.event.base64_encoded = false;
+        self.event
+            .content_type
+            .get_or_insert_with(|| "application/json".to_string());
+        self
+    }
+
+    pub fn request_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.event.request_headers.push(Header {
+            name: name.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn response_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.event.response_headers.push(Header {
+            name: name.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn request_body(mut self, body: impl Into<String>) -> Self {
+        self.event.request_body = Some(body.into());
+        self
+    }
+
+    /// Mark the request as failed without a response, see [`Event::error`].
+    pub fn error(mut self, error: impl Into<String>) -> Self {
+        self.event.error = Some(error.into());
+        self
+    }
+
+    pub fn started_at(mut self, millis: f64) -> Self {
+        self.event.started_at = Some(millis);
+        self
+    }
+
+    pub fn duration_ms(mut self, millis: f64) -> Self {
+        self.event.duration_ms = Some(millis);
+        self
+    }
+
+    pub fn page_url(mut self, url: impl Into<String>) -> Self {
+        self.event.page_url = Some(url.into());
+        self
+    }
+
+    pub fn navigation_id(mut self, id: u64) -> Self {
+        self.event.navigation_id = Some(id);
+        self
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.event.label = Some(label.into());
+        self
+    }
+
+    pub fn build(self) -> Event {
+        self.event
+    }
+}
@@ -0,0 +1,208 @@
+//! Topic-based fan-out of one event stream to many in-process consumers.
+//!
+//! An [`EventBus`] sorts each published event into topics, named predicates such as
+//! `"api.users"` or `"errors"`, and delivers it to every subscriber whose pattern matches one of
+//! them. Patterns use the same glob syntax as intercept rules, so `"api.*"` subscribes to every
+//! `api.` topic and `"*"` to all of them. Each subscriber gets an event at most once, however
+//! many of its topics match.
+//!
+//! Two topics are built in: `"all"` matches every event, and `"errors"` matches failed requests
+//! and responses with a status of 400 or more.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::Event;
+use crate::intercept::glob_matches;
+
+type Predicate = dyn Fn(&Event) -> bool + Send + Sync;
+
+/// Cheap to clone; clones share topics and subscribers.
+#[derive(Clone)]
+pub struct EventBus {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    topics: Vec<(String, Arc<Predicate>)>,
+    subscribers: Vec<(String, mpsc::UnboundedSender<Event>)>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.lock();
+        f.debug_struct("EventBus")
+            .field(
+                "topics",
+                &inner
+                    .topics
+                    .iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>(),
+            )
+            .field("subscribers", &inner.subscribers.len())
+            .finish()
+    }
+}
+
+impl EventBus {
+    /// A bus with only the built-in `"all"` and `"errors"` topics.
+    pub fn new() -> Self {
+        let bus = Self {
+            inner: Arc::new(Mutex::new(Inner {
+                topics: Vec::new(),
+                subscribers: Vec::new(),
+            })),
+        };
+        bus.topic("all", |_| true).topic("errors", |ev| {
+            ev.error.is_some() || ev.status.is_some_and(|status| status >= 400)
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add the topic `name`, covering events for which `predicate` is true.
+    pub fn topic(
+        self,
+        name: impl Into<String>,
+        predicate: impl Fn(&Event) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.lock().topics.push((name.into(), Arc::new(predicate)));
+        self
+    }
+
+    /// Add the topic `name`, covering events whose URL contains `needle`.
+    pub fn url_topic(self, name: impl Into<String>, needle: impl Into<String>) -> Self {
+        let needle = needle.into();
+        self.topic(name, move |ev| ev.url.contains(&needle))
+    }
+
+    /// Receive every event published from now on to a topic matching `pattern`. Dropping the
+    /// receiver unsubscribes.
+    pub fn subscribe(&self, pattern: impl Into<String>) -> mpsc::UnboundedReceiver<Event> {
+        let (tx, rx) = mpsc::unbounded();
+        self.lock().subscribers.push((pattern.into(), tx));
+        rx
+    }
+
+    /// The topics `event` belongs to, in the order they were added.
+    pub fn topics_of(&self, event: &Event) -> Vec<String> {
+        self.lock()
+            .topics
+            .iter()
+            .filter(|(_, predicate)| predicate(event))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Deliver `event` to every matching subscriber and return how many received it.
+    pub fn publish(&self, event: &Event) -> usize {
+        let topics = self.topics_of(event);
+        let mut inner = self.lock();
+        let mut delivered = 0;
+        inner.subscribers.retain(|(pattern, tx)| {
+            if !topics.iter().any(|topic| glob_matches(pattern, topic)) {
+                return !tx.is_closed();
+            }
+            let sent = tx.unbounded_send(event.clone()).is_ok();
+            delivered += usize::from(sent);
+            sent
+        });
+        delivered
+    }
+
+    /// Publish every event from `rx` until it closes.
+    pub fn attach(&self, mut rx: mpsc::UnboundedReceiver<Event>) -> JoinHandle<()> {
+        let bus = self.clone();
+        tokio::spawn(async move {
+            while let Some(event) = rx.next().await {
+                bus.publish(&event);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(url: &str, status: u16) -> Event {
+        Event::builder().url(url).status(status).build()
+    }
+
+    #[test]
+    fn topics_include_the_built_in_ones() {
+        let bus = EventBus::new().url_topic("api.users", "/api/users");
+        assert_eq!(
+            bus.topics_of(&event("https://example.com/api/users", 200)),
+            ["all", "api.users"]
+        );
+        assert_eq!(
+            bus.topics_of(&event("https://example.com/api/users", 500)),
+            ["all", "errors", "api.users"]
+        );
+        let failed = Event::builder()
+            .url("https://example.com/")
+            .error("net::ERR_FAILED")
+            .build();
+        assert_eq!(bus.topics_of(&failed), ["all", "errors"]);
+    }
+
+    #[test]
+    fn publish_delivers_once_per_subscriber_and_drops_closed_ones() {
+        let bus = EventBus::new()
+            .url_topic("api.users", "/api/users")
+            .url_topic("api.orders", "/api/orders");
+        let mut api = bus.subscribe("api.*");
+        let mut everything = bus.subscribe("*");
+        let mut orders = bus.subscribe("api.orders");
+        let closed = bus.subscribe("all");
+        drop(closed);
+
+        assert_eq!(bus.publish(&event("https://example.com/api/users", 200)), 2);
+        assert_eq!(
+            api.try_next().unwrap().unwrap().url,
+            "https://example.com/api/users"
+        );
+        assert!(api.try_next().is_err());
+        assert!(everything.try_next().unwrap().is_some());
+        assert!(everything.try_next().is_err());
+        assert!(orders.try_next().is_err());
+        assert_eq!(bus.lock().subscribers.len(), 3);
+
+        drop(orders);
+        assert_eq!(bus.publish(&event("https://example.com/", 200)), 1);
+        assert_eq!(bus.lock().subscribers.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn attach_publishes_until_the_stream_closes() {
+        let bus = EventBus::new();
+        let mut errors = bus.subscribe("errors");
+        let (tx, rx) = mpsc::unbounded();
+        let task = bus.attach(rx);
+        tx.unbounded_send(event("https://example.com/ok", 200))
+            .unwrap();
+        tx.unbounded_send(event("https://example.com/missing", 404))
+            .unwrap();
+        drop(tx);
+        task.await.unwrap();
+        assert_eq!(
+            errors.next().await.unwrap().url,
+            "https://example.com/missing"
+        );
+        assert!(errors.try_next().is_err());
+    }
+}
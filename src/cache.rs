@@ -0,0 +1,102 @@
+//! Where responses were served from.
+//!
+//! With [`EventStreamConfig::tag_cache_source`](crate::EventStreamConfig::tag_cache_source) set,
+//! the stream follows CDP `Network.responseReceived` and `Network.requestServedFromCache` and
+//! tags each event with the [`CacheSource`] of its response. Page scripts cannot tell a cached
+//! response from a fresh one, so the tag is matched to the event by URL, in the order the
+//! responses arrived. Responses to requests that started before the event's request are skipped
+//! and forgotten: they belong to requests the stream did not capture, such as the document
+//! itself or an image at the same URL.
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::watch::{Follower, Notice, Request, UrlQueue};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheSource {
+    Network,
+    DiskCache,
+    MemoryCache,
+    ServiceWorker,
+}
+
+impl CacheSource {
+    /// Served from the browser's HTTP cache, on disk or in memory.
+    pub fn is_cache_hit(self) -> bool {
+        matches!(self, Self::DiskCache | Self::MemoryCache)
+    }
+}
+
+// Sources of responses seen over CDP, waiting for their events.
+pub(crate) type CacheSources = UrlQueue<CacheSource>;
+
+/// Tags responses with their source as CDP reports them.
+pub(crate) struct CacheFollower(pub(crate) Arc<CacheSources>);
+
+impl Follower for CacheFollower {
+    fn follow(&mut self, notice: &Notice, request: Option<&Request>) {
+        let Notice::Response(event) = notice else {
+            return;
+        };
+        let response = &event.response;
+        let source = if response.from_service_worker == Some(true) {
+            CacheSource::ServiceWorker
+        } else if response.from_disk_cache == Some(true) {
+            CacheSource::DiskCache
+        } else if request.is_some_and(|r| r.served_from_cache) {
+            CacheSource::MemoryCache
+        } else {
+            CacheSource::Network
+        };
+        let started_at = request.map(|r| r.started_at);
+        self.0.push(response.url.clone(), started_at, source);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watch::{Requests, events};
+
+    #[test]
+    fn take_skips_responses_to_earlier_requests() {
+        let sources = CacheSources::default();
+        let url = "https://example.com/logo.png".to_string();
+        // An `<img>` load the hooks never saw, then the fetch that was captured.
+        sources.push(url.clone(), Some(1_000.0), CacheSource::Network);
+        sources.push(url.clone(), Some(2_000.5), CacheSource::MemoryCache);
+        assert_eq!(
+            sources.take(&url, Some(2_000.0)),
+            Some(CacheSource::MemoryCache)
+        );
+        // The stale entry went with it.
+        assert_eq!(sources.take(&url, None), None);
+
+        sources.push(url.clone(), None, CacheSource::DiskCache);
+        assert_eq!(
+            sources.take(&url, Some(5_000.0)),
+            Some(CacheSource::DiskCache)
+        );
+    }
+
+    #[test]
+    fn redirected_responses_match_the_first_hop_start() {
+        let sources = Arc::new(CacheSources::default());
+        let follower = CacheFollower(Arc::clone(&sources));
+        let mut requests = Requests::new(vec![Box::new(follower)]);
+        let first = "https://example.com/logo";
+        let last = "https://cdn.example.com/logo.png";
+        requests.handle(&events::sent("3", first, 2.0, "Low", None));
+        requests.handle(&events::sent("3", last, 2.5, "Low", Some(first)));
+        requests.handle(&events::response("3", last, true));
+
+        // The hooks saw the final URL and the time the fetch started.
+        assert_eq!(
+            sources.take(last, Some(2_000.0)),
+            Some(CacheSource::DiskCache)
+        );
+    }
+}
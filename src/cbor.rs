@@ -0,0 +1,155 @@
+//! CBOR (RFC 8949) event encoding, enabled by the `cbor` feature.
+//!
+//! Events are encoded through their `Serialize` representation, so field names match the JSONL
+//! export. The framed writer prefixes each encoded event with its length as a big-endian `u32`.
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use serde_json::Value;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::{Error, Event};
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+
+/// Encode a single event as a CBOR data item.
+pub fn to_cbor(event: &Event) -> Result<Vec<u8>, Error> {
+    let value = serde_json::to_value(event).map_err(Error::SerializeJson)?;
+    let mut out = Vec::with_capacity(event.body.len() + 256);
+    encode_value(&value, &mut out);
+    Ok(out)
+}
+
+/// Write every event from the receiver as a length-prefixed CBOR frame until the stream closes.
+/// Returns the number of frames written.
+pub async fn write_cbor_frames<W: AsyncWrite + Unpin>(
+    rx: &mut mpsc::UnboundedReceiver<Event>,
+    writer: &mut W,
+) -> Result<usize, Error> {
+    let mut written = 0;
+    while let Some(event) = rx.next().await {
+        let payload = to_cbor(&event)?;
+        let len = u32::try_from(payload.len()).map_err(|_| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "encoded event exceeds u32::MAX bytes",
+            ))
+        })?;
+        writer
+            .write_all(&len.to_be_bytes())
+            .await
+            .map_err(Error::Io)?;
+        writer.write_all(&payload).await.map_err(Error::Io)?;
+        writer.flush().await.map_err(Error::Io)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+fn encode_value(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(0xf6),
+        Value::Bool(false) => out.push(0xf4),
+        Value::Bool(true) => out.push(0xf5),
+        Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                encode_head(MAJOR_UNSIGNED, u, out);
+            } else if let Some(i) = n.as_i64() {
+                // Negative integers are stored as -1 - n.
+                encode_head(MAJOR_NEGATIVE, (-1 - i) as u64, out);
+            } else {
+                out.push(0xfb);
+                out.extend_from_slice(&n.as_f64().unwrap_or(f64::NAN).to_be_bytes());
+            }
+        }
+        Value::String(s) => encode_text(s, out),
+        Value::Array(items) => {
+            encode_head(MAJOR_ARRAY, items.len() as u64, out);
+            for item in items {
+                encode_value(item, out);
+            }
+        }
+        Value::Object(map) => {
+            encode_head(MAJOR_MAP, map.len() as u64, out);
+            for (key, item) in map {
+                encode_text(key, out);
+                encode_value(item, out);
+            }
+        }
+    }
+}
+
+fn encode_text(s: &str, out: &mut Vec<u8>) {
+    encode_head(MAJOR_TEXT, s.len() as u64, out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+// Initial byte plus the shortest big-endian argument encoding.
+fn encode_head(major: u8, n: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    if n < 24 {
+        out.push(major | n as u8);
+    } else if let Ok(n) = u8::try_from(n) {
+        out.extend_from_slice(&[major | 24, n]);
+    } else if let Ok(n) = u16::try_from(n) {
+        out.push(major | 25);
+        out.extend_from_slice(&n.to_be_bytes());
+    } else if let Ok(n) = u32::try_from(n) {
+        out.push(major | 26);
+        out.extend_from_slice(&n.to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn encoded(value: Value) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_value(&value, &mut out);
+        out
+    }
+
+    #[test]
+    fn heads_use_the_shortest_argument() {
+        assert_eq!(encoded(json!(23)), [0x17]);
+        assert_eq!(encoded(json!(24)), [0x18, 24]);
+        assert_eq!(encoded(json!(256)), [0x19, 0x01, 0x00]);
+        assert_eq!(encoded(json!(65_536)), [0x1a, 0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(
+            encoded(json!(u64::MAX)),
+            [0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(encoded(json!(-1)), [0x20]);
+        assert_eq!(encoded(json!(-500)), [0x39, 0x01, 0xf3]);
+    }
+
+    #[test]
+    fn values_follow_rfc_8949() {
+        assert_eq!(encoded(json!(null)), [0xf6]);
+        assert_eq!(encoded(json!([true, false])), [0x82, 0xf5, 0xf4]);
+        assert_eq!(encoded(json!(1.5)), [0xfb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(encoded(json!({"a": "b"})), [0xa1, 0x61, b'a', 0x61, b'b']);
+    }
+
+    #[test]
+    fn events_encode_as_maps_of_their_json_fields() {
+        let event = Event::builder().url("https://example.com/").build();
+        let fields = serde_json::to_value(&event)
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .len();
+        let cbor = to_cbor(&event).unwrap();
+        // Events have more than 23 fields, so the map length takes one extra byte.
+        assert_eq!(cbor[..2], [0xb8, u8::try_from(fields).unwrap()]);
+    }
+}
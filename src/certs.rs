@@ -0,0 +1,76 @@
+//! Warnings for TLS certificates close to expiry.
+//!
+//! With [`EventStreamConfig::certificate_expiry_window`](crate::EventStreamConfig::certificate_expiry_window)
+//! set, the stream reads the security details the browser reports with every response and sends a
+//! [`CaptureError::CertificateExpiring`] for each certificate that expires within the window.
+//! Page scripts cannot see certificates, so this uses CDP `Network.responseReceived` and covers
+//! every response the page loads, captured or not.
+
+use std::collections::HashSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::channel::mpsc;
+
+use crate::watch::{Follower, Notice, Request};
+use crate::{CaptureError, Redaction};
+
+/// Warns about the certificates of responses that expire within `window`.
+pub(crate) struct CertificateFollower {
+    window: Duration,
+    redaction: Redaction,
+    err_tx: mpsc::Sender<CaptureError>,
+    // Each certificate is reported once per stream.
+    reported: HashSet<(String, i64)>,
+}
+
+impl CertificateFollower {
+    pub(crate) fn new(
+        window: Duration,
+        redaction: Redaction,
+        err_tx: mpsc::Sender<CaptureError>,
+    ) -> Self {
+        Self {
+            window,
+            redaction,
+            err_tx,
+            reported: HashSet::new(),
+        }
+    }
+}
+
+impl Follower for CertificateFollower {
+    fn follow(&mut self, notice: &Notice, _: Option<&Request>) {
+        let Notice::Response(event) = notice else {
+            return;
+        };
+        let Some(details) = &event.response.security_details else {
+            return;
+        };
+        let expires_at = *details.valid_to.inner() as i64;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        let window = i64::try_from(self.window.as_secs()).unwrap_or(i64::MAX);
+        if expires_at.saturating_sub(now) > window
+            || !self
+                .reported
+                .insert((details.subject_name.clone(), expires_at))
+        {
+            return;
+        }
+        let url = self.redaction.redact_url(&event.response.url);
+        tracing::warn!(
+            url = %url,
+            subject = %details.subject_name,
+            expires_at,
+            "certificate expiring soon"
+        );
+        let _ = self.err_tx.try_send(CaptureError::CertificateExpiring {
+            url,
+            subject: details.subject_name.clone(),
+            issuer: details.issuer.clone(),
+            expires_at,
+        });
+    }
+}
@@ -0,0 +1,170 @@
+//! Preset `EventStreamConfig`s, and loading one from JSON and the environment.
+//!
+//! Other formats are not built in; `EventStreamConfig` implements `Deserialize`, so TOML or
+//! YAML files load with the matching serde crate and the same field names.
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{Error, EventStreamConfig};
+
+// Prefix of the environment variables read by `EventStreamConfig::from_env`.
+const ENV_PREFIX: &str = "EVENT_STREAM_";
+
+impl EventStreamConfig {
+    /// JSON API traffic: responses with a JSON content type, including `+json` types and NDJSON,
+    /// plus requests that failed without a response.
+    pub fn json_api() -> Self {
+        Self {
+            content_type_substring_filter: Some("json".to_string()),
+            capture_failed_requests: true,
+            ..Self::default()
+        }
+    }
+
+    /// HTML and XML documents fetched by scripts, e.g. partials or feeds. Page navigations are
+    /// not fetch or XHR requests and are not captured.
+    pub fn documents() -> Self {
+        Self {
+            content_type_filters: vec![
+                "text/html".to_string(),
+                "application/xhtml+xml".to_string(),
+                "text/xml".to_string(),
+                "application/xml".to_string(),
+            ],
+            ..Self::default()
+        }
+    }
+
+    /// Images, audio and video fetched by scripts, including HLS playlists and DASH manifests as
+    /// loaded by JavaScript players; see [`media`](crate::media). Elements such as `<img>` load
+    /// outside fetch and XHR and are not captured.
+    pub fn media() -> Self {
+        Self {
+            content_type_filters: vec![
+                "image/".to_string(),
+                "audio/".to_string(),
+                "video/".to_string(),
+                "mpegurl".to_string(),
+                "dash+xml".to_string(),
+            ],
+            ..Self::default()
+        }
+    }
+
+    /// Every fetch and XHR response, and every request that failed without one. Credential
+    /// headers are still [redacted](Self::redaction).
+    pub fn everything() -> Self {
+        Self {
+            capture_failed_requests: true,
+            ..Self::default()
+        }
+    }
+
+    /// Parse a config from JSON. Fields use their Rust names, durations are given in
+    /// milliseconds with an `_ms` suffix (`certificate_expiry_window_ms`, `retry.backoff_ms`,
+    /// `fault_injection.max_delay_ms`), and missing fields keep their defaults. Intercept rules,
+    /// mocks and redaction scrubbers hold code or loaded data and are not read.
+    ///
+    /// ```json
+    /// { "url_substring_filter": "/api/", "same_origin_only": true, "retry": { "attempts": 3 } }
+    /// ```
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json).map_err(Error::ParseJson)
+    }
+
+    /// Read a JSON config file, see [`from_json`](Self::from_json).
+    pub async fn read_json_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let json = tokio::fs::read_to_string(path).await.map_err(Error::Io)?;
+        Self::from_json(&json)
+    }
+
+    /// Build a config from `EVENT_STREAM_*` environment variables, one per field: the field
+    /// name upper-cased after the prefix, e.g. `EVENT_STREAM_POLL_INTERVAL_MS=250`. Values are
+    /// read as JSON where they parse as JSON, and as plain strings otherwise, so lists and nested
+    /// settings are written as in [`from_json`](Self::from_json), e.g.
+    /// `EVENT_STREAM_URL_EXCLUDE_FILTERS='["ads.", "tracker."]'`. Quote strings that would parse
+    /// as something else, e.g. `EVENT_STREAM_LABEL='"42"'`. Unknown `EVENT_STREAM_*` variables
+    /// are an error, as are ones whose value is not valid Unicode; other variables are ignored
+    /// whatever they hold.
+    pub fn from_env() -> Result<Self, Error> {
+        Self::from_vars(std::env::vars_os())
+    }
+
+    fn from_vars(vars: impl IntoIterator<Item = (OsString, OsString)>) -> Result<Self, Error> {
+        let mut fields = serde_json::Map::new();
+        for (name, value) in vars {
+            let Some(field) = name.to_str().and_then(|name| name.strip_prefix(ENV_PREFIX)) else {
+                continue;
+            };
+            let value = value.into_string().map_err(|_| {
+                Error::InvalidEnv(format!("{ENV_PREFIX}{field} is not valid Unicode"))
+            })?;
+            let value = serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value));
+            fields.insert(field.to_ascii_lowercase(), value);
+        }
+        serde_json::from_value(fields.into()).map_err(Error::ParseJson)
+    }
+}
+
+// A `Duration` given as a number of milliseconds.
+pub(crate) fn millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_millis)
+}
+
+pub(crate) fn opt_millis<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    Ok(Option::<u64>::deserialize(deserializer)?.map(Duration::from_millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(OsString, OsString)> {
+        vars.iter().map(|(k, v)| (k.into(), v.into())).collect()
+    }
+
+    #[test]
+    fn reads_prefixed_variables_as_json_or_strings() {
+        let config = EventStreamConfig::from_vars(vars(&[
+            ("EVENT_STREAM_POLL_INTERVAL_MS", "250"),
+            ("EVENT_STREAM_LABEL", "checkout"),
+            (
+                "EVENT_STREAM_URL_EXCLUDE_FILTERS",
+                r#"["ads.", "tracker."]"#,
+            ),
+            ("PATH", "/usr/bin"),
+        ]))
+        .unwrap();
+        assert_eq!(config.poll_interval_ms, 250);
+        assert_eq!(config.label.as_deref(), Some("checkout"));
+        assert_eq!(config.url_exclude_filters, ["ads.", "tracker."]);
+    }
+
+    #[test]
+    fn unknown_variables_are_an_error() {
+        let result = EventStreamConfig::from_vars(vars(&[("EVENT_STREAM_NOPE", "1")]));
+        assert!(matches!(result, Err(Error::ParseJson(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_prefixed_variables_must_be_unicode() {
+        use std::os::unix::ffi::OsStringExt;
+        let garbage = || OsString::from_vec(vec![0xff, 0xfe]);
+
+        let unrelated = vec![(garbage(), garbage())];
+        assert!(EventStreamConfig::from_vars(unrelated).is_ok());
+
+        let ours = vec![("EVENT_STREAM_LABEL".into(), garbage())];
+        assert!(matches!(
+            EventStreamConfig::from_vars(ours),
+            Err(Error::InvalidEnv(_))
+        ));
+    }
+}
@@ -0,0 +1,374 @@
+//! A cookie jar rebuilt from captured traffic.
+//!
+//! With [`EventStreamConfig::track_cookies`](crate::EventStreamConfig::track_cookies) set, a
+//! stream keeps one, see [`EventStreamHandle::cookies`](crate::EventStreamHandle::cookies).
+//! Browsers hide `Set-Cookie` and the `Cookie` request header from page scripts, so the stream
+//! fills the jar from CDP `Network.requestWillBeSentExtraInfo` and
+//! `Network.responseReceivedExtraInfo` instead: the cookies each request carried and the
+//! `Set-Cookie` lines each response sent, minus those the browser blocked. The jar follows the
+//! session as the capture runs, for every request of the page, whether or not the stream's
+//! filters capture it.
+//!
+//! [`CookieJar::observe`] applies the same headers from an [`Event`], for events built by hand
+//! or loaded from fixtures. For the browser's whole store use CDP `Network.getCookies`.
+
+use std::sync::Arc;
+
+use chromiumoxide::cdp::browser_protocol::network;
+use serde::Serialize;
+use url::Url;
+
+use crate::watch::{Follower, Notice, Request};
+use crate::{Event, handle, websocket};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// The `Domain` attribute without its leading dot, or the request host for host-only cookies.
+    pub domain: String,
+    pub path: String,
+    /// The raw `Expires` attribute.
+    pub expires: Option<String>,
+    pub max_age: Option<i64>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the jar from `event`'s `Cookie` request headers and `Set-Cookie` response headers.
+    /// A `Set-Cookie` with `Max-Age` of zero or less removes the cookie.
+    pub fn observe(&mut self, event: &Event) {
+        let Ok(url) = Url::parse(&event.url) else {
+            return;
+        };
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+
+        for header in &event.request_headers {
+            if header.name.eq_ignore_ascii_case("cookie") {
+                for (name, value) in parse_cookie_header(&header.value) {
+                    // The request does not say which domain or path the cookie was scoped to.
+                    self.learn(Cookie {
+                        name,
+                        value,
+                        domain: host.clone(),
+                        path: "/".to_string(),
+                        expires: None,
+                        max_age: None,
+                        secure: false,
+                        http_only: false,
+                        same_site: None,
+                    });
+                }
+            }
+        }
+
+        for header in &event.response_headers {
+            if header.name.eq_ignore_ascii_case("set-cookie") {
+                for line in split_set_cookie(&header.value) {
+                    if let Some(cookie) = parse_set_cookie(line, &url, &host) {
+                        self.set(cookie);
+                    }
+                }
+            }
+        }
+    }
+
+    // A cookie a request carried. Cookies the jar already knows take the new value, which a
+    // script may have changed through `document.cookie`, and keep the attributes a
+    // `Set-Cookie` gave them. The same path is preferred, for cookies set on several.
+    fn learn(&mut self, cookie: Cookie) {
+        let same = |c: &Cookie| c.name == cookie.name && c.domain == cookie.domain;
+        let known = self
+            .cookies
+            .iter()
+            .position(|c| same(c) && c.path == cookie.path)
+            .or_else(|| self.cookies.iter().position(same));
+        match known {
+            Some(i) => self.cookies[i].value = cookie.value,
+            None => self.cookies.push(cookie),
+        }
+    }
+
+    // Apply one `Set-Cookie` line of a response from `url`.
+    fn set_line(&mut self, url: &str, line: &str) {
+        let Ok(url) = Url::parse(url) else {
+            return;
+        };
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        if let Some(cookie) = parse_set_cookie(line, &url, &host) {
+            self.set(cookie);
+        }
+    }
+
+    fn set(&mut self, cookie: Cookie) {
+        self.cookies.retain(|c| {
+            !(c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path)
+        });
+        if cookie.max_age.is_none_or(|age| age > 0) {
+            self.cookies.push(cookie);
+        }
+    }
+
+    /// Every cookie in the jar, oldest first.
+    pub fn cookies(&self) -> &[Cookie] {
+        &self.cookies
+    }
+
+    /// The most recently set cookie named `name`, on any domain.
+    pub fn get(&self, name: &str) -> Option<&Cookie> {
+        self.cookies.iter().rev().find(|c| c.name == name)
+    }
+
+    /// The cookies a request to `url` would carry, by domain, path and `Secure`.
+    pub fn for_url(&self, url: &str) -> Vec<&Cookie> {
+        let Ok(url) = Url::parse(url) else {
+            return Vec::new();
+        };
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        let secure = url.scheme() == "https";
+        self.cookies
+            .iter()
+            .filter(|c| domain_matches(&host, &c.domain))
+            .filter(|c| path_matches(url.path(), &c.path))
+            .filter(|c| secure || !c.secure)
+            .collect()
+    }
+
+    /// The jar as a `Cookie` header value for `url`.
+    pub fn header_for(&self, url: &str) -> String {
+        self.for_url(url)
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+}
+
+/// Feeds the stream's jar from the cookies requests carried and responses set.
+pub(crate) struct CookieFollower(pub(crate) Arc<handle::Shared>);
+
+impl Follower for CookieFollower {
+    fn follow(&mut self, notice: &Notice, request: Option<&Request>) {
+        match notice {
+            Notice::SentExtraInfo(event) => {
+                let mut jar = self.0.cookies.lock().unwrap_or_else(|e| e.into_inner());
+                for associated in &event.associated_cookies {
+                    if associated.blocked_reasons.is_empty() {
+                        jar.learn(from_cdp(&associated.cookie));
+                    }
+                }
+            }
+            Notice::ResponseExtraInfo(event) => {
+                // The extra-info events carry no URL; redirects reuse the request id, so the
+                // watcher's URL for it is the one this response belongs to.
+                let Some(request) = request else {
+                    return;
+                };
+                let blocked: Vec<_> = event
+                    .blocked_cookies
+                    .iter()
+                    .map(|b| b.cookie_line.as_str())
+                    .collect();
+                let mut jar = self.0.cookies.lock().unwrap_or_else(|e| e.into_inner());
+                for header in websocket::header_list(&event.headers) {
+                    if header.name == "set-cookie" && !blocked.contains(&header.value.as_str()) {
+                        jar.set_line(&request.url, &header.value);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// A cookie as the browser stored it. Its expiry is absolute, not the raw attribute, so it is
+// left out.
+fn from_cdp(cookie: &network::Cookie) -> Cookie {
+    Cookie {
+        name: cookie.name.clone(),
+        value: cookie.value.clone(),
+        domain: cookie.domain.trim_start_matches('.').to_ascii_lowercase(),
+        path: cookie.path.clone(),
+        expires: None,
+        max_age: None,
+        secure: cookie.secure,
+        http_only: cookie.http_only,
+        same_site: cookie.same_site.as_ref().map(|s| s.as_ref().to_string()),
+    }
+}
+
+fn parse_cookie_header(value: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    value.split(';').filter_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        let name = name.trim();
+        (!name.is_empty()).then(|| (name.to_string(), value.trim().to_string()))
+    })
+}
+
+// `fetch` joins repeated headers with ", ", which also appears inside `Expires` dates. Split only
+// where the text after the comma starts a new `name=`.
+fn split_set_cookie(value: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, _) in value.match_indices(',') {
+        let rest = value[i + 1..].trim_start();
+        let name = rest.split(['=', ';', ',']).next().unwrap_or_default();
+        let starts_cookie = !name.is_empty()
+            && !name.contains(char::is_whitespace)
+            && rest[name.len()..].starts_with('=');
+        if starts_cookie {
+            lines.push(value[start..i].trim());
+            start = i + 1;
+        }
+    }
+    lines.push(value[start..].trim());
+    lines.retain(|line| !line.is_empty());
+    lines
+}
+
+fn parse_set_cookie(line: &str, url: &Url, host: &str) -> Option<Cookie> {
+    let mut parts = line.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: value.trim().trim_matches('"').to_string(),
+        domain: host.to_string(),
+        path: default_path(url.path()),
+        expires: None,
+        max_age: None,
+        secure: false,
+        http_only: false,
+        same_site: None,
+    };
+    for attr in parts {
+        let (key, value) = match attr.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => (attr.trim(), ""),
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "domain" if !value.is_empty() => {
+                cookie.domain = value.trim_start_matches('.').to_ascii_lowercase();
+            }
+            "path" if value.starts_with('/') => cookie.path = value.to_string(),
+            "expires" => cookie.expires = Some(value.to_string()),
+            "max-age" => cookie.max_age = value.parse().ok(),
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            "samesite" => cookie.same_site = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    Some(cookie)
+}
+
+// RFC 6265 5.1.4: the request path up to, but not including, its last `/`.
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => path[..i].to_string(),
+    }
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+fn path_matches(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path
+        || path
+            .strip_prefix(cookie_path)
+            .is_some_and(|rest| cookie_path.ends_with('/') || rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_set_cookie_keeps_expires_dates_whole() {
+        let joined = "a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT; Path=/, b=2, c=x,y";
+        assert_eq!(
+            split_set_cookie(joined),
+            [
+                "a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT; Path=/",
+                "b=2",
+                "c=x,y"
+            ]
+        );
+        assert!(split_set_cookie("  ").is_empty());
+    }
+
+    #[test]
+    fn set_line_scopes_to_the_response_url() {
+        let mut jar = CookieJar::new();
+        jar.set_line(
+            "https://shop.example.com/cart/items",
+            "sid=abc; Secure; HttpOnly",
+        );
+        jar.set_line(
+            "https://shop.example.com/",
+            "theme=dark; Domain=.example.com",
+        );
+        let sid = jar.get("sid").unwrap();
+        assert_eq!(
+            (sid.domain.as_str(), sid.path.as_str()),
+            ("shop.example.com", "/cart")
+        );
+        assert!(sid.secure && sid.http_only);
+        assert_eq!(jar.header_for("https://www.example.com/"), "theme=dark");
+        assert_eq!(
+            jar.header_for("http://shop.example.com/cart/x"),
+            "theme=dark"
+        );
+        assert_eq!(
+            jar.header_for("https://shop.example.com/cart/x"),
+            "sid=abc; theme=dark"
+        );
+
+        jar.set_line("https://shop.example.com/cart/items", "sid=; Max-Age=0");
+        assert!(jar.get("sid").is_none());
+    }
+
+    #[test]
+    fn learn_updates_values_but_keeps_set_cookie_attributes() {
+        let mut jar = CookieJar::new();
+        jar.set_line("https://example.com/", "sid=abc; Path=/app; HttpOnly");
+        jar.learn(Cookie {
+            name: "sid".to_string(),
+            value: "changed-by-script".to_string(),
+            domain: "example.com".to_string(),
+            path: "/".to_string(),
+            expires: None,
+            max_age: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        });
+        assert_eq!(jar.cookies().len(), 1);
+        assert_eq!(jar.cookies()[0].path, "/app");
+        assert!(jar.cookies()[0].http_only);
+        assert_eq!(jar.cookies()[0].value, "changed-by-script");
+    }
+}
@@ -0,0 +1,111 @@
+use crate::Event;
+
+// Set by curl itself from the actual request it sends.
+const SKIPPED_HEADERS: [&str; 2] = ["content-length", "host"];
+
+impl Event {
+    /// Render the captured request as a ready-to-run `curl` command (POSIX shell quoting).
+    ///
+    /// Only what the page-side hooks observed is included: headers added by the browser
+    /// itself (cookies, user agent, ...) are not part of the result, and neither are the
+    /// [`redacted_headers`](Self::redacted_headers).
+    pub fn to_curl(&self) -> String {
+        let method = self.method.as_deref().unwrap_or("GET");
+        let mut parts = vec![format!("curl {}", shell_quote(&self.url))];
+
+        let implied = match self.request_body {
+            Some(_) => "POST",
+            None => "GET",
+        };
+        if !method.eq_ignore_ascii_case(implied) {
+            parts.push(format!("-X {}", shell_quote(method)));
+        }
+        for header in &self.request_headers {
+            if self.is_redacted(header)
+                || SKIPPED_HEADERS
+                    .iter()
+                    .any(|h| header.name.eq_ignore_ascii_case(h))
+            {
+                continue;
+            }
+            parts.push(format!(
+                "-H {}",
+                shell_quote(&format!("{}: {}", header.name, header.value))
+            ));
+        }
+        if let Some(body) = &self.request_body {
+            parts.push(format!("--data-raw {}", shell_quote(body)));
+        }
+        parts.join(" \\\n  ")
+    }
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Redaction;
+
+    #[test]
+    fn shell_quote_survives_single_quotes() {
+        assert_eq!(shell_quote("plain"), "'plain'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("''"), r"''\'''\'''");
+        // Everything else is literal inside single quotes.
+        assert_eq!(shell_quote(r#"$HOME `id` \ "x""#), r#"'$HOME `id` \ "x"'"#);
+    }
+
+    #[test]
+    fn to_curl_quotes_every_part_and_spells_out_unusual_methods() {
+        let event = Event::builder()
+            .url("https://example.com/search?q=rock'n'roll&x=1")
+            .method("PUT")
+            .request_header("Content-Type", "application/json")
+            .request_header("Host", "example.com")
+            .request_header("Content-Length", "17")
+            .request_header("x-note", "don't")
+            .request_body(r#"{"name":"O'Hara"}"#)
+            .build();
+        assert_eq!(
+            event.to_curl(),
+            [
+                r"curl 'https://example.com/search?q=rock'\''n'\''roll&x=1'",
+                "-X 'PUT'",
+                "-H 'Content-Type: application/json'",
+                r"-H 'x-note: don'\''t'",
+                r#"--data-raw '{"name":"O'\''Hara"}'"#,
+            ]
+            .join(" \\\n  ")
+        );
+
+        // GET without a body and POST with one are implied.
+        let post = Event::builder()
+            .url("https://example.com/")
+            .method("post")
+            .request_body("a=1")
+            .build();
+        assert_eq!(
+            post.to_curl(),
+            "curl 'https://example.com/' \\\n  --data-raw 'a=1'"
+        );
+        let get = Event::builder().url("https://example.com/").build();
+        assert_eq!(get.to_curl(), "curl 'https://example.com/'");
+    }
+
+    #[test]
+    fn to_curl_leaves_out_redacted_headers() {
+        let mut event = Event::builder()
+            .url("https://example.com/api")
+            .request_header("authorization", "Bearer abc")
+            .request_header("accept", "application/json")
+            .build();
+        Redaction::default().mask("<hidden>").apply(&mut event);
+        assert_eq!(
+            event.to_curl(),
+            "curl 'https://example.com/api' \\\n  -H 'accept: application/json'"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Events reshaped as request / response pairs.
+//!
+//! An [`Event`] already carries both sides of a request, flattened into one struct. An
+//! [`Exchange`] groups them the way most reports and assertions read: what was sent, what came
+//! back, and how long it took. Convert single events with `Exchange::from`, or a whole stream
+//! with [`exchanges`]. Fields only some consumers need, such as `decoded` or `extracted`, stay
+//! on the event.
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use serde::{Deserialize, Serialize};
+
+use crate::multipart::Part;
+use crate::{Event, Header};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Exchange {
+    pub request: Request,
+    /// `None` when the request failed without a response, see `error`.
+    pub response: Option<Response>,
+    pub timing: Timing,
+    /// Why the request failed, see [`Event::error`].
+    pub error: Option<String>,
+    /// See [`Event::seq`].
+    pub seq: Option<u64>,
+    /// See [`Event::label`].
+    pub label: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<Header>,
+    pub body: Option<String>,
+    pub parts: Option<Vec<Part>>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub headers: Vec<Header>,
+    pub body: String,
+    /// `body` holds base64-encoded bytes.
+    pub base64_encoded: bool,
+}
+
+/// Milliseconds, as in [`Event`].
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct Timing {
+    pub started_at: Option<f64>,
+    pub time_to_headers_ms: Option<f64>,
+    pub duration_ms: Option<f64>,
+    pub received_at: Option<f64>,
+    pub received_monotonic_ms: Option<f64>,
+}
+
+impl From<Event> for Exchange {
+    fn from(event: Event) -> Self {
+        Self {
+            request: Request {
+                method: event.method.unwrap_or_else(|| "GET".to_string()),
+                url: event.url,
+                headers: event.request_headers,
+                body: event.request_body,
+                parts: event.request_parts,
+            },
+            response: event.status.map(|status| Response {
+                status,
+                content_type: event.content_type,
+                headers: event.response_headers,
+                body: event.body,
+                base64_encoded: event.base64_encoded,
+            }),
+            timing: Timing {
+                started_at: event.started_at,
+                time_to_headers_ms: event.time_to_headers_ms,
+                duration_ms: event.duration_ms,
+                received_at: event.received_at,
+                received_monotonic_ms: event.received_monotonic_ms,
+            },
+            error: event.error,
+            seq: event.seq,
+            label: event.label,
+        }
+    }
+}
+
+/// Forward every event from `rx` as an [`Exchange`].
+pub fn exchanges(mut rx: mpsc::UnboundedReceiver<Event>) -> mpsc::UnboundedReceiver<Exchange> {
+    let (tx, out) = mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some(event) = rx.next().await {
+            if tx.unbounded_send(Exchange::from(event)).is_err() {
+                break; // receiver dropped
+            }
+        }
+    });
+    out
+}
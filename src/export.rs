@@ -0,0 +1,271 @@
+use std::path::Path;
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
+
+use crate::har::format_epoch_millis;
+use crate::{Error, Event};
+
+/// A column of the CSV summary written by [`write_csv`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CsvColumn {
+    /// Request start as an ISO 8601 UTC timestamp.
+    Timestamp,
+    Method,
+    Url,
+    Status,
+    ContentType,
+    /// Response body length in bytes, decoded for base64 bodies.
+    Size,
+    /// Total request duration in milliseconds.
+    Duration,
+}
+
+impl CsvColumn {
+    pub const ALL: [CsvColumn; 7] = [
+        CsvColumn::Timestamp,
+        CsvColumn::Method,
+        CsvColumn::Url,
+        CsvColumn::Status,
+        CsvColumn::ContentType,
+        CsvColumn::Size,
+        CsvColumn::Duration,
+    ];
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            CsvColumn::Timestamp => "timestamp",
+            CsvColumn::Method => "method",
+            CsvColumn::Url => "url",
+            CsvColumn::Status => "status",
+            CsvColumn::ContentType => "content_type",
+            CsvColumn::Size => "size",
+            CsvColumn::Duration => "duration_ms",
+        }
+    }
+
+    fn value(&self, event: &Event) -> String {
+        match self {
+            CsvColumn::Timestamp => event
+                .started_at
+                .map(format_epoch_millis)
+                .unwrap_or_default(),
+            CsvColumn::Method => event.method.clone().unwrap_or_default(),
+            CsvColumn::Url => event.url.clone(),
+            CsvColumn::Status => event.status.map(|s| s.to_string()).unwrap_or_default(),
+            CsvColumn::ContentType => event.content_type.clone().unwrap_or_default(),
+            CsvColumn::Size => event.body_len().to_string(),
+            CsvColumn::Duration => event
+                .duration_ms
+                .map(|d| format!("{d:.1}"))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CsvExportConfig {
+    /// Columns to write, in order.
+    pub columns: Vec<CsvColumn>,
+    /// Write a header row before the first event.
+    pub header: bool,
+}
+
+impl Default for CsvExportConfig {
+    fn default() -> Self {
+        Self {
+            columns: CsvColumn::ALL.to_vec(),
+            header: true,
+        }
+    }
+}
+
+/// Write every event from the receiver as one JSON line until the stream closes.
+/// Each line is flushed as it is written, so the output can be tailed while capturing.
+/// Returns the number of events written.
+pub async fn write_jsonl<W: AsyncWrite + Unpin>(
+    rx: &mut mpsc::UnboundedReceiver<Event>,
+    writer: &mut W,
+) -> Result<usize, Error> {
+    let mut written = 0;
+    while let Some(event) = rx.next().await {
+        let mut line = serde_json::to_vec(&event).map_err(Error::SerializeJson)?;
+        line.push(b'\n');
+        writer.write_all(&line).await.map_err(Error::Io)?;
+        writer.flush().await.map_err(Error::Io)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Create (or truncate) `path` and stream every event into it as JSON lines.
+/// Takes the receiver by value so it can be handed straight to `tokio::spawn`.
+pub async fn stream_to_jsonl_file(
+    mut rx: mpsc::UnboundedReceiver<Event>,
+    path: impl AsRef<Path>,
+) -> Result<usize, Error> {
+    let file = tokio::fs::File::create(path).await.map_err(Error::Io)?;
+    let mut writer = BufWriter::new(file);
+    write_jsonl(&mut rx, &mut writer).await
+}
+
+/// Write every event from the receiver as a JSON frame prefixed with its length as a
+/// big-endian `u32`, until the stream closes. Frames need no delimiter scanning on the reading
+/// side, which suits sidecar processes. Returns the number of events written.
+pub async fn write_json_frames<W: AsyncWrite + Unpin>(
+    rx: &mut mpsc::UnboundedReceiver<Event>,
+    writer: &mut W,
+) -> Result<usize, Error> {
+    let mut written = 0;
+    while let Some(event) = rx.next().await {
+        let payload = serde_json::to_vec(&event).map_err(Error::SerializeJson)?;
+        let len = u32::try_from(payload.len()).map_err(|_| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "encoded event exceeds u32::MAX bytes",
+            ))
+        })?;
+        writer
+            .write_all(&len.to_be_bytes())
+            .await
+            .map_err(Error::Io)?;
+        writer.write_all(&payload).await.map_err(Error::Io)?;
+        writer.flush().await.map_err(Error::Io)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Connect to the Unix domain socket at `path` and stream every event into it as
+/// [length-prefixed JSON frames](write_json_frames).
+#[cfg(unix)]
+pub async fn stream_to_unix_socket(
+    mut rx: mpsc::UnboundedReceiver<Event>,
+    path: impl AsRef<Path>,
+) -> Result<usize, Error> {
+    let mut socket = tokio::net::UnixStream::connect(path)
+        .await
+        .map_err(Error::Io)?;
+    let written = write_json_frames(&mut rx, &mut socket).await?;
+    socket.shutdown().await.map_err(Error::Io)?;
+    Ok(written)
+}
+
+/// Open the named pipe `name`, e.g. `\\.\pipe\captures`, and stream every event into it as
+/// [length-prefixed JSON frames](write_json_frames).
+#[cfg(windows)]
+pub async fn stream_to_named_pipe(
+    mut rx: mpsc::UnboundedReceiver<Event>,
+    name: impl AsRef<std::ffi::OsStr>,
+) -> Result<usize, Error> {
+    let mut pipe = tokio::net::windows::named_pipe::ClientOptions::new()
+        .open(name)
+        .map_err(Error::Io)?;
+    write_json_frames(&mut rx, &mut pipe).await
+}
+
+/// Write one CSV row per event from the receiver until the stream closes.
+/// Returns the number of events written, not counting the header row.
+pub async fn write_csv<W: AsyncWrite + Unpin>(
+    rx: &mut mpsc::UnboundedReceiver<Event>,
+    writer: &mut W,
+    config: &CsvExportConfig,
+) -> Result<usize, Error> {
+    if config.header {
+        let header: Vec<&str> = config.columns.iter().map(CsvColumn::header).collect();
+        write_csv_row(writer, header.iter().copied()).await?;
+    }
+    let mut written = 0;
+    while let Some(event) = rx.next().await {
+        let row: Vec<String> = config.columns.iter().map(|c| c.value(&event)).collect();
+        write_csv_row(writer, row.iter().map(String::as_str)).await?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+async fn write_csv_row<'a, W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    fields: impl Iterator<Item = &'a str>,
+) -> Result<(), Error> {
+    let mut line = fields.map(escape_csv_field).collect::<Vec<_>>().join(",");
+    line.push_str("\r\n");
+    writer.write_all(line.as_bytes()).await.map_err(Error::Io)?;
+    writer.flush().await.map_err(Error::Io)
+}
+
+// RFC 4180 quoting: wrap fields containing separators, quotes or line breaks, doubling quotes.
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_csv_field_quotes_only_when_needed() {
+        assert_eq!(escape_csv_field("plain"), "plain");
+        assert_eq!(escape_csv_field(""), "");
+        assert_eq!(escape_csv_field("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_csv_field("line\r\nbreak"), "\"line\r\nbreak\"");
+    }
+
+    #[tokio::test]
+    async fn write_csv_writes_the_selected_columns_in_order() {
+        let (tx, mut rx) = mpsc::unbounded();
+        let text = Event::builder()
+            .url("https://example.com/a?x=1,2")
+            .method("GET")
+            .status(200)
+            .content_type("text/plain; charset=\"utf-8\"")
+            .body("hello")
+            .duration_ms(12.34)
+            .build();
+        let image = Event::builder()
+            .url("https://example.com/logo.png")
+            .bytes_body([0u8, 1, 2, 3, 4])
+            .build();
+        tx.unbounded_send(text).unwrap();
+        tx.unbounded_send(image).unwrap();
+        drop(tx);
+
+        let config = CsvExportConfig {
+            columns: vec![
+                CsvColumn::Url,
+                CsvColumn::ContentType,
+                CsvColumn::Size,
+                CsvColumn::Duration,
+                CsvColumn::Status,
+            ],
+            header: true,
+        };
+        let mut out = Vec::new();
+        assert_eq!(write_csv(&mut rx, &mut out, &config).await.unwrap(), 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "url,content_type,size,duration_ms,status\r\n\
+             \"https://example.com/a?x=1,2\",\"text/plain; charset=\"\"utf-8\"\"\",5,12.3,200\r\n\
+             https://example.com/logo.png,,5,,\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn write_csv_can_skip_the_header() {
+        let (tx, mut rx) = mpsc::unbounded::<Event>();
+        drop(tx);
+        let config = CsvExportConfig {
+            columns: vec![CsvColumn::Method],
+            header: false,
+        };
+        let mut out = Vec::new();
+        assert_eq!(write_csv(&mut rx, &mut out, &config).await.unwrap(), 0);
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+/// A selector run in the page against HTML and XML responses, see
+/// [`EventStreamConfig::extractors`](crate::EventStreamConfig::extractors).
+///
+/// Matches are collected into [`Event::extracted`](crate::Event::extracted) under `name`, as the
+/// trimmed text of each matched node, or the value of `attribute` when set.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Extractor {
+    pub name: String,
+    pub selector: Selector,
+    #[serde(default)]
+    pub attribute: Option<String>,
+    /// Only run against responses whose URL contains this substring.
+    #[serde(rename = "urlFilter", alias = "url_substring", default)]
+    pub url_substring: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Selector {
+    Css(String),
+    XPath(String),
+}
+
+impl Extractor {
+    pub fn css(name: impl Into<String>, selector: impl Into<String>) -> Self {
+        Self::new(name, Selector::Css(selector.into()))
+    }
+
+    /// An XPath expression. Expressions that evaluate to a string, number or boolean yield that
+    /// single value.
+    pub fn xpath(name: impl Into<String>, expression: impl Into<String>) -> Self {
+        Self::new(name, Selector::XPath(expression.into()))
+    }
+
+    fn new(name: impl Into<String>, selector: Selector) -> Self {
+        Self {
+            name: name.into(),
+            selector,
+            attribute: None,
+            url_substring: None,
+        }
+    }
+
+    pub fn attribute(mut self, attribute: impl Into<String>) -> Self {
+        self.attribute = Some(attribute.into());
+        self
+    }
+
+    pub fn for_url(mut self, url_substring: impl Into<String>) -> Self {
+        self.url_substring = Some(url_substring.into());
+        self
+    }
+}
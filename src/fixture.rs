@@ -0,0 +1,140 @@
+//! Record a capture session to a fixture file and replay it without a browser.
+//!
+//! Fixtures use the same JSON-lines format as [`write_jsonl`](crate::write_jsonl), so any
+//! JSONL capture can be replayed. Replayed receivers yield the recorded events in order and
+//! then close, which makes downstream code deterministic to unit-test.
+//!
+//! Batch fixtures go one step earlier: they hold the raw [`SourceBatch`]es drained from the page,
+//! one JSON object per line, with the in-flight, body-failure and eviction counters and the
+//! body errors alongside the events. Record them with [`record_source`] and replay them with
+//! [`replay_source`] through [`run_event_source`](crate::run_event_source), so a regression test
+//! sees the same filtering, annotation and redaction as a live stream, poll by poll. They can
+//! also be written by hand to reproduce orderings that are hard to trigger, such as a failed
+//! body read next to its event or the same URL captured twice in one poll.
+
+use std::path::Path;
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use tokio::io::{AsyncWriteExt, BufWriter};
+
+use crate::{Error, Event, EventSource, ScriptedSource, SourceBatch, StreamEndReason};
+
+/// Tee the receiver into a fixture file at `path`.
+///
+/// Returns a receiver that yields the same events as they are written. If writing fails,
+/// recording stops but events keep being forwarded.
+pub async fn record(
+    mut rx: mpsc::UnboundedReceiver<Event>,
+    path: impl AsRef<Path>,
+) -> Result<mpsc::UnboundedReceiver<Event>, Error> {
+    let file = tokio::fs::File::create(path).await.map_err(Error::Io)?;
+    let (tx, out) = mpsc::unbounded();
+
+    tokio::spawn(async move {
+        let mut writer = Some(BufWriter::new(file));
+        while let Some(event) = rx.next().await {
+            if let Some(w) = writer.as_mut()
+                && let Err(e) = write_line(w, &event).await
+            {
+                tracing::warn!(error = %e, "writing fixture failed, recording stopped");
+                writer = None;
+            }
+            if tx.unbounded_send(event).is_err() {
+                break; // receiver dropped
+            }
+        }
+        if let Some(mut w) = writer {
+            let _ = w.shutdown().await;
+        }
+    });
+
+    Ok(out)
+}
+
+/// Read every event from a fixture file. Blank lines are ignored.
+pub async fn load(path: impl AsRef<Path>) -> Result<Vec<Event>, Error> {
+    let contents = tokio::fs::read_to_string(path).await.map_err(Error::Io)?;
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(Error::ParseJson))
+        .collect()
+}
+
+/// Build a receiver that yields `events` in order and then closes.
+pub fn replay(events: impl IntoIterator<Item = Event>) -> mpsc::UnboundedReceiver<Event> {
+    let (tx, rx) = mpsc::unbounded();
+    for event in events {
+        // The receiver is still held here, so sending cannot fail.
+        let _ = tx.unbounded_send(event);
+    }
+    rx
+}
+
+/// Load a fixture file and replay it through a receiver.
+pub async fn replay_file(path: impl AsRef<Path>) -> Result<mpsc::UnboundedReceiver<Event>, Error> {
+    Ok(replay(load(path).await?))
+}
+
+async fn write_line<W: tokio::io::AsyncWrite + Unpin>(
+    writer: &mut W,
+    value: &impl serde::Serialize,
+) -> Result<(), Error> {
+    let mut line = serde_json::to_vec(value).map_err(Error::SerializeJson)?;
+    line.push(b'\n');
+    writer.write_all(&line).await.map_err(Error::Io)?;
+    writer.flush().await.map_err(Error::Io)
+}
+
+/// Wrap `source` so every batch it drains is also written to a batch fixture at `path`.
+///
+/// If writing fails, recording stops but batches keep being passed on.
+pub async fn record_source<S: EventSource>(
+    source: S,
+    path: impl AsRef<Path>,
+) -> Result<RecordingSource<S>, Error> {
+    let file = tokio::fs::File::create(path).await.map_err(Error::Io)?;
+    Ok(RecordingSource {
+        source,
+        writer: Some(BufWriter::new(file)),
+    })
+}
+
+/// A source that records what it drains, see [`record_source`].
+pub struct RecordingSource<S> {
+    source: S,
+    writer: Option<BufWriter<tokio::fs::File>>,
+}
+
+impl<S: EventSource> EventSource for RecordingSource<S> {
+    async fn drain(&mut self) -> Result<SourceBatch, Error> {
+        let batch = self.source.drain().await?;
+        if let Some(w) = self.writer.as_mut()
+            && let Err(e) = write_line(w, &batch).await
+        {
+            tracing::warn!(error = %e, "writing batch fixture failed, recording stopped");
+            self.writer = None;
+        }
+        Ok(batch)
+    }
+
+    async fn closed(&mut self) -> StreamEndReason {
+        self.source.closed().await
+    }
+}
+
+/// Read every batch from a batch fixture file. Blank lines are ignored.
+pub async fn load_batches(path: impl AsRef<Path>) -> Result<Vec<SourceBatch>, Error> {
+    let contents = tokio::fs::read_to_string(path).await.map_err(Error::Io)?;
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(Error::ParseJson))
+        .collect()
+}
+
+/// Load a batch fixture file into a source that drains one recorded batch per poll, then closes.
+pub async fn replay_source(path: impl AsRef<Path>) -> Result<ScriptedSource, Error> {
+    Ok(ScriptedSource::new(load_batches(path).await?))
+}
@@ -0,0 +1,98 @@
+use url::form_urlencoded;
+
+use crate::Event;
+
+const FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
+
+impl Event {
+    /// Whether the request body is `application/x-www-form-urlencoded`, by its `Content-Type`.
+    pub fn is_form_encoded(&self) -> bool {
+        self.request_header("content-type").is_some_and(|ct| {
+            ct.trim_start()
+                .to_ascii_lowercase()
+                .starts_with(FORM_CONTENT_TYPE)
+        })
+    }
+
+    /// The decoded key/value pairs of a form-encoded request body, in order and with repeated
+    /// keys kept. `None` if the request is not form-encoded or has no recorded body.
+    pub fn form_params(&self) -> Option<Vec<(String, String)>> {
+        if !self.is_form_encoded() {
+            return None;
+        }
+        let body = self.request_body.as_deref()?;
+        Some(
+            form_urlencoded::parse(body.as_bytes())
+                .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                .collect(),
+        )
+    }
+
+    /// The first value of form parameter `name`.
+    pub fn form_param(&self, name: &str) -> Option<String> {
+        self.form_params()?
+            .into_iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Event;
+
+    fn form(body: &str) -> Event {
+        Event::builder()
+            .url("https://example.com/login")
+            .request_header(
+                "Content-Type",
+                "application/x-www-form-urlencoded;charset=UTF-8",
+            )
+            .request_body(body)
+            .build()
+    }
+
+    #[test]
+    fn form_params_decode_plus_and_percent_escapes() {
+        let event = form("name=Jane+Doe&city=S%C3%A3o%20Paulo&tag=a&tag=b&empty=&flag");
+        assert_eq!(
+            event.form_params().unwrap(),
+            [
+                ("name", "Jane Doe"),
+                ("city", "São Paulo"),
+                ("tag", "a"),
+                ("tag", "b"),
+                ("empty", ""),
+                ("flag", ""),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+        assert_eq!(event.form_param("tag").as_deref(), Some("a"));
+        assert_eq!(event.form_param("missing"), None);
+    }
+
+    #[test]
+    fn form_params_keep_malformed_escapes_literally() {
+        let event = form("discount=100%&code=%zz%4");
+        assert_eq!(event.form_param("discount").as_deref(), Some("100%"));
+        assert_eq!(event.form_param("code").as_deref(), Some("%zz%4"));
+    }
+
+    #[test]
+    fn form_params_need_a_form_content_type_and_a_body() {
+        let json = Event::builder()
+            .url("https://example.com/")
+            .request_header("content-type", "application/json")
+            .request_body("a=1")
+            .build();
+        assert!(!json.is_form_encoded());
+        assert_eq!(json.form_params(), None);
+
+        let no_body = Event::builder()
+            .url("https://example.com/")
+            .request_header("content-type", " Application/X-WWW-Form-Urlencoded")
+            .build();
+        assert!(no_body.is_form_encoded());
+        assert_eq!(no_body.form_params(), None);
+    }
+}
@@ -0,0 +1,255 @@
+//! GraphQL-aware view of captured traffic.
+//!
+//! [`exchanges`] turns one captured [`Event`] into the GraphQL operations it carried, pairing
+//! each operation's `operationName`, `query` and `variables` with its `data` and `errors`.
+//! Batched requests (a JSON array of operations) yield one exchange per element. Requests are
+//! recognised by their shape, a POST body or GET query string with a `query` or a persisted
+//! query hash, so endpoints need not be named `/graphql`.
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::Event;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationKind {
+    Query,
+    Mutation,
+    Subscription,
+}
+
+/// One GraphQL operation and its result.
+#[derive(Clone, Debug, Serialize)]
+pub struct GraphQlExchange {
+    pub url: String,
+    pub operation_name: Option<String>,
+    /// From the first keyword of the query document; `None` for persisted queries sent without
+    /// one.
+    pub kind: Option<OperationKind>,
+    pub query: Option<String>,
+    /// `Value::Null` when the request had no variables.
+    pub variables: Value,
+    /// Request `extensions`, e.g. the persisted query hash.
+    pub extensions: Option<Value>,
+    pub status: Option<u16>,
+    pub data: Option<Value>,
+    pub errors: Vec<Value>,
+    pub started_at: Option<f64>,
+    pub duration_ms: Option<f64>,
+}
+
+impl GraphQlExchange {
+    /// Whether the server reported errors for this operation.
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+}
+
+/// Whether `event` looks like a GraphQL request.
+pub fn is_graphql(event: &Event) -> bool {
+    !operations(event).is_empty()
+}
+
+/// The GraphQL operations carried by `event`, paired with their results. Empty for other
+/// traffic.
+pub fn exchanges(event: &Event) -> Vec<GraphQlExchange> {
+    let ops = operations(event);
+    if ops.is_empty() {
+        return Vec::new();
+    }
+    let response = serde_json::from_str::<Value>(&event.body).ok();
+    // A batched request is answered with an array in the same order.
+    let results: Vec<Option<&Value>> = match &response {
+        Some(Value::Array(items)) => (0..ops.len()).map(|i| items.get(i)).collect(),
+        other => (0..ops.len())
+            .map(|i| if i == 0 { other.as_ref() } else { None })
+            .collect(),
+    };
+
+    ops.into_iter()
+        .zip(results)
+        .map(|(op, result)| {
+            let query = op.get("query").and_then(Value::as_str).map(str::to_string);
+            GraphQlExchange {
+                url: event.url.clone(),
+                operation_name: op
+                    .get("operationName")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                kind: query.as_deref().map(operation_kind),
+                query,
+                variables: op.get("variables").cloned().unwrap_or(Value::Null),
+                extensions: op.get("extensions").cloned(),
+                status: event.status,
+                data: result
+                    .and_then(|r| r.get("data"))
+                    .filter(|d| !d.is_null())
+                    .cloned(),
+                errors: result
+                    .and_then(|r| r.get("errors"))
+                    .and_then(Value::as_array)
+                    .cloned()
+                    .unwrap_or_default(),
+                started_at: event.started_at,
+                duration_ms: event.duration_ms,
+            }
+        })
+        .collect()
+}
+
+/// Forward the GraphQL exchanges found in `rx`, dropping all other traffic.
+pub fn graphql_exchanges(
+    mut rx: mpsc::UnboundedReceiver<Event>,
+) -> mpsc::UnboundedReceiver<GraphQlExchange> {
+    let (tx, out) = mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some(event) = rx.next().await {
+            for exchange in exchanges(&event) {
+                if tx.unbounded_send(exchange).is_err() {
+                    return; // receiver dropped
+                }
+            }
+        }
+    });
+    out
+}
+
+// The operation objects of a request, from the JSON body or, for GET, the query string.
+fn operations(event: &Event) -> Vec<Value> {
+    let candidates = match event.request_body.as_deref() {
+        Some(body) => match serde_json::from_str::<Value>(body) {
+            Ok(Value::Array(items)) => items,
+            Ok(value) => vec![value],
+            Err(_) => Vec::new(),
+        },
+        None => from_query_string(&event.url).into_iter().collect(),
+    };
+    candidates.into_iter().filter(is_operation).collect()
+}
+
+fn is_operation(value: &Value) -> bool {
+    value.get("query").is_some_and(Value::is_string)
+        || value.pointer("/extensions/persistedQuery").is_some()
+}
+
+// GET requests carry the operation as `query`, `operationName` and JSON-encoded `variables`
+// and `extensions` parameters.
+fn from_query_string(url: &str) -> Option<Value> {
+    let url = url::Url::parse(url).ok()?;
+    let mut op = serde_json::Map::new();
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "query" | "operationName" => {
+                op.insert(key.into_owned(), Value::String(value.into_owned()));
+            }
+            "variables" | "extensions" => {
+                let parsed = serde_json::from_str(&value).unwrap_or(Value::Null);
+                op.insert(key.into_owned(), parsed);
+            }
+            _ => {}
+        }
+    }
+    (!op.is_empty()).then_some(Value::Object(op))
+}
+
+// The first keyword of the document; the `{ ... }` shorthand is a query.
+fn operation_kind(query: &str) -> OperationKind {
+    let mut rest = query.trim_start();
+    // Skip leading `#` comments.
+    while let Some(comment) = rest.strip_prefix('#') {
+        rest = comment
+            .split_once('\n')
+            .map_or("", |(_, tail)| tail)
+            .trim_start();
+    }
+    if rest.starts_with("mutation") {
+        OperationKind::Mutation
+    } else if rest.starts_with("subscription") {
+        OperationKind::Subscription
+    } else {
+        OperationKind::Query
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn post(body: Value, response: Value) -> Event {
+        Event::builder()
+            .url("https://api.example.com/gql")
+            .method("POST")
+            .status(200)
+            .request_body(body.to_string())
+            .body(response.to_string())
+            .build()
+    }
+
+    #[test]
+    fn batched_operations_pair_with_results_by_position() {
+        let event = post(
+            json!([
+                {"operationName": "Me", "query": "# cached\nquery Me { me { id } }"},
+                {"query": "mutation { logout }", "variables": {"all": true}},
+                {"query": "subscription { ticks }"},
+            ]),
+            json!([
+                {"data": {"me": {"id": 1}}},
+                {"data": null, "errors": [{"message": "expired"}]},
+            ]),
+        );
+        let ops = exchanges(&event);
+        assert_eq!(ops.len(), 3);
+        assert_eq!(ops[0].operation_name.as_deref(), Some("Me"));
+        assert_eq!(ops[0].kind, Some(OperationKind::Query));
+        assert_eq!(ops[0].data, Some(json!({"me": {"id": 1}})));
+        assert_eq!(ops[1].kind, Some(OperationKind::Mutation));
+        assert_eq!(ops[1].variables, json!({"all": true}));
+        assert_eq!(ops[1].data, None);
+        assert!(ops[1].has_errors());
+        // The batch response was one short.
+        assert_eq!(ops[2].kind, Some(OperationKind::Subscription));
+        assert!(ops[2].data.is_none() && !ops[2].has_errors());
+    }
+
+    #[test]
+    fn persisted_queries_are_recognised_without_a_query() {
+        let extensions = json!({"persistedQuery": {"version": 1, "sha256Hash": "abc"}});
+        let event = post(
+            json!({"operationName": "Feed", "extensions": extensions}),
+            json!({"errors": [{"message": "PersistedQueryNotFound"}]}),
+        );
+        let ops = exchanges(&event);
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].kind, None);
+        assert_eq!(ops[0].query, None);
+        assert_eq!(ops[0].extensions, Some(extensions));
+        assert_eq!(ops[0].errors.len(), 1);
+
+        // The same over GET, with JSON-encoded parameters in the query string.
+        let get = Event::builder()
+            .url(
+                "https://api.example.com/gql?operationName=Feed&variables=%7B%22n%22%3A2%7D\
+                 &extensions=%7B%22persistedQuery%22%3A%7B%22version%22%3A1%7D%7D",
+            )
+            .body(r#"{"data":{"feed":[]}}"#)
+            .build();
+        let ops = exchanges(&get);
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].variables, json!({"n": 2}));
+        assert_eq!(ops[0].data, Some(json!({"feed": []})));
+    }
+
+    #[test]
+    fn other_traffic_is_not_graphql() {
+        assert!(!is_graphql(&post(json!({"jsonrpc": "2.0"}), json!({}))));
+        assert!(!is_graphql(&post(json!({"query": 42}), json!({}))));
+        let page = Event::builder().url("https://example.com/?q=shoes").build();
+        assert!(exchanges(&page).is_empty());
+    }
+}
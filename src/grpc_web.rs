@@ -0,0 +1,288 @@
+//! gRPC-Web response decoding.
+//!
+//! gRPC-Web bodies are a sequence of length-prefixed frames: messages, then a trailers frame
+//! carrying `grpc-status` and `grpc-message`. The page hooks capture `application/grpc-web*`
+//! bodies as bytes (see [`Event::base64_encoded`]), and the `-text` variant arrives as base64
+//! text; [`decode_frames`] handles both. Messages stay raw protobuf bytes: decode them with your
+//! own generated types, or inspect them schema-less with [`decode_wire`].
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::{Error, Event, Header};
+
+// Frame flag bits.
+const FLAG_COMPRESSED: u8 = 0x01;
+const FLAG_TRAILERS: u8 = 0x80;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Frame {
+    /// One protobuf message. `compressed` messages use the `grpc-encoding` of the response.
+    Message { compressed: bool, data: Vec<u8> },
+    /// The trailers frame, with lower-cased names.
+    Trailers(Vec<Header>),
+}
+
+/// Whether `event` is a gRPC-Web response.
+pub fn is_grpc_web(event: &Event) -> bool {
+    event
+        .content_type
+        .as_deref()
+        .is_some_and(|ct| ct.to_ascii_lowercase().starts_with("application/grpc-web"))
+}
+
+/// Decode the frames of a gRPC-Web response body.
+pub fn decode_frames(event: &Event) -> Result<Vec<Frame>, Error> {
+    let text_encoded = event
+        .content_type
+        .as_deref()
+        .is_some_and(|ct| ct.to_ascii_lowercase().contains("grpc-web-text"));
+    let bytes = if event.base64_encoded {
+        STANDARD.decode(&event.body).map_err(Error::DecodeBase64)?
+    } else if text_encoded {
+        decode_text_body(&event.body)?
+    } else {
+        event.body.as_bytes().to_vec()
+    };
+    parse_frames(&bytes)
+}
+
+/// Split raw gRPC-Web body bytes into frames.
+pub fn parse_frames(mut bytes: &[u8]) -> Result<Vec<Frame>, Error> {
+    let mut frames = Vec::new();
+    while !bytes.is_empty() {
+        let Some((&[flags, a, b, c, d], rest)) = bytes.split_first_chunk::<5>() else {
+            return Err(Error::DecodeGrpcWeb("truncated frame header".to_string()));
+        };
+        let len = u32::from_be_bytes([a, b, c, d]) as usize;
+        if rest.len() < len {
+            return Err(Error::DecodeGrpcWeb(format!(
+                "frame of {len} bytes but only {} left",
+                rest.len()
+            )));
+        }
+        let (payload, rest) = rest.split_at(len);
+        frames.push(if flags & FLAG_TRAILERS != 0 {
+            Frame::Trailers(parse_trailers(payload))
+        } else {
+            Frame::Message {
+                compressed: flags & FLAG_COMPRESSED != 0,
+                data: payload.to_vec(),
+            }
+        });
+        bytes = rest;
+    }
+    Ok(frames)
+}
+
+/// The `grpc-status` trailer, if the frames include one.
+pub fn grpc_status(frames: &[Frame]) -> Option<u32> {
+    frames.iter().find_map(|frame| match frame {
+        Frame::Trailers(trailers) => trailers
+            .iter()
+            .find(|h| h.name == "grpc-status")
+            .and_then(|h| h.value.trim().parse().ok()),
+        Frame::Message { .. } => None,
+    })
+}
+
+// The `-text` variant may concatenate independently padded base64 chunks.
+fn decode_text_body(body: &str) -> Result<Vec<u8>, Error> {
+    let body: String = body.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    let mut out = Vec::new();
+    let mut rest = body.as_str();
+    while !rest.is_empty() {
+        let end = match rest.find('=') {
+            Some(i) => i + rest[i..].bytes().take_while(|&b| b == b'=').count(),
+            None => rest.len(),
+        };
+        let (chunk, tail) = rest.split_at(end);
+        out.extend(STANDARD.decode(chunk).map_err(Error::DecodeBase64)?);
+        rest = tail;
+    }
+    Ok(out)
+}
+
+fn parse_trailers(payload: &[u8]) -> Vec<Header> {
+    String::from_utf8_lossy(payload)
+        .split("\r\n")
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| Header {
+            name: name.trim().to_ascii_lowercase(),
+            value: value.trim().to_string(),
+        })
+        .collect()
+}
+
+/// A protobuf field decoded without a schema.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WireField {
+    pub number: u32,
+    pub value: WireValue,
+}
+
+/// A protobuf wire value. `Bytes` covers strings, nested messages and packed repeated fields,
+/// which the wire format does not tell apart.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WireValue {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(Vec<u8>),
+    Fixed32(u32),
+}
+
+/// Decode one protobuf message into its top-level fields, without a schema.
+pub fn decode_wire(mut bytes: &[u8]) -> Result<Vec<WireField>, Error> {
+    let mut fields = Vec::new();
+    while !bytes.is_empty() {
+        let key = read_varint(&mut bytes)?;
+        let number = u32::try_from(key >> 3)
+            .map_err(|_| Error::DecodeGrpcWeb("field number out of range".to_string()))?;
+        let value = match key & 0x7 {
+            0 => WireValue::Varint(read_varint(&mut bytes)?),
+            1 => WireValue::Fixed64(u64::from_le_bytes(take(&mut bytes)?)),
+            2 => {
+                let len = read_varint(&mut bytes)? as usize;
+                if bytes.len() < len {
+                    return Err(Error::DecodeGrpcWeb(
+                        "truncated length-delimited field".to_string(),
+                    ));
+                }
+                let (data, rest) = bytes.split_at(len);
+                bytes = rest;
+                WireValue::Bytes(data.to_vec())
+            }
+            5 => WireValue::Fixed32(u32::from_le_bytes(take(&mut bytes)?)),
+            other => {
+                return Err(Error::DecodeGrpcWeb(format!(
+                    "unsupported wire type {other}"
+                )));
+            }
+        };
+        fields.push(WireField { number, value });
+    }
+    Ok(fields)
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, Error> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let Some((&byte, rest)) = bytes.split_first() else {
+            break;
+        };
+        *bytes = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::DecodeGrpcWeb("malformed varint".to_string()))
+}
+
+fn take<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N], Error> {
+    let (head, rest) = bytes
+        .split_first_chunk::<N>()
+        .ok_or_else(|| Error::DecodeGrpcWeb("truncated fixed-width field".to_string()))?;
+    *bytes = rest;
+    Ok(*head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![flags];
+        out.extend_from_slice(&u32::try_from(payload.len()).unwrap().to_be_bytes());
+        out.extend_from_slice(payload);
+        out
+    }
+
+    #[test]
+    fn parse_frames_splits_messages_and_trailers() {
+        let mut body = frame(0, &[0x08, 0x96, 0x01]);
+        body.extend(frame(FLAG_COMPRESSED, b"zz"));
+        body.extend(frame(
+            FLAG_TRAILERS,
+            b"Grpc-Status: 0\r\ngrpc-message: ok\r\n",
+        ));
+        let frames = parse_frames(&body).unwrap();
+        assert_eq!(
+            frames[..2],
+            [
+                Frame::Message {
+                    compressed: false,
+                    data: vec![0x08, 0x96, 0x01],
+                },
+                Frame::Message {
+                    compressed: true,
+                    data: b"zz".to_vec(),
+                },
+            ]
+        );
+        assert_eq!(grpc_status(&frames), Some(0));
+        let Frame::Trailers(trailers) = &frames[2] else {
+            panic!("expected trailers, got {frames:?}");
+        };
+        assert_eq!(trailers[1].name, "grpc-message");
+        assert_eq!(trailers[1].value, "ok");
+    }
+
+    #[test]
+    fn parse_frames_rejects_truncation() {
+        assert!(matches!(
+            parse_frames(&[0, 0, 0]),
+            Err(Error::DecodeGrpcWeb(_))
+        ));
+        assert!(matches!(
+            parse_frames(&[0, 0, 0, 0, 5, 1]),
+            Err(Error::DecodeGrpcWeb(_))
+        ));
+        assert_eq!(parse_frames(&[]).unwrap(), []);
+    }
+
+    #[test]
+    fn decode_frames_reads_text_bodies_in_padded_chunks() {
+        let message = frame(0, b"hi");
+        let trailers = frame(FLAG_TRAILERS, b"grpc-status: 5\r\n");
+        let body = format!(
+            "{}\n{}",
+            STANDARD.encode(&message),
+            STANDARD.encode(&trailers)
+        );
+        let event = Event::builder()
+            .content_type("application/grpc-web-text+proto")
+            .body(body)
+            .build();
+        assert!(is_grpc_web(&event));
+        let frames = decode_frames(&event).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(grpc_status(&frames), Some(5));
+    }
+
+    #[test]
+    fn decode_wire_reads_every_wire_type() {
+        let bytes = [
+            0x08, 0x96, 0x01, // 1: varint 150
+            0x12, 0x02, b'h', b'i', // 2: bytes "hi"
+            0x1d, 1, 0, 0, 0, // 3: fixed32 1
+            0x21, 2, 0, 0, 0, 0, 0, 0, 0, // 4: fixed64 2
+        ];
+        let values: Vec<_> = decode_wire(&bytes)
+            .unwrap()
+            .into_iter()
+            .map(|f| (f.number, f.value))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                (1, WireValue::Varint(150)),
+                (2, WireValue::Bytes(b"hi".to_vec())),
+                (3, WireValue::Fixed32(1)),
+                (4, WireValue::Fixed64(2)),
+            ]
+        );
+        assert!(decode_wire(&[0x08, 0x96]).is_err());
+        assert!(decode_wire(&[0x0b]).is_err());
+    }
+}
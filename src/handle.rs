@@ -0,0 +1,394 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use chromiumoxide::page::Page;
+use futures::channel::mpsc;
+
+use crate::blocked::BlockedRequest;
+use crate::cache::{CacheSource, CacheSources};
+use crate::cookies::CookieJar;
+use crate::intercept::OriginalBodies;
+use crate::navigation::Navigations;
+use crate::priority::Priorities;
+use crate::websocket::WebSocketHandshake;
+use crate::{CaptureError, Error, EventStreamConfig, NetworkConditions, RequestStarted};
+
+/// Handle to a running event stream, returned alongside the receiver by
+/// [`start_event_stream_with_handle`](crate::start_event_stream_with_handle).
+///
+/// Cloning is cheap; dropping every handle does not stop the stream.
+#[derive(Clone, Debug)]
+pub struct EventStreamHandle {
+    pub(crate) shared: Arc<Shared>,
+    page: Page,
+}
+
+// State shared between the handle and the background task.
+#[derive(Debug, Default)]
+pub(crate) struct Shared {
+    pub(crate) events_emitted: AtomicU64,
+    pub(crate) events_filtered: AtomicU64,
+    pub(crate) bodies_failed: AtomicU64,
+    pub(crate) bytes_captured: AtomicU64,
+    pub(crate) in_flight: AtomicU64,
+    pub(crate) events_evicted: AtomicU64,
+    pub(crate) restarts: AtomicU64,
+    pub(crate) long_running: AtomicU64,
+    from_network: AtomicU64,
+    from_disk_cache: AtomicU64,
+    from_memory_cache: AtomicU64,
+    from_service_worker: AtomicU64,
+    pub(crate) paused: AtomicBool,
+    errors: Mutex<Option<mpsc::Receiver<CaptureError>>>,
+    starts: Mutex<Option<mpsc::UnboundedSender<RequestStarted>>>,
+    starts_rx: Mutex<Option<mpsc::UnboundedReceiver<RequestStarted>>>,
+    blocked: Mutex<Option<mpsc::UnboundedSender<BlockedRequest>>>,
+    blocked_rx: Mutex<Option<mpsc::UnboundedReceiver<BlockedRequest>>>,
+    websockets: Mutex<Option<mpsc::UnboundedSender<WebSocketHandshake>>>,
+    websockets_rx: Mutex<Option<mpsc::UnboundedReceiver<WebSocketHandshake>>>,
+    end_reason: Mutex<Option<StreamEndReason>>,
+    running: AtomicBool,
+    last_event_at: Mutex<Option<Instant>>,
+    pub(crate) cookies: Mutex<CookieJar>,
+    network_condition: Mutex<Option<String>>,
+    pub(crate) original_bodies: Arc<OriginalBodies>,
+    pub(crate) cache_sources: Arc<CacheSources>,
+    pub(crate) priorities: Arc<Priorities>,
+    pub(crate) navigations: Mutex<Navigations>,
+}
+
+impl Shared {
+    pub(crate) fn record_event(&self) {
+        *self.last_event_at.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+    }
+
+    /// Label of the network conditions currently emulated, if any.
+    pub(crate) fn network_condition(&self) -> Option<String> {
+        self.network_condition
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub(crate) fn stats(&self) -> StreamStats {
+        StreamStats {
+            events_emitted: self.events_emitted.load(Ordering::Relaxed),
+            events_filtered: self.events_filtered.load(Ordering::Relaxed),
+            bodies_failed: self.bodies_failed.load(Ordering::Relaxed),
+            bytes_captured: self.bytes_captured.load(Ordering::Relaxed),
+            in_flight_requests: self.in_flight.load(Ordering::Relaxed),
+            events_evicted: self.events_evicted.load(Ordering::Relaxed),
+            restarts: self.restarts.load(Ordering::Relaxed),
+            long_running: self.long_running.load(Ordering::Relaxed),
+            from_network: self.from_network.load(Ordering::Relaxed),
+            from_disk_cache: self.from_disk_cache.load(Ordering::Relaxed),
+            from_memory_cache: self.from_memory_cache.load(Ordering::Relaxed),
+            from_service_worker: self.from_service_worker.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn record_cache_source(&self, source: CacheSource) {
+        let counter = match source {
+            CacheSource::Network => &self.from_network,
+            CacheSource::DiskCache => &self.from_disk_cache,
+            CacheSource::MemoryCache => &self.from_memory_cache,
+            CacheSource::ServiceWorker => &self.from_service_worker,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Forward `start` to the handle's receiver, if the stream has one.
+    pub(crate) fn send_request_start(&self, start: RequestStarted) {
+        if let Some(starts) = &*self.starts.lock().unwrap_or_else(|e| e.into_inner()) {
+            // A dropped receiver just means nobody is watching.
+            let _ = starts.unbounded_send(start);
+        }
+    }
+
+    pub(crate) fn send_blocked_request(&self, blocked: BlockedRequest) {
+        if let Some(tx) = &*self.blocked.lock().unwrap_or_else(|e| e.into_inner()) {
+            let _ = tx.unbounded_send(blocked);
+        }
+    }
+
+    pub(crate) fn send_websocket_handshake(&self, handshake: WebSocketHandshake) {
+        if let Some(tx) = &*self.websockets.lock().unwrap_or_else(|e| e.into_inner()) {
+            let _ = tx.unbounded_send(handshake);
+        }
+    }
+
+    /// Close the request-start, blocked-request and WebSocket channels; the stream is ending.
+    pub(crate) fn close_side_channels(&self) {
+        *self.starts.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        *self.blocked.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        *self.websockets.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    pub(crate) fn set_end_reason(&self, reason: StreamEndReason) {
+        *self.end_reason.lock().unwrap_or_else(|e| e.into_inner()) = Some(reason);
+    }
+}
+
+/// Held by the background task; clears `running` however the task ends, including by panic.
+pub(crate) struct RunningGuard(pub(crate) Arc<Shared>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.running.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Why a stream's background task stopped, see [`EventStreamHandle::end_reason`].
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Eq)]
+pub enum StreamEndReason {
+    /// Every event receiver was dropped.
+    #[error("receiver dropped")]
+    ReceiverDropped,
+    /// The debugger detached from the target, e.g. because the tab closed or crashed.
+    #[error("target detached: {0}")]
+    TargetDetached(String),
+    /// The page, or the connection to the browser, went away.
+    #[error("page closed")]
+    PageClosed,
+    /// Draining the page buffer failed for another reason.
+    #[error("drain failed: {0}")]
+    DrainFailed(String),
+    /// The poll loop panicked more often than `max_restarts` allows. Holds the panic message.
+    #[error("panicked: {0}")]
+    Panicked(String),
+    /// The [`capture_for`](crate::EventStreamConfig::capture_for) window elapsed.
+    #[error("capture window elapsed")]
+    CaptureWindowElapsed,
+    /// [`max_events`](crate::EventStreamConfig::max_events) events were sent.
+    #[error("max events reached")]
+    MaxEventsReached,
+}
+
+/// Snapshot of a stream's counters, see [`EventStreamHandle::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// Events sent to the receiver.
+    pub events_emitted: u64,
+    /// Events drained from the page but rejected by this stream's filters.
+    pub events_filtered: u64,
+    /// Captured responses whose body the page failed to read.
+    pub bodies_failed: u64,
+    /// Total body bytes of emitted events.
+    pub bytes_captured: u64,
+    /// Requests started by the page whose response had not been read at the last poll.
+    pub in_flight_requests: u64,
+    /// Events discarded because the page buffer hit `max_buffered_events`.
+    pub events_evicted: u64,
+    /// Times the poll loop was restarted after a panic.
+    pub restarts: u64,
+    /// Emitted events tagged [`long_running`](crate::Event::long_running).
+    pub long_running: u64,
+    /// Emitted events by [`CacheSource`], with
+    /// [`tag_cache_source`](crate::EventStreamConfig::tag_cache_source) set.
+    pub from_network: u64,
+    pub from_disk_cache: u64,
+    pub from_memory_cache: u64,
+    pub from_service_worker: u64,
+}
+
+impl StreamStats {
+    /// Share of tagged events served from the HTTP cache, from `0.0` to `1.0`, or `None` before
+    /// any event was tagged.
+    pub fn cache_hit_ratio(&self) -> Option<f64> {
+        let hits = self.from_disk_cache + self.from_memory_cache;
+        let total = hits + self.from_network + self.from_service_worker;
+        (total > 0).then(|| hits as f64 / total as f64)
+    }
+}
+
+impl EventStreamHandle {
+    pub(crate) fn new(
+        page: Page,
+        errors: mpsc::Receiver<CaptureError>,
+        config: &EventStreamConfig,
+    ) -> Self {
+        let (starts, starts_rx) = side_channel(config.emit_request_starts);
+        let (blocked, blocked_rx) = side_channel(config.report_blocked_requests);
+        let (websockets, websockets_rx) = side_channel(config.capture_websocket_handshakes);
+        Self {
+            shared: Arc::new(Shared {
+                errors: Mutex::new(Some(errors)),
+                starts: Mutex::new(starts),
+                starts_rx: Mutex::new(starts_rx),
+                blocked: Mutex::new(blocked),
+                blocked_rx: Mutex::new(blocked_rx),
+                websockets: Mutex::new(websockets),
+                websockets_rx: Mutex::new(websockets_rx),
+                running: AtomicBool::new(true),
+                ..Shared::default()
+            }),
+            page,
+        }
+    }
+
+    pub fn stats(&self) -> StreamStats {
+        self.shared.stats()
+    }
+
+    /// Whether the background task is still alive. Turns `false` when it stops for any reason.
+    pub fn is_running(&self) -> bool {
+        self.shared.running.load(Ordering::Relaxed)
+    }
+
+    /// When the last event was sent to the receiver, or `None` if none has been yet.
+    ///
+    /// A stream that [is running](Self::is_running) but has not produced an event for a long
+    /// time may simply be watching a quiet page; compare against your expected traffic.
+    pub fn last_event_at(&self) -> Option<Instant> {
+        *self
+            .shared
+            .last_event_at
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Why the stream stopped, or `None` while it is still running.
+    ///
+    /// When this is set the event receiver has yielded (or will yield) its last event, which
+    /// tells a dead page apart from a quiet one.
+    pub fn end_reason(&self) -> Option<StreamEndReason> {
+        self.shared
+            .end_reason
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// A snapshot of the cookie jar built from the page's traffic so far, see [`CookieJar`].
+    /// Empty unless [`track_cookies`](crate::EventStreamConfig::track_cookies) is set.
+    pub fn cookies(&self) -> CookieJar {
+        self.shared
+            .cookies
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Throttle the page's network with `Network.emulateNetworkConditions`. Events drained from
+    /// then on carry `conditions.label` in [`Event::network_condition`](crate::Event::network_condition);
+    /// a request that straddles the switch is tagged with whichever was active when it was drained.
+    pub async fn emulate_network(&self, conditions: NetworkConditions) -> Result<(), Error> {
+        self.page
+            .execute(conditions.params())
+            .await
+            .map_err(Error::ConfigureNetwork)?;
+        *self
+            .shared
+            .network_condition
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(conditions.label);
+        Ok(())
+    }
+
+    /// Stop emulating network conditions.
+    pub async fn clear_network_emulation(&self) -> Result<(), Error> {
+        self.page
+            .execute(NetworkConditions::unthrottled_params())
+            .await
+            .map_err(Error::ConfigureNetwork)?;
+        *self
+            .shared
+            .network_condition
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = None;
+        Ok(())
+    }
+
+    /// Cut the page off from the network, or reconnect it. Going offline replaces any
+    /// [`emulate_network`](Self::emulate_network) throttling, and going back online clears it.
+    /// Set [`capture_failed_requests`](crate::EventStreamConfig::capture_failed_requests) to see
+    /// the requests that fail meanwhile in the same stream.
+    pub async fn set_offline(&self, offline: bool) -> Result<(), Error> {
+        if offline {
+            self.emulate_network(NetworkConditions::offline()).await
+        } else {
+            self.clear_network_emulation().await
+        }
+    }
+
+    /// Stop draining the page. Captures stay in the page buffer, bounded by
+    /// [`max_buffered_events`](crate::EventStreamConfig::max_buffered_events), until
+    /// [`resume`](Self::resume).
+    pub fn pause(&self) {
+        self.shared.paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Start draining again, from the next poll. Also starts a stream created with
+    /// [`start_paused`](crate::EventStreamConfig::start_paused).
+    pub fn resume(&self) {
+        self.shared.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.shared.paused.load(Ordering::Relaxed)
+    }
+
+    /// Take the receiver of [`CaptureError`]s for this stream.
+    ///
+    /// There is one receiver per stream, so this returns `Some` only on the first call across all
+    /// clones of the handle. The channel is bounded; errors are dropped while it is full. When the
+    /// background task stops it sends [`CaptureError::StreamEnded`] (if there is room) and closes.
+    pub fn take_errors(&self) -> Option<mpsc::Receiver<CaptureError>> {
+        self.shared
+            .errors
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// Take the receiver of [`RequestStarted`]s for this stream, see
+    /// [`emit_request_starts`](crate::EventStreamConfig::emit_request_starts). Like
+    /// [`take_errors`](Self::take_errors), this returns `Some` only once, and `None` if the
+    /// option is off. The channel closes when the stream ends.
+    pub fn take_request_starts(&self) -> Option<mpsc::UnboundedReceiver<RequestStarted>> {
+        self.shared
+            .starts_rx
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// Take the receiver of [`BlockedRequest`]s for this stream, see
+    /// [`report_blocked_requests`](crate::EventStreamConfig::report_blocked_requests). Returns
+    /// `Some` only once, and `None` if the option is off; the channel closes when the stream
+    /// ends.
+    pub fn take_blocked_requests(&self) -> Option<mpsc::UnboundedReceiver<BlockedRequest>> {
+        self.shared
+            .blocked_rx
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+
+    /// Take the receiver of [`WebSocketHandshake`]s for this stream, see
+    /// [`capture_websocket_handshakes`](crate::EventStreamConfig::capture_websocket_handshakes).
+    /// Returns `Some` only once, and `None` if the option is off; the channel closes when the
+    /// stream ends.
+    pub fn take_websocket_handshakes(&self) -> Option<mpsc::UnboundedReceiver<WebSocketHandshake>> {
+        self.shared
+            .websockets_rx
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+    }
+}
+
+// A side-channel pair, only when the stream reports on it; senders without one are no-ops.
+fn side_channel<T>(
+    enabled: bool,
+) -> (
+    Option<mpsc::UnboundedSender<T>>,
+    Option<mpsc::UnboundedReceiver<T>>,
+) {
+    if !enabled {
+        return (None, None);
+    }
+    let (tx, rx) = mpsc::unbounded();
+    (Some(tx), Some(rx))
+}
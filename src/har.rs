@@ -0,0 +1,417 @@
+//! HAR 1.2 export and import.
+//!
+//! Feed events to a [`HarRecorder`] as they arrive and call [`HarRecorder::finish`] to get a
+//! [`Har`] document, or let [`record_har`] drain a receiver for you. Fields the page-side hooks
+//! cannot observe (cookies, HTTP version, header sizes, connection timings) are written with the
+//! placeholder values the spec allows.
+//!
+//! [`Har::read_from_file`] and [`Har::to_events`] go the other way, so HAR files saved from
+//! DevTools or other tools can be analysed or served as [`Mocks`](crate::Mocks).
+
+use std::path::Path;
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Event};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Har {
+    pub log: Log,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Log {
+    pub version: String,
+    pub creator: Creator,
+    #[serde(default)]
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Creator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    pub started_date_time: String,
+    /// Total elapsed time of the request in milliseconds.
+    pub time: f64,
+    pub request: Request,
+    pub response: Response,
+    #[serde(default)]
+    pub cache: serde_json::Value,
+    pub timings: Timings,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<NameValue>,
+    #[serde(default)]
+    pub headers: Vec<NameValue>,
+    #[serde(default)]
+    pub query_string: Vec<NameValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<PostData>,
+    #[serde(default = "unknown_size")]
+    pub headers_size: i64,
+    #[serde(default = "unknown_size")]
+    pub body_size: i64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Response {
+    pub status: u16,
+    #[serde(default)]
+    pub status_text: String,
+    #[serde(default)]
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<NameValue>,
+    #[serde(default)]
+    pub headers: Vec<NameValue>,
+    pub content: Content,
+    #[serde(default, rename = "redirectURL")]
+    pub redirect_url: String,
+    #[serde(default = "unknown_size")]
+    pub headers_size: i64,
+    #[serde(default = "unknown_size")]
+    pub body_size: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct NameValue {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostData {
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default)]
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Content {
+    pub size: i64,
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+/// Phase timings in milliseconds; `-1` marks a phase that was not measured.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Timings {
+    pub send: f64,
+    pub wait: f64,
+    pub receive: f64,
+}
+
+fn unknown_size() -> i64 {
+    -1
+}
+
+impl Har {
+    pub fn to_json_string(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(Error::SerializeJson)
+    }
+
+    pub async fn write_to_file(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        tokio::fs::write(path, self.to_json_string()?)
+            .await
+            .map_err(Error::Io)
+    }
+
+    pub fn from_json_str(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json).map_err(Error::ParseJson)
+    }
+
+    pub async fn read_from_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let json = tokio::fs::read_to_string(path).await.map_err(Error::Io)?;
+        Self::from_json_str(&json)
+    }
+
+    /// One event per entry, in log order. Entries with status `0`, which DevTools writes for
+    /// requests that never got a response, become events without a status.
+    pub fn to_events(&self) -> Vec<Event> {
+        self.log.entries.iter().map(event_from_entry).collect()
+    }
+}
+
+/// Incrementally assembles captured events into a HAR log.
+#[derive(Clone, Debug, Default)]
+pub struct HarRecorder {
+    entries: Vec<Entry>,
+}
+
+impl HarRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, event: &Event) {
+        self.entries.push(entry_from_event(event));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Build the HAR document from every recorded event, in recording order.
+    pub fn finish(self) -> Har {
+        Har {
+            log: Log {
+                version: "1.2".to_string(),
+                creator: Creator {
+                    name: env!("CARGO_PKG_NAME").to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                },
+                entries: self.entries,
+            },
+        }
+    }
+}
+
+/// Record every event from the receiver until the stream closes and return the HAR document.
+pub async fn record_har(rx: &mut mpsc::UnboundedReceiver<Event>) -> Har {
+    let mut recorder = HarRecorder::new();
+    while let Some(event) = rx.next().await {
+        recorder.record(&event);
+    }
+    recorder.finish()
+}
+
+fn entry_from_event(event: &Event) -> Entry {
+    let duration = event.duration_ms.unwrap_or(0.0);
+    let wait = event.time_to_headers_ms.unwrap_or(duration);
+    let request_content_type = event.request_header("content-type").unwrap_or_default();
+
+    Entry {
+        started_date_time: format_epoch_millis(event.started_at.unwrap_or(0.0)),
+        time: duration,
+        request: Request {
+            method: event.method.clone().unwrap_or_else(|| "GET".to_string()),
+            url: event.url.clone(),
+            http_version: String::new(),
+            cookies: Vec::new(),
+            headers: name_values(&event.request_headers),
+            query_string: query_string(&event.url),
+            post_data: event.request_body.as_ref().map(|text| PostData {
+                mime_type: request_content_type.to_string(),
+                text: text.clone(),
+            }),
+            headers_size: -1,
+            body_size: event.request_body.as_ref().map_or(0, |b| b.len() as i64),
+        },
+        response: Response {
+            status: event.status.unwrap_or(0),
+            status_text: String::new(),
+            http_version: String::new(),
+            cookies: Vec::new(),
+            headers: name_values(&event.response_headers),
+            content: Content {
+                size: event.body_len() as i64,
+                mime_type: event.content_type.clone().unwrap_or_default(),
+                text: Some(event.body.clone()),
+                encoding: event.base64_encoded.then(|| "base64".to_string()),
+            },
+            redirect_url: event
+                .response_header("location")
+                .unwrap_or_default()
+                .to_string(),
+            headers_size: -1,
+            body_size: event.body_len() as i64,
+        },
+        cache: serde_json::json!({}),
+        timings: Timings {
+            send: 0.0,
+            wait,
+            receive: (duration - wait).max(0.0),
+        },
+    }
+}
+
+fn event_from_entry(entry: &Entry) -> Event {
+    let content = &entry.response.content;
+    let wait = entry.timings.send.max(0.0) + entry.timings.wait.max(0.0);
+    Event {
+        url: entry.request.url.clone(),
+        content_type: (!content.mime_type.is_empty()).then(|| content.mime_type.clone()),
+        status: (entry.response.status != 0).then_some(entry.response.status),
+        body: content.text.clone().unwrap_or_default(),
+        base64_encoded: content.encoding.as_deref() == Some("base64"),
+        method: Some(entry.request.method.clone()),
+        request_headers: headers(&entry.request.headers),
+        request_body: entry.request.post_data.as_ref().map(|p| p.text.clone()),
+        request_parts: None,
+        response_headers: headers(&entry.response.headers),
+        started_at: parse_epoch_millis(&entry.started_date_time),
+        time_to_headers_ms: Some(wait),
+        duration_ms: Some(entry.time),
+        received_at: parse_epoch_millis(&entry.started_date_time).map(|t| t + wait),
+        ..Event::default()
+    }
+}
+
+fn headers(name_values: &[NameValue]) -> Vec<crate::Header> {
+    name_values
+        .iter()
+        .map(|nv| crate::Header {
+            name: nv.name.to_ascii_lowercase(),
+            value: nv.value.clone(),
+        })
+        .collect()
+}
+
+fn name_values(headers: &[crate::Header]) -> Vec<NameValue> {
+    headers
+        .iter()
+        .map(|h| NameValue {
+            name: h.name.clone(),
+            value: h.value.clone(),
+        })
+        .collect()
+}
+
+fn query_string(url: &str) -> Vec<NameValue> {
+    url::Url::parse(url)
+        .map(|u| {
+            u.query_pairs()
+                .map(|(name, value)| NameValue {
+                    name: name.into_owned(),
+                    value: value.into_owned(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Format milliseconds since the Unix epoch as an ISO 8601 UTC timestamp with millisecond precision.
+pub(crate) fn format_epoch_millis(ms: f64) -> String {
+    let total_ms = ms.max(0.0) as i64;
+    let (secs, millis) = (total_ms.div_euclid(1000), total_ms.rem_euclid(1000));
+    let (days, secs_of_day) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // Civil-from-days, after Howard Hinnant's date algorithms.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{millis:03}Z",
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+    )
+}
+
+/// Parse an ISO 8601 timestamp such as `2024-05-01T12:00:00.123Z` or
+/// `2024-05-01T14:00:00.123+02:00` into milliseconds since the Unix epoch.
+pub(crate) fn parse_epoch_millis(timestamp: &str) -> Option<f64> {
+    let (date, time) = timestamp.split_once('T')?;
+    let mut ymd = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (ymd.next()?.ok()?, ymd.next()?.ok()?, ymd.next()?.ok()?);
+
+    let (clock, offset_secs) = if let Some(clock) = time.strip_suffix('Z') {
+        (clock, 0)
+    } else {
+        let i = time.rfind(['+', '-'])?;
+        let (h, m) = time[i + 1..]
+            .split_once(':')
+            .unwrap_or((&time[i + 1..], "0"));
+        let offset = h.parse::<i64>().ok()? * 3600 + m.parse::<i64>().ok()? * 60;
+        (
+            &time[..i],
+            if &time[i..=i] == "-" { -offset } else { offset },
+        )
+    };
+    let mut hms = clock.splitn(3, ':');
+    let hour: i64 = hms.next()?.parse().ok()?;
+    let minute: i64 = hms.next()?.parse().ok()?;
+    let second: f64 = hms.next().unwrap_or("0").parse().ok()?;
+
+    // Days-from-civil, the inverse of `format_epoch_millis`.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let secs = days * 86_400 + hour * 3600 + minute * 60 - offset_secs;
+    Some(secs as f64 * 1000.0 + second * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_bodies_report_their_decoded_size() {
+        let event = Event::builder()
+            .content_type("image/png")
+            .bytes_body([0u8; 10])
+            .build();
+        let entry = entry_from_event(&event);
+        assert_eq!(entry.response.content.size, 10);
+        assert_eq!(entry.response.body_size, 10);
+        assert_eq!(entry.response.content.encoding.as_deref(), Some("base64"));
+    }
+
+    #[test]
+    fn epoch_millis_round_trip() {
+        assert_eq!(format_epoch_millis(0.0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            format_epoch_millis(951_782_400_123.9),
+            "2000-02-29T00:00:00.123Z"
+        );
+        assert_eq!(format_epoch_millis(-5.0), "1970-01-01T00:00:00.000Z");
+        for ms in [
+            0.0,
+            951_782_400_123.0,
+            1_714_564_800_500.0,
+            4_102_444_799_999.0,
+        ] {
+            assert_eq!(parse_epoch_millis(&format_epoch_millis(ms)), Some(ms));
+        }
+    }
+
+    #[test]
+    fn parse_epoch_millis_applies_offsets() {
+        let utc = parse_epoch_millis("2024-05-01T12:00:00.123Z");
+        assert_eq!(utc, Some(1_714_564_800_123.0));
+        assert_eq!(parse_epoch_millis("2024-05-01T14:00:00.123+02:00"), utc);
+        assert_eq!(parse_epoch_millis("2024-05-01T06:30:00.123-05:30"), utc);
+        assert_eq!(parse_epoch_millis("2024-05-01"), None);
+        assert_eq!(parse_epoch_millis("not a date"), None);
+    }
+}
@@ -0,0 +1,619 @@
+// Page-side capture hooks, installed by `install_event_hooks`.
+// `cfg` is built from `EventStreamConfig`; captured events are pushed to `window.__event_stream`.
+function(cfg) {
+  try {
+    window.__event_stream = window.__event_stream || [];
+    window.__event_in_flight = window.__event_in_flight || 0;
+    window.__event_body_failures = window.__event_body_failures || 0;
+    window.__event_evicted = window.__event_evicted || 0;
+    window.__event_errors = window.__event_errors || [];
+    window.__event_next_seq = window.__event_next_seq || 0;
+    window.__event_next_request = window.__event_next_request || 0;
+    window.__event_starts = window.__event_starts || [];
+    window.__event_order = window.__event_order || [];
+    window.__event_nav_bounds = window.__event_nav_bounds || [];
+    window.__event_nav_url = window.__event_nav_url || location.href;
+    const urlFilter = cfg.urlFilter; // string or null
+    const ctFilter = cfg.ctFilter;   // string or null
+    const ctAny = cfg.ctAny || [];   // array of strings, any may match
+    const urlExclude = cfg.urlExclude || []; // array of strings
+    const maxBuffered = cfg.maxBuffered; // number or null
+    const dropNewest = cfg.dropNewest;   // bool
+    const ordered = cfg.ordered;         // bool
+    const splitSse = cfg.splitSse;       // bool
+    const splitNdjson = cfg.splitNdjson; // bool
+    const partBodyLimit = cfg.partBodyLimit || 0; // bytes of FormData text to keep
+    const captureFailures = cfg.captureFailures; // bool
+    const sameOriginOnly = cfg.sameOriginOnly;   // bool
+    const faults = cfg.faults; // {bodyFailureRate, maxDelayMs} or null
+    const bodyTimeoutMs = cfg.bodyTimeoutMs; // number or null
+    const emitStarts = cfg.emitStarts;       // bool
+    const extractors = cfg.extractors || []; // [{name, selector: {css|xpath}, attribute, urlFilter}]
+    // How long an unfinished response may hold back later ones in ordered mode.
+    const ORDER_TIMEOUT_MS = 10000;
+
+    // Bounded push: once the buffer is full, evict the oldest event or discard the new one.
+    function enqueue(ev) {
+      const buf = window.__event_stream;
+      if (maxBuffered && buf.length >= maxBuffered) {
+        window.__event_evicted++;
+        if (dropNewest) return;
+        buf.shift();
+      }
+      buf.push(ev);
+    }
+
+    // Injected delays hold events back at random; they count as in flight meanwhile.
+    function deliver(ev) {
+      if (!faults || !(faults.maxDelayMs > 0)) return enqueue(ev);
+      window.__event_in_flight++;
+      setTimeout(function() {
+        window.__event_in_flight--;
+        enqueue(ev);
+      }, Math.random() * faults.maxDelayMs);
+    }
+
+    function injectBodyFailure() {
+      return !!faults && Math.random() < faults.bodyFailureRate;
+    }
+
+    function bodyFailed(url, err) {
+      window.__event_body_failures++;
+      // Details for the error channel; the counter above stays exact if this is capped.
+      if (window.__event_errors.length < 100) {
+        window.__event_errors.push({ url: url, message: String((err && err.message) || err) });
+      }
+    }
+
+    // A response gets its sequence number when its headers arrive and its event(s) once the
+    // body has been read. In ordered mode, events are released to the buffer in sequence order.
+    function reserve() {
+      const slot = { seq: window.__event_next_seq++, at: performance.now(), done: false, evs: [] };
+      if (ordered) window.__event_order.push(slot);
+      return slot;
+    }
+
+    // DOMParser type for an HTML/XML content type, or null.
+    function markupType(ct) {
+      const t = (ct || '').split(';')[0].trim().toLowerCase();
+      if (t === 'text/html') return 'text/html';
+      if (t === 'application/xhtml+xml') return t;
+      if (t === 'text/xml' || t === 'application/xml' || t.endsWith('+xml')) return 'application/xml';
+      return null;
+    }
+
+    function nodeValue(node, attribute) {
+      if (attribute) return node.getAttribute ? node.getAttribute(attribute) : null;
+      return (node.textContent || '').trim();
+    }
+
+    function runSelector(doc, ex) {
+      const out = [];
+      if (ex.selector.css !== undefined) {
+        doc.querySelectorAll(ex.selector.css).forEach(function(n) { out.push(nodeValue(n, ex.attribute)); });
+        return out;
+      }
+      const r = doc.evaluate(ex.selector.xpath, doc, null, XPathResult.ANY_TYPE, null);
+      switch (r.resultType) {
+        case XPathResult.NUMBER_TYPE: return [String(r.numberValue)];
+        case XPathResult.STRING_TYPE: return [r.stringValue];
+        case XPathResult.BOOLEAN_TYPE: return [String(r.booleanValue)];
+      }
+      for (let n = r.iterateNext(); n; n = r.iterateNext()) out.push(nodeValue(n, ex.attribute));
+      return out;
+    }
+
+    // Run the configured extractors over a markup body.
+    function extract(ev) {
+      const type = markupType(ev.contentType);
+      if (!type || ev.base64Encoded || typeof DOMParser === 'undefined') return;
+      const applicable = extractors.filter(function(ex) { return !ex.urlFilter || ev.url.includes(ex.urlFilter); });
+      if (!applicable.length) return;
+      let doc;
+      try {
+        doc = new DOMParser().parseFromString(ev.body, type);
+        if (type !== 'text/html' && doc.getElementsByTagName('parsererror').length) return;
+      } catch(e) { return; }
+      const extracted = {};
+      applicable.forEach(function(ex) {
+        try {
+          const values = runSelector(doc, ex).filter(function(v) { return v !== null; });
+          if (values.length) extracted[ex.name] = (extracted[ex.name] || []).concat(values);
+        } catch(e) {}
+      });
+      ev.extracted = extracted;
+    }
+
+    // Add an event for `slot`; `ev` is null when the body could not be read.
+    function push(slot, ev) {
+      if (!ev) return;
+      ev.seq = slot.seq;
+      ev.pageUrl = location.href;
+      ev.navigationId = navigationOf(ev.requestId);
+      ev.documentStartedAt = performance.timeOrigin;
+      if (extractors.length) extract(ev);
+      if (!ordered || slot.released) deliver(ev);
+      else slot.evs.push(ev);
+    }
+
+    // Later responses no longer wait for `slot`.
+    function finish(slot) {
+      slot.done = true;
+      if (ordered) flushOrdered();
+    }
+
+    function complete(slot, ev) {
+      push(slot, ev);
+      finish(slot);
+    }
+
+    // Release finished slots from the head; a head older than the timeout is released
+    // unfinished and its events are enqueued whenever they arrive.
+    function flushOrdered() {
+      const q = window.__event_order;
+      const now = performance.now();
+      while (q.length && (q[0].done || now - q[0].at > ORDER_TIMEOUT_MS)) {
+        const slot = q.shift();
+        slot.released = true;
+        slot.evs.splice(0).forEach(deliver);
+      }
+    }
+    window.__event_flush_ordered = flushOrdered;
+
+    // Number a request as it goes out and, if enabled, record its start. Starts are filtered by
+    // URL only, as the content type is not known yet, and share the event buffer's bound.
+    function started(url, method, startedAt) {
+      const id = window.__event_next_request++;
+      if (emitStarts && shouldCapture(url, null)) {
+        const buf = window.__event_starts;
+        if (maxBuffered && buf.length >= maxBuffered) buf.shift();
+        buf.push({ requestId: id, url: url, method: method, startedAt: startedAt, pageUrl: location.href });
+      }
+      return id;
+    }
+
+    // A same-document navigation starts with the next request: `__event_nav_bounds` holds the
+    // request number each one started at, counted per document like `seq`.
+    function navigated() {
+      if (location.href === window.__event_nav_url) return;
+      window.__event_nav_url = location.href;
+      window.__event_nav_bounds.push(window.__event_next_request);
+    }
+
+    // Navigation a request went out in; requests that were not numbered get the current one.
+    function navigationOf(id) {
+      const bounds = window.__event_nav_bounds;
+      if (id === null || id === undefined) return bounds.length;
+      let n = 0;
+      while (n < bounds.length && bounds[n] <= id) n++;
+      return n;
+    }
+
+    // `ct` is null for requests that failed without a response; the content-type filter
+    // does not apply to them.
+    function shouldCapture(url, ct) {
+      const okUrl = !urlFilter || (url && url.indexOf(urlFilter) !== -1);
+      const okCt = ct === null || ((!ctFilter || (ct && ct.indexOf(ctFilter) !== -1)) &&
+        (!ctAny.length || (!!ct && ctAny.some(function(s) { return ct.indexOf(s) !== -1; }))));
+      const excluded = !!url && urlExclude.some(function(s) { return url.indexOf(s) !== -1; });
+      return okUrl && okCt && !excluded && (!sameOriginOnly || isSameOrigin(url));
+    }
+
+    function isSameOrigin(url) {
+      try { return new URL(String(url), location.href).origin === location.origin; } catch(e) { return true; }
+    }
+
+    // Bodies of any type but these are read as bytes and shipped base64-encoded; `text()` would
+    // mangle images, fonts, archives and the like. A declared charset also marks text, and the
+    // `-text` gRPC-Web variant is already base64 text. Untyped bodies are read as text.
+    const TEXT_TYPES = ['json', 'xml', 'javascript', 'x-www-form-urlencoded', 'multipart/form-data'];
+    function isBinary(ct) {
+      const t = (ct || '').toLowerCase();
+      const essence = t.split(';')[0].trim();
+      if (!essence || essence.indexOf('text/') === 0 || /;\s*charset=/.test(t)) return false;
+      return essence.slice(-5) !== '-text' && !TEXT_TYPES.some(function(b) { return essence.indexOf(b) !== -1; });
+    }
+
+    function toBase64(buf) {
+      const bytes = new Uint8Array(buf);
+      let bin = '';
+      for (let i = 0; i < bytes.length; i += 0x8000) {
+        bin += String.fromCharCode.apply(null, bytes.subarray(i, i + 0x8000));
+      }
+      return btoa(bin);
+    }
+
+    // XHR body as [text, base64Encoded]; `responseText` throws for non-text response types.
+    function xhrBody(xhr) {
+      const type = xhr.responseType;
+      if (type === 'arraybuffer') return [xhr.response ? toBase64(xhr.response) : '', true];
+      if (type === 'json') return [JSON.stringify(xhr.response), false];
+      if (type === 'blob' || type === 'document') return ['', false];
+      return [xhr.responseText || '', false];
+    }
+
+    function isEventStream(ct) {
+      return (ct || '').toLowerCase().indexOf('text/event-stream') === 0;
+    }
+
+    const NDJSON_TYPES = ['application/x-ndjson', 'application/ndjson', 'application/jsonl', 'application/x-jsonlines'];
+    function isNdjson(ct) {
+      const t = (ct || '').toLowerCase();
+      return NDJSON_TYPES.some(function(n) { return t.indexOf(n) === 0; });
+    }
+
+    // How a streamed body splits into events: SSE messages keep their terminating blank line
+    // (the parser needs it), NDJSON lines drop the newline and skip blank lines.
+    const SSE_SPLIT = { sep: /\r\n\r\n|\n\n|\r\r/, keepSep: true };
+    const NDJSON_SPLIT = { sep: /\r?\n/, keepSep: false };
+
+    // Read a body incrementally and push one event per unit as it arrives. The first unit
+    // finishes the slot, so a long-lived stream does not hold back later responses. Resolves to
+    // null once the stream ends.
+    function readSplit(stream, split, slot, eventFor) {
+      const reader = stream.getReader();
+      const decoder = new TextDecoder();
+      let buf = '';
+      let sent = 0;
+      function emit(unit) {
+        push(slot, eventFor(unit));
+        if (sent++ === 0) finish(slot);
+      }
+      function pump() {
+        return reader.read().then(function(r) {
+          buf += decoder.decode(r.value || new Uint8Array(0), { stream: !r.done });
+          let m;
+          while ((m = split.sep.exec(buf))) {
+            const end = m.index + m[0].length;
+            const unit = split.keepSep ? buf.slice(0, end) : buf.slice(0, m.index);
+            if (split.keepSep || unit.trim()) emit(unit);
+            buf = buf.slice(end);
+          }
+          if (!r.done) return pump();
+          if (buf.trim() || sent === 0) emit(buf);
+          return null;
+        });
+      }
+      return pump();
+    }
+
+    // Read a text body, keeping what has arrived in `partial.text` for a body timeout to emit.
+    function readText(stream, partial) {
+      const reader = stream.getReader();
+      const decoder = new TextDecoder();
+      partial.reader = reader;
+      function pump() {
+        return reader.read().then(function(r) {
+          partial.text += decoder.decode(r.value || new Uint8Array(0), { stream: !r.done });
+          return r.done ? partial.text : pump();
+        });
+      }
+      return pump();
+    }
+
+    function splitFor(ct) {
+      if (splitSse && isEventStream(ct)) return SSE_SPLIT;
+      if (splitNdjson && isNdjson(ct)) return NDJSON_SPLIT;
+      return null;
+    }
+
+    function headerList(headers) {
+      const out = [];
+      try {
+        headers.forEach(function(value, name) { out.push({ name: name, value: value }); });
+      } catch(e) {}
+      return out;
+    }
+
+    // XHR only exposes response headers as one CRLF-separated string.
+    function parseRawHeaders(raw) {
+      const out = [];
+      (raw || '').trim().split(/[\r\n]+/).forEach(function(line) {
+        const i = line.indexOf(':');
+        if (i > 0) {
+          out.push({ name: line.slice(0, i).trim().toLowerCase(), value: line.slice(i + 1).trim() });
+        }
+      });
+      return out;
+    }
+
+    // Only textual request bodies are recorded as text; FormData goes to requestParts, and
+    // Blob/streams are left as null.
+    function bodyText(body) {
+      if (typeof body === 'string') return body;
+      if (typeof URLSearchParams !== 'undefined' && body instanceof URLSearchParams) return body.toString();
+      return null;
+    }
+
+    function isFormData(body) {
+      return typeof FormData !== 'undefined' && body instanceof FormData;
+    }
+
+    // FormData is serialized by the browser after we see it, so describe it part by part.
+    function formDataParts(body) {
+      if (!isFormData(body)) return null;
+      const parts = [];
+      body.forEach(function(value, name) {
+        if (typeof value === 'string') {
+          const size = new TextEncoder().encode(value).length;
+          parts.push({ name: name, filename: null, contentType: null, size: size,
+                       body: size <= partBodyLimit ? value : null });
+        } else {
+          parts.push({ name: name, filename: value.name || 'blob',
+                       contentType: value.type || 'application/octet-stream',
+                       size: value.size, body: null });
+        }
+      });
+      return parts;
+    }
+
+    // The browser labels URLSearchParams and FormData bodies itself; record the header it will
+    // send (without the multipart boundary, which it picks later).
+    function addImplicitContentType(headers, body) {
+      let ct = null;
+      if (typeof URLSearchParams !== 'undefined' && body instanceof URLSearchParams) {
+        ct = 'application/x-www-form-urlencoded;charset=UTF-8';
+      } else if (isFormData(body)) {
+        ct = 'multipart/form-data';
+      }
+      if (!ct || headers.some(function(h) { return h.name === 'content-type'; })) return;
+      headers.push({ name: 'content-type', value: ct });
+    }
+
+    function describeFetchRequest(input, init) {
+      const req = { method: 'GET', headers: [], body: null, parts: null };
+      try {
+        if (typeof Request !== 'undefined' && input instanceof Request) {
+          req.method = input.method;
+          req.headers = headerList(input.headers);
+        }
+        if (init && init.method) req.method = String(init.method).toUpperCase();
+        if (init && init.headers) req.headers = headerList(new Headers(init.headers));
+        if (init && init.body !== undefined) {
+          req.body = bodyText(init.body);
+          req.parts = formDataParts(init.body);
+          addImplicitContentType(req.headers, init.body);
+        }
+      } catch(e) {}
+      return req;
+    }
+
+    function absoluteUrl(url) {
+      try { return new URL(String(url), location.href).href; } catch(e) { return String(url || ''); }
+    }
+
+    // Event for a request that failed without a response.
+    function failureEvent(url, req, startedAt, t0, message) {
+      return {
+        url: url, body: '', base64Encoded: false, contentType: null, status: null,
+        method: req.method, requestHeaders: req.headers, requestBody: req.body,
+        requestParts: req.parts, responseHeaders: [],
+        startedAt: startedAt, timeToHeadersMs: null, durationMs: performance.now() - t0,
+        error: message, requestId: req.id
+      };
+    }
+
+    // History hook: `pushState`, back/forward and fragment changes. `replaceState` only
+    // rewrites the current entry and is not a navigation.
+    if (!window.__event_history_hooked) {
+      window.__event_history_hooked = true;
+      const origPushState = history.pushState;
+      history.pushState = function() {
+        const r = origPushState.apply(this, arguments);
+        try { navigated(); } catch(e) {}
+        return r;
+      };
+      ['popstate', 'hashchange'].forEach(function(kind) {
+        window.addEventListener(kind, function() { try { navigated(); } catch(e) {} });
+      });
+    }
+
+    // fetch hook
+    if (!window.__event_fetch_hooked) {
+      window.__event_fetch_hooked = true;
+      const origFetch = window.fetch;
+      window.__event_orig_fetch = origFetch; // for `Event::replay`
+      window.fetch = async function(input, init) {
+        const req = describeFetchRequest(input, init);
+        const startedAt = Date.now();
+        const t0 = performance.now();
+        try {
+          req.id = started(absoluteUrl(typeof input === 'string' ? input : (input && input.url) || input),
+                           req.method, startedAt);
+        } catch(e) {}
+        window.__event_in_flight++;
+        let res;
+        try {
+          res = await origFetch.apply(this, arguments);
+        } catch(e) {
+          window.__event_in_flight--;
+          try {
+            const url = absoluteUrl(typeof input === 'string' ? input : (input && input.url) || input);
+            if (captureFailures && shouldCapture(url, null)) {
+              complete(reserve(), failureEvent(url, req, startedAt, t0, String((e && e.message) || e)));
+            }
+          } catch(_) {}
+          throw e;
+        }
+        let settled = false;
+        try {
+          const headersAt = performance.now();
+          const receivedAt = Date.now();
+          const ct = (res.headers && res.headers.get && res.headers.get('content-type')) || '';
+          const url = res.url || (typeof input === 'string' ? input : (input && input.url) || '');
+          if (shouldCapture(url, ct)) {
+            const clone = res.clone();
+            const slot = reserve();
+            const binary = isBinary(ct);
+            function eventFor(body) {
+              return {
+                url: url, body: body, base64Encoded: binary, contentType: ct, status: res.status,
+                method: req.method, requestHeaders: req.headers, requestBody: req.body,
+                requestParts: req.parts, responseHeaders: headerList(res.headers),
+                startedAt: startedAt, timeToHeadersMs: headersAt - t0, durationMs: performance.now() - t0,
+                receivedAt: receivedAt, receivedMonotonicMs: performance.timeOrigin + headersAt,
+                requestId: req.id
+              };
+            }
+            settled = true;
+            const split = clone.body && splitFor(ct);
+            const partial = { text: '', reader: null, timedOut: false };
+            let read = binary ? clone.arrayBuffer().then(toBase64)
+              : split ? readSplit(clone.body, split, slot, eventFor)
+              : bodyTimeoutMs && clone.body ? readText(clone.body, partial)
+              : clone.text();
+            // Split bodies have streamed their events by the time the read settles.
+            if (!split && injectBodyFailure()) {
+              read = read.then(function() { throw new Error('injected body failure'); });
+            }
+            // A body still open after the timeout is emitted with what has arrived so far.
+            const timer = !split && bodyTimeoutMs ? setTimeout(function() {
+              partial.timedOut = true;
+              window.__event_in_flight--;
+              try {
+                const ev = eventFor(binary ? '' : partial.text);
+                ev.bodyTimedOut = true;
+                complete(slot, ev);
+              } catch(e) {}
+              if (partial.reader) partial.reader.cancel().catch(function() {});
+            }, bodyTimeoutMs) : null;
+            read.then(function(txt) {
+              if (partial.timedOut || txt === null) return; // emitted already, or streamed by readSplit
+              try {
+                complete(slot, eventFor(txt));
+              } catch(e) {}
+            }, function(err) {
+              if (partial.timedOut) return;
+              complete(slot, null);
+              bodyFailed(url, err);
+            }).finally(function() {
+              clearTimeout(timer);
+              if (!partial.timedOut) window.__event_in_flight--;
+            });
+          }
+        } catch(e) {}
+        if (!settled) window.__event_in_flight--;
+        return res;
+      };
+    }
+
+    // XHR hook
+    if (!window.__event_xhr_hooked) {
+      window.__event_xhr_hooked = true;
+      const origOpen = XMLHttpRequest.prototype.open;
+      const origSend = XMLHttpRequest.prototype.send;
+      const origSetRequestHeader = XMLHttpRequest.prototype.setRequestHeader;
+      XMLHttpRequest.prototype.open = function(method, url) {
+        try {
+          this.__event_method = String(method || 'GET').toUpperCase();
+          this.__event_url = url;
+          this.__event_request_headers = [];
+        } catch(e) {}
+        return origOpen.apply(this, arguments);
+      };
+      XMLHttpRequest.prototype.setRequestHeader = function(name, value) {
+        try {
+          this.__event_request_headers.push({ name: String(name).toLowerCase(), value: String(value) });
+        } catch(e) {}
+        return origSetRequestHeader.apply(this, arguments);
+      };
+      XMLHttpRequest.prototype.send = function(body) {
+        const xhr = this;
+        const requestBody = bodyText(body);
+        const requestParts = formDataParts(body);
+        try { addImplicitContentType(xhr.__event_request_headers, body); } catch(e) {}
+        const startedAt = Date.now();
+        const t0 = performance.now();
+        let requestId = null;
+        try { requestId = started(absoluteUrl(xhr.__event_url), xhr.__event_method || 'GET', startedAt); } catch(e) {}
+        let headersAt = null;
+        let receivedAt = null;
+        let slot = null;
+        let timedOut = false;
+        function captured() {
+          const ct = (xhr.getResponseHeader && xhr.getResponseHeader('content-type')) || '';
+          const url = xhr.responseURL || xhr.__event_url || '';
+          return shouldCapture(url, ct) ? { url: url, ct: ct } : null;
+        }
+        function eventFor(c, body, base64) {
+          return {
+            url: c.url, body: body, base64Encoded: base64, contentType: c.ct, status: xhr.status,
+            method: xhr.__event_method || 'GET', requestHeaders: xhr.__event_request_headers || [],
+            requestBody: requestBody, requestParts: requestParts,
+            responseHeaders: parseRawHeaders(xhr.getAllResponseHeaders()),
+            startedAt: startedAt, timeToHeadersMs: headersAt === null ? null : headersAt - t0,
+            durationMs: performance.now() - t0, receivedAt: receivedAt,
+            receivedMonotonicMs: headersAt === null ? null : performance.timeOrigin + headersAt,
+            requestId: requestId
+          };
+        }
+        // A response still open after the timeout is emitted with the text received so far.
+        function timeOut() {
+          try {
+            const c = captured();
+            if (!slot || !c) return;
+            const type = xhr.responseType;
+            const ev = eventFor(c, type === '' || type === 'text' ? xhr.responseText || '' : '', false);
+            ev.bodyTimedOut = true;
+            complete(slot, ev);
+            slot = null;
+            timedOut = true;
+            window.__event_in_flight--;
+          } catch(e) {}
+        }
+        // Network errors, timeouts and aborts; `loadend` follows.
+        ['error', 'timeout', 'abort'].forEach(function(kind) {
+          xhr.addEventListener(kind, function() {
+            try {
+              if (timedOut) return;
+              const url = xhr.responseURL || absoluteUrl(xhr.__event_url);
+              if (!captureFailures || !shouldCapture(url, null)) return;
+              const req = { method: xhr.__event_method || 'GET', headers: xhr.__event_request_headers || [],
+                            body: requestBody, parts: requestParts, id: requestId };
+              complete(slot || reserve(), failureEvent(url, req, startedAt, t0, kind));
+              slot = null;
+            } catch(e) {}
+          });
+        });
+        window.__event_in_flight++;
+        this.addEventListener('loadend', function() {
+          if (timedOut) return;
+          window.__event_in_flight--;
+          // Aborted or failed after the headers arrived.
+          if (slot) complete(slot, null);
+          slot = null;
+        });
+        this.addEventListener('readystatechange', function() {
+          if (xhr.readyState === 2 && headersAt === null) {
+            headersAt = performance.now();
+            receivedAt = Date.now();
+            try {
+              if (captured()) {
+                slot = reserve();
+                if (bodyTimeoutMs) setTimeout(timeOut, bodyTimeoutMs);
+              }
+            } catch(e) {}
+          }
+        });
+        this.addEventListener('load', function() {
+          try {
+            if (timedOut) return;
+            const c = captured();
+            // Synchronous requests skip readyState 2.
+            if (c && !slot) slot = reserve();
+            if (c && injectBodyFailure()) {
+              complete(slot, null);
+              bodyFailed(c.url, 'injected body failure');
+              slot = null;
+            } else if (c) {
+              const body = xhrBody(this);
+              complete(slot, eventFor(c, body[0], body[1]));
+              slot = null;
+            }
+          } catch(e) {}
+        });
+        return origSend.apply(this, arguments);
+      };
+    }
+  } catch(e) {}
+}
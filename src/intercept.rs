@@ -0,0 +1,790 @@
+//! Request interception through the CDP Fetch domain.
+//!
+//! [`EventStreamConfig::intercept_rules`](crate::EventStreamConfig::intercept_rules) pauses
+//! matching requests in the browser and applies their [`InterceptAction`]s before letting them
+//! continue, see [`InterceptRule`] for which rules apply. Unlike the page hooks this sees every
+//! request the page makes, including ones the page never reads.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chromiumoxide::Binary;
+use chromiumoxide::cdp::browser_protocol::fetch::{
+    ContinueRequestParams, DisableParams, EnableParams, EventRequestPaused, FailRequestParams,
+    FulfillRequestParams, GetResponseBodyParams, HeaderEntry, RequestPattern, RequestStage,
+};
+pub use chromiumoxide::cdp::browser_protocol::network::ErrorReason;
+use chromiumoxide::cdp::browser_protocol::network::ResourceType;
+use chromiumoxide::page::Page;
+use futures::StreamExt;
+use tokio::task::JoinHandle;
+
+use crate::mock::{self, MockMiss, MockServer};
+use crate::{Error, Event, EventStreamConfig, Header, Redaction};
+
+/// A URL pattern and what to do with the requests it matches.
+///
+/// Rules act in two stages. When a request goes out, the first rule in the list that matches it
+/// and acts on requests, i.e. anything but [`InterceptAction::Transform`], applies. When its
+/// response comes back, the first matching `Transform` rule does, matched against the URL the
+/// request went to after any rewrite. So a `Rewrite` or `Fail` rule never shadows a `Transform`
+/// listed after it, but of two rules for the same stage only the first applies.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InterceptRule {
+    /// Requests whose URL matches this pattern are intercepted. `*` matches any run of
+    /// characters, `?` exactly one, and `\` escapes either.
+    pub url_pattern: String,
+    pub action: InterceptAction,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InterceptAction {
+    /// Replace the first occurrence of `from` in the URL with `to`, e.g. to point a production
+    /// host at staging. The page does not see the change; the captured event keeps the original
+    /// URL and records the new one in [`Event::rewritten_url`](crate::Event::rewritten_url).
+    Rewrite { from: String, to: String },
+    /// Hold the request for this long before sending it, e.g. to exercise client-side timeouts.
+    Delay(Duration),
+    /// Fail the request with this network error without sending it, e.g. to simulate an outage.
+    /// The page sees an ordinary network failure; with
+    /// [`capture_failed_requests`](crate::EventStreamConfig::capture_failed_requests) the event
+    /// records the reason in [`Event::error`](crate::Event::error).
+    Fail(ErrorReason),
+    /// Let the request through, then replace the response body with the result of the
+    /// callback before the page sees it. The event's `body` is what the page received and
+    /// [`Event::original_body`](crate::Event::original_body) what the server sent.
+    Transform(BodyTransform),
+    /// Ask the callback what to do with the request, for cases the other actions do not cover.
+    /// Changes it makes are not recorded on the captured event.
+    Decide(RequestDecider),
+}
+
+/// Callback of an [`InterceptAction::Transform`] rule, from the body the server sent to the one
+/// the page receives. Bodies are raw bytes, already decompressed.
+#[derive(Clone)]
+pub struct BodyTransform(Arc<TransformFn>);
+
+type TransformFn = dyn Fn(&[u8]) -> Vec<u8> + Send + Sync;
+
+impl BodyTransform {
+    pub fn new(transform: impl Fn(&[u8]) -> Vec<u8> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(transform))
+    }
+}
+
+impl fmt::Debug for BodyTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BodyTransform(..)")
+    }
+}
+
+// Two transforms are equal only if they are the same callback.
+impl PartialEq for BodyTransform {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for BodyTransform {}
+
+/// The callback of an [`InterceptAction::Decide`] rule.
+#[derive(Clone)]
+pub struct RequestDecider(Arc<DecideFn>);
+
+type DecideFn = dyn Fn(&PausedRequest) -> Decision + Send + Sync;
+
+impl RequestDecider {
+    pub fn new(decide: impl Fn(&PausedRequest) -> Decision + Send + Sync + 'static) -> Self {
+        Self(Arc::new(decide))
+    }
+}
+
+impl fmt::Debug for RequestDecider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RequestDecider(..)")
+    }
+}
+
+impl PartialEq for RequestDecider {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for RequestDecider {}
+
+/// A request paused by an [`InterceptAction::Decide`] rule, before it is sent.
+#[derive(Clone, Debug)]
+pub struct PausedRequest {
+    pub url: String,
+    pub method: String,
+    /// Names are lower-cased.
+    pub headers: Vec<Header>,
+    /// The body the browser is about to send, if it has one.
+    pub body: Option<Vec<u8>>,
+    /// The CDP resource type, e.g. `Fetch`, `XHR` or `Document`.
+    pub resource_type: String,
+}
+
+impl PausedRequest {
+    /// First header named `name` (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.as_str())
+    }
+}
+
+/// What a [`RequestDecider`] wants done with a [`PausedRequest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// Send the request as it is.
+    Continue,
+    /// Send the request with these changes.
+    Modify(RequestChanges),
+    /// Fail the request with this network error without sending it.
+    Fail(ErrorReason),
+    /// Answer the request with this response without sending it.
+    Fulfill(Fulfillment),
+}
+
+/// Changes to a request before it is sent. Unset fields keep the request's own value.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestChanges {
+    pub url: Option<String>,
+    pub method: Option<String>,
+    /// Replaces every request header.
+    pub headers: Option<Vec<Header>>,
+    pub body: Option<Vec<u8>>,
+}
+
+/// A response to answer a request with, see [`Decision::Fulfill`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fulfillment {
+    pub status: u16,
+    pub headers: Vec<Header>,
+    pub body: Vec<u8>,
+}
+
+impl Fulfillment {
+    pub fn new(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push(Header {
+            name: name.into(),
+            value: value.into(),
+        });
+        self
+    }
+}
+
+impl InterceptAction {
+    fn rewrite(&self, url: &str) -> Option<String> {
+        match self {
+            InterceptAction::Rewrite { from, to } => url
+                .contains(from.as_str())
+                .then(|| url.replacen(from.as_str(), to, 1)),
+            InterceptAction::Delay(_)
+            | InterceptAction::Fail(_)
+            | InterceptAction::Transform(_)
+            | InterceptAction::Decide(_) => None,
+        }
+    }
+}
+
+impl InterceptRule {
+    pub fn rewrite(
+        url_pattern: impl Into<String>,
+        from: impl Into<String>,
+        to: impl Into<String>,
+    ) -> Self {
+        Self {
+            url_pattern: url_pattern.into(),
+            action: InterceptAction::Rewrite {
+                from: from.into(),
+                to: to.into(),
+            },
+        }
+    }
+
+    pub fn delay(url_pattern: impl Into<String>, delay: Duration) -> Self {
+        Self {
+            url_pattern: url_pattern.into(),
+            action: InterceptAction::Delay(delay),
+        }
+    }
+
+    pub fn fail(url_pattern: impl Into<String>, reason: ErrorReason) -> Self {
+        Self {
+            url_pattern: url_pattern.into(),
+            action: InterceptAction::Fail(reason),
+        }
+    }
+
+    pub fn transform(
+        url_pattern: impl Into<String>,
+        transform: impl Fn(&[u8]) -> Vec<u8> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            url_pattern: url_pattern.into(),
+            action: InterceptAction::Transform(BodyTransform::new(transform)),
+        }
+    }
+
+    pub fn decide(
+        url_pattern: impl Into<String>,
+        decide: impl Fn(&PausedRequest) -> Decision + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            url_pattern: url_pattern.into(),
+            action: InterceptAction::Decide(RequestDecider::new(decide)),
+        }
+    }
+
+    pub fn matches(&self, url: &str) -> bool {
+        glob_matches(&self.url_pattern, url)
+    }
+}
+
+/// The action of the first rule matching `url` that acts on the request as it goes out.
+fn request_action<'a>(rules: &'a [InterceptRule], url: &str) -> Option<&'a InterceptAction> {
+    rules
+        .iter()
+        .filter(|rule| !matches!(rule.action, InterceptAction::Transform(_)))
+        .find(|rule| rule.matches(url))
+        .map(|rule| &rule.action)
+}
+
+/// The first transform rule matching `url`, the URL the request went to.
+fn response_transform<'a>(rules: &'a [InterceptRule], url: &str) -> Option<&'a BodyTransform> {
+    rules.iter().find_map(|rule| match &rule.action {
+        InterceptAction::Transform(transform) if rule.matches(url) => Some(transform),
+        _ => None,
+    })
+}
+
+/// The URL the rules send the request to instead, if they rewrite it.
+fn rewritten_url(rules: &[InterceptRule], url: &str) -> Option<String> {
+    request_action(rules, url)?.rewrite(url)
+}
+
+/// The error the rules fail the request with, if they fail it.
+fn injected_failure<'a>(rules: &'a [InterceptRule], url: &str) -> Option<&'a ErrorReason> {
+    match request_action(rules, url)? {
+        InterceptAction::Fail(reason) => Some(reason),
+        _ => None,
+    }
+}
+
+// Server bodies replaced by transform rules, waiting for their captured events. Bounded, since
+// the page may never capture some of the responses.
+const MAX_ORIGINAL_BODIES: usize = 64;
+
+#[derive(Debug, Default)]
+pub(crate) struct OriginalBodies(Mutex<VecDeque<(String, Vec<u8>)>>);
+
+impl OriginalBodies {
+    fn push(&self, url: String, body: Vec<u8>) {
+        let mut bodies = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if bodies.len() == MAX_ORIGINAL_BODIES {
+            bodies.pop_front();
+        }
+        bodies.push_back((url, body));
+    }
+
+    fn take(&self, url: &str) -> Option<Vec<u8>> {
+        let mut bodies = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let i = bodies.iter().position(|(u, _)| u == url)?;
+        bodies.remove(i).map(|(_, body)| body)
+    }
+}
+
+/// Record what the rules did to the request behind `event`.
+pub(crate) fn annotate(rules: &[InterceptRule], originals: &OriginalBodies, event: &mut Event) {
+    if rules.is_empty() {
+        return;
+    }
+    event.rewritten_url = rewritten_url(rules, &event.url);
+    if let (Some(error), Some(reason)) = (&event.error, injected_failure(rules, &event.url)) {
+        event.error = Some(format!("{error} (injected {})", reason.as_ref()));
+    }
+    // Transforms saw the response under the URL it came from.
+    let sent_to = event.rewritten_url.as_deref().unwrap_or(&event.url);
+    if let Some(original) = originals.take(sent_to) {
+        event.original_body = Some(if event.base64_encoded {
+            STANDARD.encode(original)
+        } else {
+            String::from_utf8_lossy(&original).into_owned()
+        });
+    }
+}
+
+/// A running interception task for one page.
+///
+/// Dropping it without calling [`stop`](Self::stop), e.g. when a caller-driven loop is
+/// cancelled, stops the task and disables interception in the background, so no request stays
+/// paused with nobody to answer it.
+pub(crate) struct Interceptor {
+    // Taken by `stop`; still set when the interceptor is dropped without it.
+    page: Option<Page>,
+    task: JoinHandle<()>,
+}
+
+// What every paused request is answered from.
+struct Context {
+    rules: Vec<InterceptRule>,
+    mocks: Option<MockServer>,
+    originals: Arc<OriginalBodies>,
+    // URLs are logged redacted, like everything else leaving the crate.
+    redaction: Redaction,
+}
+
+impl Interceptor {
+    /// Enable the Fetch domain for the config's rules and mocks and start answering paused
+    /// requests. `None` when there are neither.
+    pub(crate) async fn start(
+        page: &Page,
+        config: &EventStreamConfig,
+        originals: Arc<OriginalBodies>,
+    ) -> Result<Option<Self>, Error> {
+        let rules = &config.intercept_rules;
+        if rules.is_empty() && config.mocks.is_none() {
+            return Ok(None);
+        }
+        // Subscribe first so no paused request is missed.
+        let mut paused = page
+            .event_listener::<EventRequestPaused>()
+            .await
+            .map_err(Error::EventListener)?;
+        let mut patterns: Vec<_> = rules
+            .iter()
+            .map(|rule| RequestPattern {
+                url_pattern: Some(rule.url_pattern.clone()),
+                resource_type: None,
+                request_stage: Some(match rule.action {
+                    InterceptAction::Transform(_) => RequestStage::Response,
+                    _ => RequestStage::Request,
+                }),
+            })
+            .collect();
+        if config.mocks.is_some() {
+            // Only fetch and XHR are ever captured, so only they can be served.
+            for resource_type in [ResourceType::Fetch, ResourceType::Xhr] {
+                patterns.push(RequestPattern {
+                    url_pattern: Some("*".to_string()),
+                    resource_type: Some(resource_type),
+                    request_stage: Some(RequestStage::Request),
+                });
+            }
+        }
+        let enable = EnableParams {
+            patterns: Some(patterns),
+            handle_auth_requests: None,
+        };
+        config
+            .retry
+            .run(|| page.execute(enable.clone()))
+            .await
+            .map_err(Error::Intercept)?;
+
+        let context = Arc::new(Context {
+            rules: rules.clone(),
+            mocks: config.mocks.clone().map(MockServer::new),
+            originals,
+            redaction: config.redaction.clone(),
+        });
+        let task_page = page.clone();
+        let task = tokio::spawn(async move {
+            while let Some(event) = paused.next().await {
+                // Answer each request on its own so a slow one holds up nothing else.
+                tokio::spawn(handle_paused(
+                    task_page.clone(),
+                    Arc::clone(&context),
+                    event,
+                ));
+            }
+        });
+        Ok(Some(Self {
+            page: Some(page.clone()),
+            task,
+        }))
+    }
+
+    /// Disable interception, letting requests through untouched, and stop the task.
+    pub(crate) async fn stop(mut self) {
+        if let Some(page) = self.page.take() {
+            disable(&page).await;
+        }
+    }
+}
+
+impl Drop for Interceptor {
+    fn drop(&mut self) {
+        self.task.abort();
+        if let Some(page) = self.page.take()
+            && let Ok(runtime) = tokio::runtime::Handle::try_current()
+        {
+            runtime.spawn(async move { disable(&page).await });
+        }
+    }
+}
+
+async fn disable(page: &Page) {
+    if let Err(e) = page.execute(DisableParams::default()).await {
+        tracing::debug!(error = %e, "disabling interception failed");
+    }
+}
+
+async fn handle_paused(page: Page, context: Arc<Context>, event: Arc<EventRequestPaused>) {
+    let url = match &event.request.url_fragment {
+        Some(fragment) => format!("{}{fragment}", event.request.url),
+        None => event.request.url.clone(),
+    };
+    let logged = context.redaction.redact_url(&url);
+    let mut params = ContinueRequestParams::new(event.request_id.clone());
+
+    // Paused with the response: only transforms act here, so other rules do not apply twice.
+    if event.response_status_code.is_some() || event.response_error_reason.is_some() {
+        if let Some(transform) = response_transform(&context.rules, &url)
+            && event.response_error_reason.is_none()
+        {
+            match fulfill_transformed(&page, &event, transform, &context.originals, &url, &logged)
+                .await
+            {
+                Ok(()) => return,
+                Err(e) => {
+                    tracing::debug!(url = %logged, error = %e, "transforming response failed")
+                }
+            }
+        }
+        if let Err(e) = page.execute(params).await {
+            tracing::debug!(url = %logged, error = %e, "continuing intercepted response failed");
+        }
+        return;
+    }
+
+    match request_action(&context.rules, &url) {
+        Some(action @ InterceptAction::Rewrite { .. }) => {
+            params.url = action.rewrite(&url);
+            if let Some(to) = &params.url {
+                let to = context.redaction.redact_url(to);
+                tracing::debug!(from = %logged, %to, "rewriting request");
+            }
+        }
+        Some(InterceptAction::Delay(delay)) => {
+            tracing::debug!(url = %logged, ?delay, "delaying request");
+            tokio::time::sleep(*delay).await;
+        }
+        Some(InterceptAction::Fail(reason)) => {
+            tracing::debug!(url = %logged, ?reason, "failing request");
+            let params = FailRequestParams::new(event.request_id.clone(), reason.clone());
+            if let Err(e) = page.execute(params).await {
+                tracing::debug!(url = %logged, error = %e, "failing intercepted request failed");
+            }
+            return;
+        }
+        Some(InterceptAction::Decide(decider)) => {
+            let request = paused_request(&event, &url);
+            match (decider.0)(&request) {
+                Decision::Continue => {}
+                Decision::Modify(changes) => {
+                    let to = changes
+                        .url
+                        .as_deref()
+                        .map(|u| context.redaction.redact_url(u));
+                    tracing::debug!(url = %logged, ?to, ?changes.method, "modifying request");
+                    params.url = changes.url;
+                    params.method = changes.method;
+                    params.headers = changes.headers.as_deref().map(header_entries);
+                    params.post_data = changes.body.map(|b| Binary::from(STANDARD.encode(b)));
+                }
+                Decision::Fail(reason) => {
+                    tracing::debug!(url = %logged, ?reason, "failing request");
+                    let params = FailRequestParams::new(event.request_id.clone(), reason);
+                    if let Err(e) = page.execute(params).await {
+                        tracing::debug!(url = %logged, error = %e, "failing intercepted request failed");
+                    }
+                    return;
+                }
+                Decision::Fulfill(response) => {
+                    tracing::debug!(url = %logged, status = response.status, "fulfilling request");
+                    let mut params = FulfillRequestParams::new(
+                        event.request_id.clone(),
+                        i64::from(response.status),
+                    );
+                    params.response_headers = Some(header_entries(&response.headers));
+                    params.body = Some(Binary::from(STANDARD.encode(&response.body)));
+                    if let Err(e) = page.execute(params).await {
+                        tracing::debug!(url = %logged, error = %e, "fulfilling intercepted request failed");
+                    }
+                    return;
+                }
+            }
+        }
+        None => {
+            if let Some(mocks) = &context.mocks
+                && matches!(event.resource_type, ResourceType::Fetch | ResourceType::Xhr)
+                && response_transform(&context.rules, &url).is_none()
+            {
+                serve_mock(&page, &event, mocks, &url, &logged).await;
+                return;
+            }
+        }
+        // Not listed by `request_action`.
+        Some(InterceptAction::Transform(_)) => {}
+    }
+    // Make sure the response pauses for a transform, even if a request pattern paused it.
+    let sent_to = params.url.as_deref().unwrap_or(&url);
+    if response_transform(&context.rules, sent_to).is_some() {
+        params.intercept_response = Some(true);
+    }
+    if let Err(e) = page.execute(params).await {
+        tracing::debug!(url = %logged, error = %e, "continuing intercepted request failed");
+    }
+}
+
+fn paused_request(event: &EventRequestPaused, url: &str) -> PausedRequest {
+    let headers = event
+        .request
+        .headers
+        .inner()
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, value)| Header {
+            name: name.to_ascii_lowercase(),
+            value: value
+                .as_str()
+                .map_or_else(|| value.to_string(), str::to_string),
+        })
+        .collect();
+    // The browser hands the body over in chunks, each base64-encoded.
+    let body = event.request.post_data_entries.as_ref().map(|entries| {
+        entries
+            .iter()
+            .filter_map(|entry| entry.bytes.as_ref())
+            .flat_map(|bytes| {
+                STANDARD
+                    .decode(AsRef::<str>::as_ref(bytes))
+                    .unwrap_or_default()
+            })
+            .collect()
+    });
+    PausedRequest {
+        url: url.to_string(),
+        method: event.request.method.clone(),
+        headers,
+        body,
+        resource_type: event.resource_type.as_ref().to_string(),
+    }
+}
+
+fn header_entries(headers: &[Header]) -> Vec<HeaderEntry> {
+    headers
+        .iter()
+        .map(|h| HeaderEntry::new(h.name.clone(), h.value.clone()))
+        .collect()
+}
+
+async fn serve_mock(
+    page: &Page,
+    event: &EventRequestPaused,
+    mocks: &MockServer,
+    url: &str,
+    logged: &str,
+) {
+    let result = match (mocks.lookup(&event.request.method, url), mocks.on_miss()) {
+        (Some(recorded), _) => {
+            tracing::debug!(url = %logged, status = ?recorded.status, "serving recorded response");
+            page.execute(mock::fulfill_params(event.request_id.clone(), recorded))
+                .await
+                .map(drop)
+        }
+        (None, MockMiss::Network) => {
+            tracing::debug!(url = %logged, "no recorded response, sending to the network");
+            page.execute(ContinueRequestParams::new(event.request_id.clone()))
+                .await
+                .map(drop)
+        }
+        (None, MockMiss::Fail(reason)) => {
+            tracing::debug!(url = %logged, ?reason, "no recorded response, failing request");
+            page.execute(FailRequestParams::new(
+                event.request_id.clone(),
+                reason.clone(),
+            ))
+            .await
+            .map(drop)
+        }
+    };
+    if let Err(e) = result {
+        tracing::debug!(url = %logged, error = %e, "answering mocked request failed");
+    }
+}
+
+// Read the paused response, run it through `transform` and answer the request with the result.
+async fn fulfill_transformed(
+    page: &Page,
+    event: &EventRequestPaused,
+    transform: &BodyTransform,
+    originals: &OriginalBodies,
+    url: &str,
+    logged: &str,
+) -> Result<(), Error> {
+    let body = page
+        .execute(GetResponseBodyParams::new(event.request_id.clone()))
+        .await
+        .map_err(Error::Intercept)?
+        .result;
+    let original = if body.base64_encoded {
+        STANDARD.decode(&body.body).map_err(Error::DecodeBase64)?
+    } else {
+        body.body.into_bytes()
+    };
+    let modified = (transform.0)(&original);
+    tracing::debug!(url = %logged, from = original.len(), to = modified.len(), "transforming response");
+
+    let mut params = FulfillRequestParams::new(
+        event.request_id.clone(),
+        event.response_status_code.unwrap_or(200),
+    );
+    // The body is sent decoded and at its new length.
+    params.response_headers = event.response_headers.as_ref().map(|headers| {
+        headers
+            .iter()
+            .filter(|h| {
+                !h.name.eq_ignore_ascii_case("content-length")
+                    && !h.name.eq_ignore_ascii_case("content-encoding")
+            })
+            .cloned()
+            .collect()
+    });
+    params.response_phrase = event
+        .response_status_text
+        .clone()
+        .filter(|text| !text.is_empty());
+    params.body = Some(Binary::from(STANDARD.encode(&modified)));
+    originals.push(url.to_string(), original);
+    page.execute(params).await.map_err(Error::Intercept)?;
+    Ok(())
+}
+
+// CDP Fetch glob: `*` is zero or more characters, `?` exactly one, `\` escapes the next one.
+pub(crate) fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was, and the text position it is currently matched up to.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+                continue;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            Some('\\') if pattern.get(p + 1) == Some(&text[t]) => {
+                p += 2;
+                t += 1;
+                continue;
+            }
+            Some(&c) if c != '\\' && c == text[t] => {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            _ => {}
+        }
+        // Mismatch: let the last `*` swallow one more character, or fail.
+        match star {
+            Some((sp, st)) => {
+                star = Some((sp, st + 1));
+                p = sp + 1;
+                t = st + 1;
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matches_cdp_patterns() {
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("*/api/*", "https://example.com/api/users"));
+        assert!(!glob_matches("*/api/*", "https://example.com/app/users"));
+        assert!(glob_matches(
+            "https://?.example.com/*",
+            "https://a.example.com/x"
+        ));
+        assert!(!glob_matches(
+            "https://?.example.com/*",
+            "https://ab.example.com/x"
+        ));
+        assert!(glob_matches("*.js", "https://cdn.example.com/a.min.js"));
+        assert!(!glob_matches("*.js", "https://cdn.example.com/a.json"));
+        assert!(glob_matches("*a*b*c", "xxaxxbxxbxxc"));
+        assert!(glob_matches("exact", "exact"));
+        assert!(!glob_matches("exact", "exactly"));
+    }
+
+    #[test]
+    fn glob_escapes_match_literally() {
+        assert!(glob_matches(r"*\?q=1", "https://example.com/?q=1"));
+        assert!(!glob_matches(r"*\?q=1", "https://example.com/xq=1"));
+        assert!(glob_matches(r"a\*b", "a*b"));
+        assert!(!glob_matches(r"a\*b", "axxb"));
+    }
+
+    #[test]
+    fn rules_apply_per_stage_in_list_order() {
+        let upper = InterceptRule::transform("*/api/*", |body| body.to_ascii_uppercase());
+        let rules = vec![
+            InterceptRule::rewrite("*/api/*", "example.com", "staging.example.com"),
+            InterceptRule::fail("*/api/*", ErrorReason::Failed),
+            upper.clone(),
+            InterceptRule::transform("*", |_| Vec::new()),
+        ];
+        let url = "https://example.com/api/users";
+        let staged = "https://staging.example.com/api/users";
+
+        // The rewrite shadows the later fail, but not the transform.
+        assert!(matches!(
+            request_action(&rules, url),
+            Some(InterceptAction::Rewrite { .. })
+        ));
+        assert_eq!(rewritten_url(&rules, url).as_deref(), Some(staged));
+        assert_eq!(injected_failure(&rules, url), None);
+        let InterceptAction::Transform(expected) = &upper.action else {
+            unreachable!()
+        };
+        assert_eq!(response_transform(&rules, staged), Some(expected));
+
+        // The transformed body is found under the URL the request went to.
+        let originals = OriginalBodies::default();
+        originals.push(staged.to_string(), b"users".to_vec());
+        let mut event = Event::builder().url(url).body("USERS").build();
+        annotate(&rules, &originals, &mut event);
+        assert_eq!(event.rewritten_url.as_deref(), Some(staged));
+        assert_eq!(event.original_body.as_deref(), Some("users"));
+    }
+}
@@ -0,0 +1,203 @@
+//! JSON-RPC 2.0 view of captured traffic.
+//!
+//! [`exchanges`] splits a captured HTTP request into its JSON-RPC calls, batched or not, and
+//! pairs each with the response object carrying the same `id`. Notifications (calls without an
+//! `id`) get no response by definition and are reported with neither `result` nor `error`.
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::Event;
+
+/// One JSON-RPC call and its response.
+#[derive(Clone, Debug, Serialize)]
+pub struct RpcExchange {
+    pub url: String,
+    /// `None` for notifications.
+    pub id: Option<Value>,
+    pub method: String,
+    /// `Value::Null` when the call had no params.
+    pub params: Value,
+    pub result: Option<Value>,
+    pub error: Option<RpcError>,
+    /// HTTP status of the carrying response.
+    pub status: Option<u16>,
+    pub started_at: Option<f64>,
+    pub duration_ms: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcExchange {
+    pub fn is_notification(&self) -> bool {
+        self.id.is_none()
+    }
+}
+
+/// Whether `event` carries JSON-RPC 2.0 calls.
+pub fn is_jsonrpc(event: &Event) -> bool {
+    !calls(event).is_empty()
+}
+
+/// The JSON-RPC calls in `event`, each paired with its response by `id`. Empty for other
+/// traffic.
+pub fn exchanges(event: &Event) -> Vec<RpcExchange> {
+    let calls = calls(event);
+    if calls.is_empty() {
+        return Vec::new();
+    }
+    // Servers may answer a batch in any order, so match on `id` rather than position.
+    let responses: Vec<Value> = match serde_json::from_str::<Value>(&event.body) {
+        Ok(Value::Array(items)) => items,
+        Ok(value) => vec![value],
+        Err(_) => Vec::new(),
+    };
+
+    calls
+        .into_iter()
+        .map(|call| {
+            let id = call.get("id").filter(|id| !id.is_null()).cloned();
+            let response = id
+                .as_ref()
+                .and_then(|id| responses.iter().find(|r| r.get("id") == Some(id)));
+            RpcExchange {
+                url: event.url.clone(),
+                method: call
+                    .get("method")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string(),
+                params: call.get("params").cloned().unwrap_or(Value::Null),
+                result: response.and_then(|r| r.get("result")).cloned(),
+                error: response
+                    .and_then(|r| r.get("error"))
+                    .and_then(|e| serde_json::from_value(e.clone()).ok()),
+                id,
+                status: event.status,
+                started_at: event.started_at,
+                duration_ms: event.duration_ms,
+            }
+        })
+        .collect()
+}
+
+/// Forward the JSON-RPC exchanges found in `rx`, dropping all other traffic.
+pub fn jsonrpc_exchanges(
+    mut rx: mpsc::UnboundedReceiver<Event>,
+) -> mpsc::UnboundedReceiver<RpcExchange> {
+    let (tx, out) = mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some(event) = rx.next().await {
+            for exchange in exchanges(&event) {
+                if tx.unbounded_send(exchange).is_err() {
+                    return; // receiver dropped
+                }
+            }
+        }
+    });
+    out
+}
+
+fn calls(event: &Event) -> Vec<Value> {
+    let Some(body) = event.request_body.as_deref() else {
+        return Vec::new();
+    };
+    let candidates = match serde_json::from_str::<Value>(body) {
+        Ok(Value::Array(items)) => items,
+        Ok(value) => vec![value],
+        Err(_) => return Vec::new(),
+    };
+    candidates
+        .into_iter()
+        .filter(|c| {
+            c.get("jsonrpc").and_then(Value::as_str) == Some("2.0")
+                && c.get("method").is_some_and(Value::is_string)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn post(body: Value, response: Value) -> Event {
+        Event::builder()
+            .url("https://rpc.example.com/")
+            .method("POST")
+            .status(200)
+            .request_body(body.to_string())
+            .body(response.to_string())
+            .build()
+    }
+
+    #[test]
+    fn batches_pair_by_id_in_any_order() {
+        let event = post(
+            json!([
+                {"jsonrpc": "2.0", "id": 1, "method": "eth_blockNumber"},
+                {"jsonrpc": "2.0", "id": "b", "method": "eth_call", "params": [{"to": "0x0"}]},
+                {"jsonrpc": "2.0", "method": "log", "params": {"level": "info"}},
+                {"jsonrpc": "1.0", "id": 9, "method": "legacy"},
+            ]),
+            json!([
+                {"jsonrpc": "2.0", "id": "b", "error": {"code": -32000, "message": "reverted", "data": "0x"}},
+                {"jsonrpc": "2.0", "id": 1, "result": "0x10"},
+            ]),
+        );
+        let calls = exchanges(&event);
+        assert_eq!(calls.len(), 3);
+
+        assert_eq!(calls[0].id, Some(json!(1)));
+        assert_eq!(calls[0].result, Some(json!("0x10")));
+        assert_eq!(calls[0].error, None);
+
+        assert_eq!(calls[1].params, json!([{"to": "0x0"}]));
+        assert_eq!(calls[1].result, None);
+        assert_eq!(
+            calls[1].error,
+            Some(RpcError {
+                code: -32000,
+                message: "reverted".to_string(),
+                data: Some(json!("0x")),
+            })
+        );
+
+        // Notifications get no response.
+        assert!(calls[2].is_notification());
+        assert_eq!(calls[2].method, "log");
+        assert!(calls[2].result.is_none() && calls[2].error.is_none());
+    }
+
+    #[test]
+    fn null_ids_are_notifications_and_match_nothing() {
+        let event = post(
+            json!({"jsonrpc": "2.0", "id": null, "method": "ping"}),
+            json!({"jsonrpc": "2.0", "id": null, "error": {"code": -32700, "message": "Parse error"}}),
+        );
+        let calls = exchanges(&event);
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].is_notification());
+        assert_eq!(calls[0].error, None);
+    }
+
+    #[test]
+    fn other_traffic_is_not_jsonrpc() {
+        assert!(!is_jsonrpc(&post(json!({"method": "x"}), json!({}))));
+        assert!(!is_jsonrpc(&post(
+            json!({"jsonrpc": "2.0", "method": 1}),
+            json!({})
+        )));
+        let get = Event::builder().url("https://rpc.example.com/").build();
+        assert!(exchanges(&get).is_empty());
+    }
+}
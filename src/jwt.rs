@@ -0,0 +1,162 @@
+//! JWT discovery in captured traffic.
+//!
+//! [`Event::jwts`] finds compact JWS tokens in the URL, headers and bodies of an event and
+//! decodes their header and claims. Signatures are **not** verified: this is for mapping auth
+//! flows, not for trusting tokens.
+
+use std::sync::LazyLock;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{Event, Header};
+
+// Both the header and the claims segment of a JWT are base64url JSON objects, so start `eyJ`.
+static TOKEN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"eyJ[A-Za-z0-9_-]+\.eyJ[A-Za-z0-9_-]+\.[A-Za-z0-9_-]*").unwrap());
+
+/// Where in the event a token was found.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum JwtLocation {
+    Url,
+    RequestHeader(String),
+    RequestBody,
+    ResponseHeader(String),
+    ResponseBody,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Jwt {
+    pub token: String,
+    pub location: JwtLocation,
+    pub header: Value,
+    pub claims: Value,
+}
+
+impl Jwt {
+    /// Decode `token` without verifying it. `None` if it is not a well-formed JWT.
+    pub fn decode(token: &str, location: JwtLocation) -> Option<Self> {
+        let mut parts = token.split('.');
+        let header = decode_segment(parts.next()?)?;
+        let claims = decode_segment(parts.next()?)?;
+        Some(Self {
+            token: token.to_string(),
+            location,
+            header,
+            claims,
+        })
+    }
+
+    /// The `alg` header.
+    pub fn algorithm(&self) -> Option<&str> {
+        self.header.get("alg").and_then(Value::as_str)
+    }
+
+    /// The `exp` claim, in seconds since the Unix epoch.
+    pub fn expires_at(&self) -> Option<i64> {
+        self.claims.get("exp").and_then(Value::as_i64)
+    }
+
+    pub fn issuer(&self) -> Option<&str> {
+        self.claims.get("iss").and_then(Value::as_str)
+    }
+
+    pub fn subject(&self) -> Option<&str> {
+        self.claims.get("sub").and_then(Value::as_str)
+    }
+}
+
+impl Event {
+    /// Every JWT found in the URL, request and response headers, and request and response
+    /// bodies, in that order. A token repeated in one place is reported once.
+    pub fn jwts(&self) -> Vec<Jwt> {
+        let mut found = Vec::new();
+        scan(&self.url, JwtLocation::Url, &mut found);
+        scan_headers(
+            &self.request_headers,
+            JwtLocation::RequestHeader,
+            &mut found,
+        );
+        if let Some(body) = &self.request_body {
+            scan(body, JwtLocation::RequestBody, &mut found);
+        }
+        scan_headers(
+            &self.response_headers,
+            JwtLocation::ResponseHeader,
+            &mut found,
+        );
+        if !self.base64_encoded {
+            scan(&self.body, JwtLocation::ResponseBody, &mut found);
+        }
+        found
+    }
+}
+
+fn scan_headers(headers: &[Header], location: fn(String) -> JwtLocation, found: &mut Vec<Jwt>) {
+    for header in headers {
+        scan(&header.value, location(header.name.clone()), found);
+    }
+}
+
+fn scan(text: &str, location: JwtLocation, found: &mut Vec<Jwt>) {
+    for m in TOKEN.find_iter(text) {
+        let duplicate = found
+            .iter()
+            .any(|jwt| jwt.token == m.as_str() && jwt.location == location);
+        if !duplicate && let Some(jwt) = Jwt::decode(m.as_str(), location.clone()) {
+            found.push(jwt);
+        }
+    }
+}
+
+fn decode_segment(segment: &str) -> Option<Value> {
+    let bytes = URL_SAFE_NO_PAD.decode(segment.trim_end_matches('=')).ok()?;
+    serde_json::from_slice::<Value>(&bytes)
+        .ok()
+        .filter(Value::is_object)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // {"alg":"HS256","typ":"JWT"}.{"sub":"1234567890","iss":"auth.example"}.sig
+    const TOKEN_TEXT: &str = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.\
+        eyJzdWIiOiIxMjM0NTY3ODkwIiwiaXNzIjoiYXV0aC5leGFtcGxlIn0.c2ln";
+
+    #[test]
+    fn finds_and_decodes_tokens_by_location() {
+        let event = Event::builder()
+            .url(format!("https://api.example/cb?id_token={TOKEN_TEXT}"))
+            .request_header("authorization", format!("Bearer {TOKEN_TEXT}"))
+            .body(format!(
+                r#"{{"access_token":"{TOKEN_TEXT}","again":"{TOKEN_TEXT}"}}"#
+            ))
+            .build();
+        let jwts = event.jwts();
+        let locations: Vec<_> = jwts.iter().map(|jwt| jwt.location.clone()).collect();
+        assert_eq!(
+            locations,
+            [
+                JwtLocation::Url,
+                JwtLocation::RequestHeader("authorization".to_string()),
+                JwtLocation::ResponseBody,
+            ]
+        );
+        assert_eq!(jwts[0].subject(), Some("1234567890"));
+        assert_eq!(jwts[0].issuer(), Some("auth.example"));
+        assert_eq!(jwts[0].header["alg"], "HS256");
+    }
+
+    #[test]
+    fn ignores_lookalikes_and_binary_bodies() {
+        let event = Event::builder()
+            .url("https://api.example/eyJub3QiOi.eyJhIGp3dA.x")
+            .bytes_body(TOKEN_TEXT)
+            .build();
+        assert!(event.jwts().is_empty());
+    }
+}
@@ -1,206 +1,1089 @@
+use std::collections::BTreeMap;
+use std::pin::pin;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
 use std::time::Duration;
 
-use futures::SinkExt;
-use futures::StreamExt;
 use futures::channel::mpsc;
-use serde::Deserialize;
-use tokio::time;
+use futures::future::{self, Either};
+use futures::{Sink, SinkExt};
+use serde::{Deserialize, Serialize};
+use tracing::Instrument;
 
+use aho_corasick::AhoCorasick;
+use chromiumoxide::cdp::browser_protocol::inspector::EventDetached;
+use chromiumoxide::cdp::browser_protocol::network::{SetBlockedUrLsParams, SetCacheDisabledParams};
+use chromiumoxide::cdp::browser_protocol::page::{
+    RemoveScriptToEvaluateOnNewDocumentParams, ScriptIdentifier,
+};
 use chromiumoxide::error::CdpError;
 use chromiumoxide::page::Page;
 
+pub mod archive;
+mod batch;
+mod blocked;
+mod body;
+pub mod buffer;
+mod builder;
+pub mod bus;
+mod cache;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+mod certs;
+mod config;
+pub mod cookies;
+mod curl;
+pub mod exchange;
+mod export;
+mod extract;
+pub mod fixture;
+mod form;
+pub mod graphql;
+pub mod grpc_web;
+mod handle;
+pub mod har;
+mod intercept;
+pub mod jsonrpc;
+pub mod jwt;
+pub mod media;
+pub mod metrics;
+mod mock;
+pub mod multipart;
+mod navigation;
+pub mod ndjson;
+mod network;
+pub mod openapi;
+pub mod payload;
+pub mod postman;
+mod priority;
+mod redact;
+mod replay;
+mod retry;
+mod rotating;
+mod server_timing;
+mod source;
+pub mod sse;
+pub mod store;
+mod wait;
+mod watch;
+mod websocket;
+#[cfg(feature = "ws-server")]
+pub mod ws_server;
+
+pub use batch::{BatchPolicy, batch_events};
+pub use blocked::BlockedRequest;
+pub use builder::EventBuilder;
+pub use cache::CacheSource;
+#[cfg(windows)]
+pub use export::stream_to_named_pipe;
+#[cfg(unix)]
+pub use export::stream_to_unix_socket;
+pub use export::{
+    CsvColumn, CsvExportConfig, stream_to_jsonl_file, write_csv, write_json_frames, write_jsonl,
+};
+pub use extract::{Extractor, Selector};
+pub use handle::{EventStreamHandle, StreamEndReason, StreamStats};
+pub use intercept::{
+    BodyTransform, Decision, ErrorReason, Fulfillment, InterceptAction, InterceptRule,
+    PausedRequest, RequestChanges, RequestDecider,
+};
+pub use mock::{MockMiss, Mocks};
+pub use navigation::{PageLoad, page_loads};
+pub use network::NetworkConditions;
+pub use redact::{Redaction, Scrubber};
+pub use retry::RetryPolicy;
+pub use rotating::{RotatingJsonlWriter, RotationPolicy, stream_to_rotating_jsonl};
+pub use server_timing::ServerTiming;
+pub use source::{BodyFailure, EventSource, PageSource, ScriptedSource, SourceBatch};
+
+pub use wait::{
+    Deadline, EventResult, EventsResult, UrlPattern, WaitOutcome, collect_until_network_idle,
+    drain, try_next_event, wait_for_content_type, wait_for_event_with_timeout, wait_for_events,
+    wait_for_json, wait_for_url, wait_until,
+};
+pub use websocket::WebSocketHandshake;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("inject_js: {0}")]
     InjectJs(CdpError),
     #[error("drain_js: {0}")]
     DrainJs(CdpError),
+    #[error("event_listener: {0}")]
+    EventListener(CdpError),
+    #[error("configure_network: {0}")]
+    ConfigureNetwork(CdpError),
+    #[error("intercept: {0}")]
+    Intercept(CdpError),
+    #[error("replay: {0}")]
+    Replay(CdpError),
+    #[error("build_matcher: {0}")]
+    BuildMatcher(aho_corasick::BuildError),
+    #[error("invalid_regex: {0}")]
+    InvalidRegex(regex::Error),
+    #[error("decode_base64: {0}")]
+    DecodeBase64(base64::DecodeError),
+    #[error("decode_grpc_web: {0}")]
+    DecodeGrpcWeb(String),
+    #[error("decode_payload: {0}")]
+    DecodePayload(String),
     #[error("parse_json: {0}")]
     ParseJson(serde_json::Error),
+    #[error("binary_body: {url}")]
+    BinaryBody { url: String },
+    #[error("decode_text: {url}: {message}")]
+    DecodeText { url: String, message: String },
+    #[error("parse_body: {url}: {source}")]
+    ParseBody {
+        url: String,
+        source: serde_json::Error,
+    },
+    #[error("serialize_json: {0}")]
+    SerializeJson(serde_json::Error),
+    #[error("invalid_env: {0}")]
+    InvalidEnv(String),
+    #[error("io: {0}")]
+    Io(std::io::Error),
+    #[error("timed out waiting for event")]
+    Timeout,
+    #[error("event stream closed")]
+    StreamClosed,
+}
+
+/// A capture problem reported on the channel from [`EventStreamHandle::take_errors`].
+///
+/// These never stop the event receiver by themselves; they exist so operators can notice that a
+/// capture is degrading (bodies going missing, the poll loop dying) instead of finding out from an
+/// incomplete dataset.
+#[derive(thiserror::Error, Debug)]
+pub enum CaptureError {
+    /// The page saw the response but could not read its body.
+    #[error("body_fetch_failed: {url}: {message}")]
+    BodyFetchFailed { url: String, message: String },
+    /// The poll loop panicked and was restarted, see [`EventStreamConfig::max_restarts`]. Events
+    /// drained in the failed poll are lost.
+    #[error("task_restarted: {panic}")]
+    TaskRestarted { panic: String },
+    /// A response was served with a TLS certificate that expires within
+    /// [`EventStreamConfig::certificate_expiry_window`], or already has. Sent once per
+    /// certificate.
+    #[error("certificate_expiring: {subject} ({url}) expires at {expires_at}")]
+    CertificateExpiring {
+        url: String,
+        subject: String,
+        issuer: String,
+        /// Seconds since the Unix epoch.
+        expires_at: i64,
+    },
+    /// The [`EventStreamConfig::capture_for`] window elapsed. Sent just before
+    /// [`StreamEnded`](Self::StreamEnded), with the stream's final counters.
+    #[error("capture_summary: {} events in {elapsed:?}", stats.events_emitted)]
+    CaptureSummary {
+        elapsed: Duration,
+        stats: StreamStats,
+    },
+    /// The background task stopped; no further events will arrive. Always the last message.
+    #[error("stream_ended: {reason}")]
+    StreamEnded { reason: StreamEndReason },
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct EventStreamConfig {
     pub poll_interval_ms: u64,
     pub url_substring_filter: Option<String>,
     pub content_type_substring_filter: Option<String>,
+    /// Only capture responses whose content type contains one of these substrings, e.g.
+    /// `["image/", "video/"]`. Empty, the default, accepts every type. Applies together with
+    /// `content_type_substring_filter`, and like it not to failed requests.
+    pub content_type_filters: Vec<String>,
+    /// Skip events whose URL contains any of these substrings, e.g. a tracker blocklist. Matched
+    /// with a single precompiled automaton, so long lists stay cheap.
+    pub url_exclude_filters: Vec<String>,
+    /// Only capture requests to the origin of the page that made them, so first-party API
+    /// traffic is not drowned in third-party noise. The origin follows the page across
+    /// navigations, see [`Event::page_url`].
+    pub same_origin_only: bool,
+    /// Maximum number of captured events held in the page between polls. `None` is unbounded.
+    pub max_buffered_events: Option<usize>,
+    /// What to discard once `max_buffered_events` is reached.
+    pub overflow_policy: OverflowPolicy,
+    /// Retry policy for the browser calls made by the crate (hook installation and drains).
+    pub retry: RetryPolicy,
+    /// How many times to restart the poll loop after a panic before ending the stream with
+    /// [`StreamEndReason::Panicked`]. Defaults to `0`.
+    pub max_restarts: u32,
+    /// Emit events in the order their responses arrived (see [`Event::seq`]) instead of the order
+    /// their bodies finished reading. An unfinished response holds later ones back for at most
+    /// 10 seconds.
+    pub ordered: bool,
+    /// Deliver `text/event-stream` responses read through `fetch` as one event per SSE message,
+    /// as the messages arrive, instead of a single event when the connection closes. Parse the
+    /// bodies with [`sse::parse`].
+    pub split_sse_messages: bool,
+    /// Deliver NDJSON / JSON-lines responses read through `fetch` as one event per line, as the
+    /// lines arrive. See [`ndjson`] for splitting bodies captured whole.
+    pub split_ndjson_lines: bool,
+    /// Record the value of `FormData` text fields of up to this many bytes in
+    /// [`Event::request_parts`]. `0`, the default, records only names and sizes.
+    pub multipart_body_limit: usize,
+    /// CSS / XPath selectors run in the page against HTML and XML responses, filling
+    /// [`Event::extracted`].
+    pub extractors: Vec<Extractor>,
+    /// URL patterns the browser refuses to load, applied with `Network.setBlockedURLs` when the
+    /// stream starts. `*` is a wildcard, e.g. `"*doubleclick.net*"`. Unlike the filters above,
+    /// blocked requests are never made. The list replaces any the page already had and stays in
+    /// effect after the stream ends.
+    pub blocked_url_patterns: Vec<String>,
+    /// Bypass the browser cache with `Network.setCacheDisabled` when the stream starts, so every
+    /// captured response comes from the network. Stays in effect after the stream ends.
+    pub disable_cache: bool,
+    /// Requests to pause in the browser and modify before they are sent, through the CDP Fetch
+    /// domain. The first matching rule applies to the request and the first matching transform to
+    /// its response, see [`InterceptRule`]. Interception ends with the stream. Not read from
+    /// config files.
+    #[serde(skip)]
+    pub intercept_rules: Vec<InterceptRule>,
+    /// Also emit an event for requests that failed without a response (network errors, going
+    /// offline, blocked or aborted requests), with [`Event::error`] set. The content-type
+    /// filter does not apply to them.
+    pub capture_failed_requests: bool,
+    /// Recorded responses to answer fetch and XHR requests with instead of the network, see
+    /// [`Mocks`]. Intercept rules matching a request take precedence. Not read from config files.
+    #[serde(skip)]
+    pub mocks: Option<Mocks>,
+    /// What to mask in events before they are emitted. The default masks credential headers,
+    /// see [`Redaction`].
+    pub redaction: Redaction,
+    /// Report certificates that expire within this window as
+    /// [`CaptureError::CertificateExpiring`]. `None`, the default, does not check. Only streams
+    /// started with a handle have an error channel to report on.
+    #[serde(
+        rename = "certificate_expiry_window_ms",
+        deserialize_with = "config::opt_millis"
+    )]
+    pub certificate_expiry_window: Option<Duration>,
+    /// Stamped on every emitted event as [`Event::label`], to tell streams apart once their
+    /// events are merged.
+    pub label: Option<String>,
+    /// Make the page hooks fail and delay captures on purpose, to test how consumers cope with
+    /// missing bodies and out-of-order delivery. `None`, the default, injects nothing.
+    pub fault_injection: Option<FaultInjection>,
+    /// End the stream once this much time has passed since it started, closing the receiver
+    /// with [`StreamEndReason::CaptureWindowElapsed`], e.g. for scheduled sampling. Streams
+    /// with a handle also report a [`CaptureError::CaptureSummary`]. `None`, the default, runs
+    /// until the page goes away.
+    #[serde(rename = "capture_for_ms", deserialize_with = "config::opt_millis")]
+    pub capture_for: Option<Duration>,
+    /// End the stream once this many events were sent, closing the receiver with
+    /// [`StreamEndReason::MaxEventsReached`] and stopping interception, e.g. `Some(1)` when only
+    /// the first matching response matters. `None`, the default, does not limit.
+    pub max_events: Option<u64>,
+    /// Create the stream paused, see [`EventStreamHandle::resume`], with the hooks also installed
+    /// in every document the page loads until the stream ends, before the document's own
+    /// scripts run. Start the stream, navigate, then resume: the first requests of the page load
+    /// are captured instead of racing the setup. Only applies to
+    /// [`start_event_stream_with_handle`], as other streams have no handle to resume.
+    pub start_paused: bool,
+    /// Emit a response whose body is still open this long after its headers arrived, e.g. a long
+    /// poll or a hanging download, with the body received so far and
+    /// [`Event::body_timed_out`] set. `None`, the default, waits for the body to finish. Bodies
+    /// split by `split_sse_messages` or `split_ndjson_lines` stream as they arrive and never
+    /// time out.
+    #[serde(rename = "body_timeout_ms", deserialize_with = "config::opt_millis")]
+    pub body_timeout: Option<Duration>,
+    /// Also report each request as it goes out, as a [`RequestStarted`] on the receiver from
+    /// [`EventStreamHandle::take_request_starts`]. Only URL filters apply to starts, as the
+    /// content type is not known yet. Take the receiver, or starts pile up in memory.
+    pub emit_request_starts: bool,
+    /// Tag events with where their response came from, see [`Event::cache_source`], and count
+    /// them in [`StreamStats`]. Follows CDP network events, which the hooks cannot see.
+    pub tag_cache_source: bool,
+    /// Report requests the browser refused to load (CSP, mixed content, CORS, extensions, ...)
+    /// as [`BlockedRequest`]s on the receiver from [`EventStreamHandle::take_blocked_requests`].
+    /// Covers every resource type, not only fetch and XHR. Only URL filters apply. Only streams
+    /// started with a handle report them; take the receiver, or reports pile up in memory.
+    pub report_blocked_requests: bool,
+    /// Record the fetch priority each request started and finished with, see
+    /// [`Event::initial_priority`]. Follows CDP network events, which the hooks cannot see.
+    pub track_priority: bool,
+    /// Tag events whose request took at least this long to finish as
+    /// [`long_running`](Event::long_running) and count them in [`StreamStats::long_running`]:
+    /// usually long polls, streaming endpoints and hanging downloads. Set
+    /// [`body_timeout`](Self::body_timeout) too to hear about requests that never finish.
+    #[serde(
+        rename = "long_running_threshold_ms",
+        deserialize_with = "config::opt_millis"
+    )]
+    pub long_running_threshold: Option<Duration>,
+    /// Report the WebSockets the page opens, with their handshake headers and status, as
+    /// [`WebSocketHandshake`]s on the receiver from
+    /// [`EventStreamHandle::take_websocket_handshakes`]. Only the URL substring and exclude
+    /// filters apply. Only streams started with a handle report them; take the receiver, or
+    /// reports pile up in memory.
+    pub capture_websocket_handshakes: bool,
+    /// Keep a cookie jar of what the page's requests carried and its responses set, read with
+    /// [`EventStreamHandle::cookies`]. Follows CDP network events, which the hooks cannot see.
+    /// Only streams started with a handle keep one.
+    pub track_cookies: bool,
+}
+
+/// Faults injected by the page hooks, see [`EventStreamConfig::fault_injection`].
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FaultInjection {
+    /// Fraction of response bodies, from `0.0` to `1.0`, whose read fails on purpose. As with a
+    /// real failure no event is emitted, and the failure is counted in
+    /// [`StreamStats::bodies_failed`] and reported as [`CaptureError::BodyFetchFailed`].
+    pub body_failure_rate: f64,
+    /// Hold each event in the page for a random time of up to this long before it can be
+    /// drained, so events arrive out of order. Held events count as in flight.
+    #[serde(rename = "max_delay_ms", deserialize_with = "config::millis")]
+    pub max_delay: Duration,
+}
+
+impl FaultInjection {
+    pub fn body_failure_rate(mut self, rate: f64) -> Self {
+        self.body_failure_rate = rate;
+        self
+    }
+
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+}
+
+/// Eviction policy for the page-side event buffer, see [`EventStreamConfig::max_buffered_events`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Evict the oldest buffered event to make room.
+    #[default]
+    DropOldest,
+    /// Discard the newly captured event.
+    DropNewest,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Event {
     pub url: String,
     #[serde(rename = "contentType", default)]
     pub content_type: Option<String>,
     #[serde(default)]
     pub status: Option<u16>,
+    /// The response body as text or, with [`base64_encoded`](Self::base64_encoded), as base64.
+    /// [`text`](Self::text) and [`bytes`](Self::bytes) read it without tracking which.
     pub body: String,
+    /// `body` holds base64-encoded bytes rather than text. Set for binary content types such as
+    /// images, fonts, PDFs and protobuf, which the page reads as raw bytes.
+    #[serde(rename = "base64Encoded", default)]
+    pub base64_encoded: bool,
+    #[serde(default)]
+    pub method: Option<String>,
+    #[serde(rename = "requestHeaders", default)]
+    pub request_headers: Vec<Header>,
+    /// Request body, when the page sent it as a string or `URLSearchParams`.
+    #[serde(rename = "requestBody", default)]
+    pub request_body: Option<String>,
+    /// Parts of a `FormData` request body, which the page records field by field instead of as a
+    /// string. See [`Event::multipart_parts`].
+    #[serde(rename = "requestParts", default)]
+    pub request_parts: Option<Vec<multipart::Part>>,
+    #[serde(rename = "responseHeaders", default)]
+    pub response_headers: Vec<Header>,
+    /// Names of the request and response headers [redaction](EventStreamConfig::redaction)
+    /// masked, lower-cased. Replays, mocks and [`to_curl`](Self::to_curl) leave these headers
+    /// out rather than send the mask.
+    #[serde(rename = "redactedHeaders", default)]
+    pub redacted_headers: Vec<String>,
+    /// Wall-clock time the request was issued, in milliseconds since the Unix epoch.
+    #[serde(rename = "startedAt", default)]
+    pub started_at: Option<f64>,
+    /// Milliseconds from issuing the request until response headers arrived.
+    #[serde(rename = "timeToHeadersMs", default)]
+    pub time_to_headers_ms: Option<f64>,
+    /// Milliseconds from issuing the request until the body was fully read.
+    #[serde(rename = "durationMs", default)]
+    pub duration_ms: Option<f64>,
+    /// The response was still open after [`EventStreamConfig::body_timeout`], so the event was
+    /// emitted early: `body` holds the text received by then, or nothing for binary bodies.
+    #[serde(rename = "bodyTimedOut", default)]
+    pub body_timed_out: bool,
+    /// The request took at least [`EventStreamConfig::long_running_threshold`] to finish, or its
+    /// body timed out.
+    #[serde(rename = "longRunning", default)]
+    pub long_running: bool,
+    /// Position of this response in the order the page received response headers, counted
+    /// per page from 0. Gaps mark responses whose body could not be read or that were evicted.
+    #[serde(default)]
+    pub seq: Option<u64>,
+    /// Number of the request in the order the page issued it, counted per document from 0.
+    /// Shared with its [`RequestStarted`].
+    #[serde(rename = "requestId", default)]
+    pub request_id: Option<u64>,
+    /// Position of this event in the order the stream emitted it, from 0. Unlike `seq` it keeps
+    /// counting across navigations and task restarts.
+    #[serde(rename = "captureSeq", default)]
+    pub capture_seq: Option<u64>,
+    /// Wall-clock time the response headers arrived, in milliseconds since the Unix epoch.
+    #[serde(rename = "receivedAt", default)]
+    pub received_at: Option<f64>,
+    /// The same moment on the browser's monotonic clock (`performance.timeOrigin +
+    /// performance.now()`), in milliseconds with sub-millisecond resolution. Unaffected by
+    /// wall-clock adjustments, so latencies between events stay exact.
+    #[serde(rename = "receivedMonotonicMs", default)]
+    pub received_monotonic_ms: Option<f64>,
+    /// The body decoded by a [`payload::DecoderRegistry`], if one matched this event.
+    #[serde(default)]
+    pub decoded: Option<serde_json::Value>,
+    /// Values matched by the configured [`Extractor`]s, by extractor name. Extractors that
+    /// matched nothing are absent.
+    #[serde(default)]
+    pub extracted: BTreeMap<String, Vec<String>>,
+    /// Where an [`InterceptAction::Rewrite`] rule actually sent the request; `url` stays the
+    /// URL the page asked for.
+    #[serde(rename = "rewrittenUrl", default)]
+    pub rewritten_url: Option<String>,
+    /// Label of the [`NetworkConditions`] emulated when the event was drained, see
+    /// [`EventStreamHandle::emulate_network`].
+    #[serde(rename = "networkCondition", default)]
+    pub network_condition: Option<String>,
+    /// Whether the response came from the network, a cache or a service worker. Only set with
+    /// [`EventStreamConfig::tag_cache_source`].
+    #[serde(rename = "cacheSource", default)]
+    pub cache_source: Option<CacheSource>,
+    /// The fetch priority the browser gave the request when it went out, as CDP names it:
+    /// `VeryLow`, `Low`, `Medium`, `High` or `VeryHigh`. Only set with
+    /// [`EventStreamConfig::track_priority`].
+    #[serde(rename = "initialPriority", default)]
+    pub initial_priority: Option<String>,
+    /// The fetch priority when the request finished, after any changes the browser made while it
+    /// was loading, e.g. once an image turned out to be in the viewport. Same values and
+    /// condition as [`initial_priority`](Self::initial_priority).
+    #[serde(rename = "finalPriority", default)]
+    pub final_priority: Option<String>,
+    /// The response body as the server sent it, when an [`InterceptAction::Transform`] rule
+    /// replaced it; `body` is what the page received. Encoded like `body`.
+    #[serde(rename = "originalBody", default)]
+    pub original_body: Option<String>,
+    /// Why the request failed without a response, e.g. `Failed to fetch` while offline. Only set
+    /// with [`EventStreamConfig::capture_failed_requests`]; such events have no status or body.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// URL of the page's document when the request was made.
+    #[serde(rename = "pageUrl", default)]
+    pub page_url: Option<String>,
+    /// The navigation the request was made in, counted from 0 for the page the stream started
+    /// on and increasing with every page load or single-page-app route change after it, see
+    /// [`page_loads`]. Ids are not necessarily contiguous.
+    #[serde(rename = "navigationId", default)]
+    pub navigation_id: Option<u64>,
+    /// Wall-clock time the page's document started loading, in milliseconds since the Unix
+    /// epoch. Tells apart two loads of the same URL.
+    #[serde(rename = "documentStartedAt", default)]
+    pub document_started_at: Option<f64>,
+    /// The [`EventStreamConfig::label`] of the stream that emitted the event.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// A request going out, sent while it starts so consumers can track concurrency and spot
+/// requests that never complete. See [`EventStreamConfig::emit_request_starts`].
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct RequestStarted {
+    /// Matches the [`Event::request_id`] of the request's event, if it gets one.
+    #[serde(rename = "requestId")]
+    pub request_id: u64,
+    pub url: String,
+    pub method: String,
+    /// Wall-clock time the request was issued, in milliseconds since the Unix epoch.
+    #[serde(rename = "startedAt")]
+    pub started_at: f64,
+    #[serde(rename = "pageUrl", default)]
+    pub page_url: Option<String>,
+    /// The [`EventStreamConfig::label`] of the stream.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// A single HTTP header as seen by the page. Names are lower-cased by the browser.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Header {
+    pub name: String,
+    pub value: String,
 }
 
-// Internal structure to track pending responses
-#[derive(Clone, Debug)]
-struct PendingResponse {
-    url: String,
+impl Event {
+    /// First request header named `name` (case-insensitive).
+    pub fn request_header(&self, name: &str) -> Option<&str> {
+        find_header(&self.request_headers, name)
+    }
+
+    /// First response header named `name` (case-insensitive).
+    pub fn response_header(&self, name: &str) -> Option<&str> {
+        find_header(&self.response_headers, name)
+    }
+}
+
+fn find_header<'a>(headers: &'a [Header], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|h| h.name.eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str())
+}
+
+// The Rust-side capture filter, compiled once per stream from its config.
+struct CaptureFilter {
+    url: Option<String>,
     content_type: Option<String>,
-    status: Option<u16>,
+    content_types: Vec<String>,
+    // All of `url_exclude_filters` in one automaton, so each URL is scanned once.
+    exclude: Option<AhoCorasick>,
+    same_origin: bool,
 }
 
-// Helper function to check if an event should be captured
-fn should_capture(config: &EventStreamConfig, url: &str, content_type: Option<&str>) -> bool {
-    let url_ok = config
-        .url_substring_filter
-        .as_ref()
-        .map(|filter| url.contains(filter))
-        .unwrap_or(true);
+impl CaptureFilter {
+    fn new(config: &EventStreamConfig) -> Result<Self, Error> {
+        let exclude = if config.url_exclude_filters.is_empty() {
+            None
+        } else {
+            Some(AhoCorasick::new(&config.url_exclude_filters).map_err(Error::BuildMatcher)?)
+        };
+        Ok(Self {
+            url: config.url_substring_filter.clone(),
+            content_type: config.content_type_substring_filter.clone(),
+            content_types: config.content_type_filters.clone(),
+            exclude,
+            same_origin: config.same_origin_only,
+        })
+    }
+
+    // Helper function to check if an event should be captured. Failed requests have no
+    // response, so the content-type filter does not apply to them.
+    fn matches(&self, ev: &Event) -> bool {
+        let content_type = ev.content_type.as_deref();
+        let ct_ok = ev.error.is_some()
+            || self
+                .content_type
+                .as_ref()
+                .map(|filter| content_type.map(|ct| ct.contains(filter)).unwrap_or(false))
+                .unwrap_or(true)
+                && (self.content_types.is_empty()
+                    || content_type.is_some_and(|ct| {
+                        self.content_types.iter().any(|filter| ct.contains(filter))
+                    }));
+
+        ct_ok && self.matches_url(&ev.url, ev.page_url.as_deref())
+    }
+
+    // The URL filters alone, which is all a request start can be checked against.
+    fn matches_url(&self, url: &str, page_url: Option<&str>) -> bool {
+        let url_ok = self
+            .url
+            .as_ref()
+            .map(|filter| url.contains(filter))
+            .unwrap_or(true);
+
+        let excluded = self.exclude.as_ref().is_some_and(|ac| ac.is_match(url));
 
-    let ct_ok = config
-        .content_type_substring_filter
-        .as_ref()
-        .map(|filter| content_type.map(|ct| ct.contains(filter)).unwrap_or(false))
-        .unwrap_or(true);
+        // Events without a page URL come from older hooks and are let through.
+        let origin_ok = !self.same_origin
+            || page_url.is_none_or(|page_url| {
+                match (url::Url::parse(url), url::Url::parse(page_url)) {
+                    (Ok(url), Ok(page_url)) => url.origin() == page_url.origin(),
+                    _ => true,
+                }
+            });
 
-    url_ok && ct_ok
+        url_ok && !excluded && origin_ok
+    }
 }
 
+// Page-side hook script; evaluated as `(<script>)(<cfg>)`.
+const HOOKS_JS: &str = include_str!("hooks.js");
+
 /// Install JS hooks to capture responses (any content-type) from fetch/XHR into a window buffer.
+#[tracing::instrument(level = "debug", skip_all, err)]
 async fn install_event_hooks(page: &Page, config: &EventStreamConfig) -> Result<(), Error> {
-    let url_filter_js =
-        serde_json::to_string(&config.url_substring_filter).unwrap_or("null".into());
-    let ct_filter_js =
-        serde_json::to_string(&config.content_type_substring_filter).unwrap_or("null".into());
-
-    let js = format!(
-        r#"(function(cfg){{
-  try {{
-    window.__event_stream = window.__event_stream || [];
-    const urlFilter = cfg.urlFilter; // string or null
-    const ctFilter = cfg.ctFilter;   // string or null
-
-    function shouldCapture(url, ct) {{
-      const okUrl = !urlFilter || (url && url.indexOf(urlFilter) !== -1);
-      const okCt = !ctFilter || (ct && ct.indexOf(ctFilter) !== -1);
-      return okUrl && okCt;
-    }}
-
-    // fetch hook
-    if (!window.__event_fetch_hooked) {{
-      window.__event_fetch_hooked = true;
-      const origFetch = window.fetch;
-      window.fetch = async function(input, init) {{
-        const res = await origFetch.apply(this, arguments);
-        try {{
-          const ct = (res.headers && res.headers.get && res.headers.get('content-type')) || '';
-          const url = res.url || (typeof input === 'string' ? input : (input && input.url) || '');
-          if (shouldCapture(url, ct)) {{
-            const clone = res.clone();
-            clone.text().then(function(txt) {{
-              try {{
-                window.__event_stream.push({{ url: url, body: txt, contentType: ct, status: res.status }});
-              }} catch(e) {{}}
-            }});
-          }}
-        }} catch(e) {{}}
-        return res;
-      }};
-    }}
-
-    // XHR hook
-    if (!window.__event_xhr_hooked) {{
-      window.__event_xhr_hooked = true;
-      const origOpen = XMLHttpRequest.prototype.open;
-      const origSend = XMLHttpRequest.prototype.send;
-      XMLHttpRequest.prototype.open = function(method, url) {{
-        try {{ this.__event_url = url; }} catch(e) {{}}
-        return origOpen.apply(this, arguments);
-      }};
-      XMLHttpRequest.prototype.send = function(body) {{
-        this.addEventListener('load', function() {{
-          try {{
-            const ct = (this.getResponseHeader && this.getResponseHeader('content-type')) || '';
-            const url = this.responseURL || this.__event_url || '';
-            if (shouldCapture(url, ct)) {{
-              window.__event_stream.push({{ url: url, body: this.responseText || '', contentType: ct, status: this.status }});
-            }}
-          }} catch(e) {{}}
-        }});
-        return origSend.apply(this, arguments);
-      }};
-    }}
-  }} catch(e) {{}}
-}})({{ urlFilter: {}, ctFilter: {} }});"#,
-        url_filter_js, ct_filter_js,
-    );
-
-    page.evaluate_expression(js)
+    let js = hooks_script(config);
+    config
+        .retry
+        .run(|| page.evaluate_expression(js.as_str()))
         .await
         .map_err(Error::InjectJs)?;
     Ok(())
 }
 
-/// Drain and parse all captured raw events from the page buffer.
-async fn drain_events(page: &Page) -> Result<Vec<Event>, Error> {
-    let js = "(() => { try { if (!window.__event_stream) return '[]'; const a = window.__event_stream.splice(0); return JSON.stringify(a); } catch(e) { return '[]'; } })()";
-    let mut s: String = page
-        .evaluate_expression(js)
+/// Install the hooks in every document `page` loads from now on, before its own scripts run.
+/// Returns the registration to remove once the stream ends.
+#[tracing::instrument(level = "debug", skip_all, err)]
+async fn install_hooks_on_new_documents(
+    page: &Page,
+    config: &EventStreamConfig,
+) -> Result<ScriptIdentifier, Error> {
+    let js = hooks_script(config);
+    config
+        .retry
+        .run(|| page.evaluate_on_new_document(js.as_str()))
         .await
-        .map_err(Error::DrainJs)?
-        .into_value()
-        .unwrap_or_default();
-    if s.is_empty() {
-        s = "[]".to_string();
+        .map_err(Error::InjectJs)
+}
+
+fn hooks_script(config: &EventStreamConfig) -> String {
+    let cfg = serde_json::json!({
+        "urlFilter": config.url_substring_filter,
+        "ctFilter": config.content_type_substring_filter,
+        "ctAny": config.content_type_filters,
+        "urlExclude": config.url_exclude_filters,
+        "maxBuffered": config.max_buffered_events,
+        "dropNewest": config.overflow_policy == OverflowPolicy::DropNewest,
+        "ordered": config.ordered,
+        "splitSse": config.split_sse_messages,
+        "splitNdjson": config.split_ndjson_lines,
+        "partBodyLimit": config.multipart_body_limit,
+        "extractors": config.extractors,
+        "captureFailures": config.capture_failed_requests,
+        "sameOriginOnly": config.same_origin_only,
+        "faults": config.fault_injection.as_ref().map(|faults| serde_json::json!({
+            "bodyFailureRate": faults.body_failure_rate,
+            "maxDelayMs": faults.max_delay.as_millis() as u64,
+        })),
+        "bodyTimeoutMs": config.body_timeout.map(|timeout| timeout.as_millis() as u64),
+        "emitStarts": config.emit_request_starts,
+    });
+    format!("({HOOKS_JS})({cfg});")
+}
+
+/// Apply the browser-side network settings of `config` to `page`.
+#[tracing::instrument(level = "debug", skip_all, err)]
+async fn configure_network(page: &Page, config: &EventStreamConfig) -> Result<(), Error> {
+    if config.disable_cache {
+        config
+            .retry
+            .run(|| page.execute(SetCacheDisabledParams::new(true)))
+            .await
+            .map_err(Error::ConfigureNetwork)?;
+    }
+    if !config.blocked_url_patterns.is_empty() {
+        let params = SetBlockedUrLsParams::new(config.blocked_url_patterns.clone());
+        config
+            .retry
+            .run(|| page.execute(params.clone()))
+            .await
+            .map_err(Error::ConfigureNetwork)?;
+    }
+    Ok(())
+}
+
+// Capacity of the error channel; errors beyond it are dropped while the consumer lags.
+const ERROR_CHANNEL_CAPACITY: usize = 64;
+
+// Errors that mean the connection to the page is gone rather than that one evaluation failed.
+fn end_reason_for(e: Error) -> StreamEndReason {
+    match e {
+        Error::DrainJs(CdpError::ChannelSendError(_) | CdpError::Ws(_) | CdpError::NoResponse) => {
+            StreamEndReason::PageClosed
+        }
+        e => StreamEndReason::DrainFailed(e.to_string()),
     }
-    let events: Vec<Event> = serde_json::from_str(&s).map_err(Error::ParseJson)?;
-    Ok(events)
 }
 
 /// Start a background task that polls for captured events and streams them over a mpsc channel.
-/// Returns the receiver; the task ends when the `Page` errors or the sender is dropped.
+/// Returns the receiver; the task ends when the `Page` closes or errors, or the receiver is
+/// dropped.
 pub async fn start_event_stream(
     page: Page,
     config: EventStreamConfig,
 ) -> Result<mpsc::UnboundedReceiver<Event>, Error> {
-    install_event_hooks(&page, &config).await?;
+    let (handle, rx) = spawn_event_stream(page, config, false).await?;
+    // Nobody can take the side channels; drop them rather than queue into them.
+    handle.shared.close_side_channels();
+    Ok(rx)
+}
+
+/// Like [`start_event_stream`], with every event tagged with `label`, e.g. the tab it came from.
+/// Shorthand for setting [`EventStreamConfig::label`].
+pub async fn start_event_stream_labeled(
+    page: Page,
+    config: EventStreamConfig,
+    label: impl Into<String>,
+) -> Result<mpsc::UnboundedReceiver<Event>, Error> {
+    let config = EventStreamConfig {
+        label: Some(label.into()),
+        ..config
+    };
+    start_event_stream(page, config).await
+}
+
+/// Like [`start_event_stream`], but also returns an [`EventStreamHandle`] for observing the
+/// running stream.
+pub async fn start_event_stream_with_handle(
+    page: Page,
+    config: EventStreamConfig,
+) -> Result<(EventStreamHandle, mpsc::UnboundedReceiver<Event>), Error> {
+    spawn_event_stream(page, config, true).await
+}
 
-    let (mut tx, rx) = mpsc::unbounded();
-    let interval = config.poll_interval_ms;
+// Start the stream; `with_handle` is false when the caller drops the handle, so nothing only
+// it could read is tracked.
+async fn spawn_event_stream(
+    page: Page,
+    config: EventStreamConfig,
+    with_handle: bool,
+) -> Result<(EventStreamHandle, mpsc::UnboundedReceiver<Event>), Error> {
+    let filter = Arc::new(CaptureFilter::new(&config)?);
+    install_event_hooks(&page, &config).await?;
+    configure_network(&page, &config).await?;
+    let (tx, rx) = mpsc::unbounded();
+    let (mut err_tx, err_rx) = mpsc::channel(ERROR_CHANNEL_CAPACITY);
+    let handle = EventStreamHandle::new(page.clone(), err_rx, &config);
+    let shared = handle.shared.clone();
+    let interceptor =
+        intercept::Interceptor::start(&page, &config, Arc::clone(&shared.original_bodies)).await?;
+    let mut detached = page
+        .event_listener::<EventDetached>()
+        .await
+        .map_err(Error::EventListener)?;
 
-    tokio::spawn(async move {
-        loop {
-            match drain_events(&page).await {
-                Ok(events) => {
-                    for ev in events {
-                        if tx.send(ev).await.is_err() {
-                            return; // receiver dropped
+    // Shared by the supervisor and every poll loop it starts.
+    let config = Arc::new(config);
+    let mut followers = capture_followers(&config, &shared);
+    if let Some(window) = config.certificate_expiry_window {
+        let redaction = config.redaction.clone();
+        followers.push(Box::new(certs::CertificateFollower::new(
+            window,
+            redaction,
+            err_tx.clone(),
+        )));
+    }
+    if config.report_blocked_requests {
+        followers.push(Box::new(blocked::BlockedFollower {
+            config: Arc::clone(&config),
+            filter: Arc::clone(&filter),
+            shared: shared.clone(),
+        }));
+    }
+    if with_handle && config.track_cookies {
+        followers.push(Box::new(cookies::CookieFollower(shared.clone())));
+    }
+    let network_watcher = watch::NetworkWatcher::start(&page, followers).await?;
+    let websocket_watcher = if config.capture_websocket_handshakes {
+        let config = Arc::clone(&config);
+        let filter = Arc::clone(&filter);
+        Some(websocket::WebSocketWatcher::start(&page, config, filter, shared.clone()).await?)
+    } else {
+        None
+    };
+    // Last, so no error after it leaves the script registered; the watchers and interceptor
+    // above stop when dropped.
+    let new_document_hooks = if config.start_paused {
+        handle.pause();
+        Some(install_hooks_on_new_documents(&page, &config).await?)
+    } else {
+        None
+    };
+    let span = tracing::debug_span!("event_stream", target_id = ?page.target_id());
+    tokio::spawn(
+        async move {
+            let _running = handle::RunningGuard(shared.clone());
+            let started = tokio::time::Instant::now();
+            // A window too long to represent never elapses.
+            let deadline = config
+                .capture_for
+                .and_then(|window| started.checked_add(window));
+            let mut restarts = 0;
+            // Supervise the poll loop: a panic inside it restarts it with fresh listeners, up to
+            // `max_restarts` times.
+            let reason = loop {
+                let mut worker = tokio::spawn(
+                    poll_loop(
+                        source::PageSource::new(page.clone(), config.retry, detached),
+                        Arc::clone(&config),
+                        Arc::clone(&filter),
+                        tx.clone(),
+                        err_tx.clone(),
+                        shared.clone(),
+                    )
+                    .in_current_span(),
+                );
+                let joined = match deadline {
+                    Some(deadline) => match tokio::time::timeout_at(deadline, &mut worker).await {
+                        Ok(joined) => joined,
+                        Err(_) => {
+                            worker.abort();
+                            tracing::debug!("capture window elapsed, stopping");
+                            let _ = err_tx.try_send(CaptureError::CaptureSummary {
+                                elapsed: started.elapsed(),
+                                stats: shared.stats(),
+                            });
+                            break StreamEndReason::CaptureWindowElapsed;
                         }
-                    }
-                }
-                Err(_e) => {
-                    // page likely went away; stop
-                    return;
+                    },
+                    None => worker.await,
+                };
+                let panic = match joined {
+                    Ok(reason) => break reason,
+                    Err(e) => panic_message(e),
+                };
+                if restarts >= config.max_restarts {
+                    tracing::error!(%panic, "poll loop panicked, stopping");
+                    break StreamEndReason::Panicked(panic);
                 }
+                restarts += 1;
+                shared.restarts.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(%panic, restarts, "poll loop panicked, restarting");
+                let _ = err_tx.try_send(CaptureError::TaskRestarted { panic });
+                detached = match page.event_listener::<EventDetached>().await {
+                    Ok(listener) => listener,
+                    Err(_) => break StreamEndReason::PageClosed,
+                };
+            };
+            if let Some(interceptor) = interceptor {
+                interceptor.stop().await;
+            }
+            drop(network_watcher);
+            if let Some(identifier) = new_document_hooks {
+                let _ = page
+                    .execute(RemoveScriptToEvaluateOnNewDocumentParams::new(identifier))
+                    .await;
             }
-            tokio::time::sleep(Duration::from_millis(interval)).await;
+            drop(websocket_watcher);
+            shared.close_side_channels();
+            shared.set_end_reason(reason.clone());
+            let _ = err_tx.try_send(CaptureError::StreamEnded { reason });
         }
-    });
+        .instrument(span),
+    );
 
-    Ok(rx)
+    Ok((handle, rx))
 }
 
-pub enum EventResult {
-    Timeout,
-    StreamClosed,
-    Ok(Event),
-}
-
-/// Wait for the next event from the receiver with a timeout.
-/// Returns `Ok(Some(event))` if an event is received, `Ok(None)` if the stream is closed,
-/// or `Err(())` if the timeout expires before an event is received.
-pub async fn wait_for_event_with_timeout(
-    rx: &mut mpsc::UnboundedReceiver<Event>,
-    timeout: Duration,
-) -> EventResult {
-    match time::timeout(timeout, rx.next()).await {
-        Ok(Some(event)) => EventResult::Ok(event),
-        Ok(None) => EventResult::StreamClosed,
-        Err(_) => EventResult::Timeout,
+/// Run the capture loop in the calling task, forwarding events into `sink`.
+///
+/// This is the loop [`start_event_stream`] spawns, without the spawn, the supervisor or the
+/// handle: the caller drives it, and cancels it by dropping the future. Interception is still
+/// disabled on cancellation, in a background task. Returns why the loop stopped; sink errors
+/// end it with [`StreamEndReason::ReceiverDropped`].
+pub async fn run_event_loop<S>(
+    page: Page,
+    config: EventStreamConfig,
+    sink: S,
+) -> Result<StreamEndReason, Error>
+where
+    S: Sink<Event> + Unpin,
+{
+    let filter = Arc::new(CaptureFilter::new(&config)?);
+    install_event_hooks(&page, &config).await?;
+    configure_network(&page, &config).await?;
+    let shared = Arc::new(handle::Shared::default());
+    let interceptor =
+        intercept::Interceptor::start(&page, &config, Arc::clone(&shared.original_bodies)).await?;
+    let followers = capture_followers(&config, &shared);
+    let _network_watcher = watch::NetworkWatcher::start(&page, followers).await?;
+    let detached = page
+        .event_listener::<EventDetached>()
+        .await
+        .map_err(Error::EventListener)?;
+
+    // Nobody observes the counters or errors of a caller-driven loop.
+    let (err_tx, _) = mpsc::channel(0);
+    let source = source::PageSource::new(page, config.retry, detached);
+    let window = config.capture_for;
+    let poll = poll_loop(source, Arc::new(config), filter, sink, err_tx, shared);
+    let reason = within(window, poll).await;
+    if let Some(interceptor) = interceptor {
+        interceptor.stop().await;
+    }
+    Ok(reason)
+}
+
+/// Run the capture loop over `source` instead of a page, forwarding events into `sink`.
+///
+/// Events go through the same filtering, labelling, [redaction](EventStreamConfig::redaction)
+/// and [intercept annotation](Event::original_body) as on a live stream; settings that act on the
+/// page, such as hooks, network conditions and interception, do not apply. Returns once the
+/// source closes or a drain fails, or with [`StreamEndReason::ReceiverDropped`] on sink errors.
+pub async fn run_event_source<Src, S>(
+    source: Src,
+    config: EventStreamConfig,
+    sink: S,
+) -> Result<StreamEndReason, Error>
+where
+    Src: EventSource,
+    S: Sink<Event> + Unpin,
+{
+    let filter = Arc::new(CaptureFilter::new(&config)?);
+    let (err_tx, _) = mpsc::channel(0);
+    let shared = Arc::new(handle::Shared::default());
+    let window = config.capture_for;
+    let poll = poll_loop(source, Arc::new(config), filter, sink, err_tx, shared);
+    Ok(within(window, poll).await)
+}
+
+// Run a caller-driven loop for at most its `capture_for` window.
+async fn within(
+    window: Option<Duration>,
+    poll: impl Future<Output = StreamEndReason>,
+) -> StreamEndReason {
+    match window {
+        Some(window) => tokio::time::timeout(window, poll)
+            .await
+            .unwrap_or(StreamEndReason::CaptureWindowElapsed),
+        None => poll.await,
+    }
+}
+
+// What the network watch follows for the events themselves.
+fn capture_followers(
+    config: &EventStreamConfig,
+    shared: &handle::Shared,
+) -> Vec<Box<dyn watch::Follower>> {
+    let mut followers: Vec<Box<dyn watch::Follower>> = Vec::new();
+    if config.tag_cache_source {
+        let sources = Arc::clone(&shared.cache_sources);
+        followers.push(Box::new(cache::CacheFollower(sources)));
+    }
+    if config.track_priority {
+        let priorities = Arc::clone(&shared.priorities);
+        followers.push(Box::new(priority::PriorityFollower(priorities)));
+    }
+    followers
+}
+
+fn panic_message(e: tokio::task::JoinError) -> String {
+    match e.try_into_panic() {
+        Ok(payload) => payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "non-string panic payload".to_string()),
+        Err(e) => e.to_string(),
+    }
+}
+
+// Poll the source until it closes, a drain fails or the receiver is dropped.
+async fn poll_loop<Src, S>(
+    mut source: Src,
+    config: Arc<EventStreamConfig>,
+    filter: Arc<CaptureFilter>,
+    mut tx: S,
+    mut err_tx: mpsc::Sender<CaptureError>,
+    shared: Arc<handle::Shared>,
+) -> StreamEndReason
+where
+    Src: EventSource,
+    S: Sink<Event> + Unpin,
+{
+    let limit_reached = |shared: &handle::Shared| {
+        config
+            .max_events
+            .is_some_and(|max| shared.events_emitted.load(Ordering::Relaxed) >= max)
+    };
+    loop {
+        if limit_reached(&shared) {
+            tracing::debug!("max events reached, stopping");
+            return StreamEndReason::MaxEventsReached;
+        }
+        // A paused stream leaves captures in the source until resumed.
+        let drained = if shared.paused.load(Ordering::Relaxed) {
+            None
+        } else {
+            Some(source.drain().await)
+        };
+        match drained {
+            None => {}
+            Some(Ok(batch)) => {
+                shared.in_flight.store(batch.in_flight, Ordering::Relaxed);
+                shared
+                    .bodies_failed
+                    .fetch_add(batch.body_failures, Ordering::Relaxed);
+                shared
+                    .events_evicted
+                    .fetch_add(batch.evicted, Ordering::Relaxed);
+                for mut start in batch.starts {
+                    if !filter.matches_url(&start.url, start.page_url.as_deref()) {
+                        continue;
+                    }
+                    start.label.clone_from(&config.label);
+                    start.url = config.redaction.redact_url(&start.url);
+                    if let Some(page_url) = start.page_url.as_mut() {
+                        *page_url = config.redaction.redact_url(page_url);
+                    }
+                    shared.send_request_start(start);
+                }
+                for e in batch.errors {
+                    // Never block capture on the error consumer: drop when full or gone.
+                    let _ = err_tx.try_send(CaptureError::BodyFetchFailed {
+                        url: config.redaction.redact_url(&e.url),
+                        message: e.message,
+                    });
+                }
+                for mut ev in batch.events {
+                    shared
+                        .navigations
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .assign(&mut ev);
+                    // Hooks installed by an earlier stream on the same page keep their
+                    // own filters, so re-check ours before forwarding.
+                    if !filter.matches(&ev) {
+                        shared.events_filtered.fetch_add(1, Ordering::Relaxed);
+                        continue;
+                    }
+                    ev.network_condition = shared.network_condition();
+                    ev.label.clone_from(&config.label);
+                    ev.capture_seq = Some(shared.events_emitted.load(Ordering::Relaxed));
+                    intercept::annotate(&config.intercept_rules, &shared.original_bodies, &mut ev);
+                    if config.tag_cache_source {
+                        ev.cache_source = shared.cache_sources.take(&ev.url, ev.started_at);
+                    }
+                    if config.track_priority {
+                        shared.priorities.annotate(&mut ev);
+                    }
+                    if let Some(threshold) = config.long_running_threshold {
+                        let threshold_ms = threshold.as_secs_f64() * 1000.0;
+                        ev.long_running = ev.body_timed_out
+                            || ev.duration_ms.is_some_and(|ms| ms >= threshold_ms);
+                    }
+                    config.redaction.apply(&mut ev);
+                    tracing::trace!(url = %ev.url, status = ?ev.status, "sending event");
+                    let len = ev.body.len() as u64;
+                    let cache_source = ev.cache_source;
+                    let long_running = ev.long_running;
+                    if tx.send(ev).await.is_err() {
+                        tracing::debug!("receiver dropped, stopping");
+                        return StreamEndReason::ReceiverDropped;
+                    }
+                    shared.events_emitted.fetch_add(1, Ordering::Relaxed);
+                    shared.bytes_captured.fetch_add(len, Ordering::Relaxed);
+                    shared.record_event();
+                    if let Some(source) = cache_source {
+                        shared.record_cache_source(source);
+                    }
+                    if long_running {
+                        shared.long_running.fetch_add(1, Ordering::Relaxed);
+                    }
+                    if limit_reached(&shared) {
+                        tracing::debug!("max events reached, stopping");
+                        return StreamEndReason::MaxEventsReached;
+                    }
+                }
+            }
+            Some(Err(e)) => {
+                tracing::warn!(error = %e, "draining events failed, stopping");
+                return end_reason_for(e);
+            }
+        }
+
+        // Sleep until the next poll, waking early if the source goes away.
+        let sleep = pin!(tokio::time::sleep(Duration::from_millis(
+            config.poll_interval_ms
+        )));
+        let closed = pin!(source.closed());
+        if let Either::Right((reason, _)) = future::select(sleep, closed).await {
+            tracing::debug!(%reason, "source closed, stopping");
+            return reason;
+        }
     }
 }
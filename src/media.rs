@@ -0,0 +1,573 @@
+//! HLS and DASH manifest tracking.
+//!
+//! Feed every event to a [`MediaTracker`] (or wrap the receiver with [`tag_media`]): it parses
+//! `.m3u8` playlists and `.mpd` manifests as they are captured and tags later segment requests
+//! with the variant they belong to. DASH manifests are scanned rather than fully parsed, which
+//! covers `SegmentTemplate`, `SegmentList` and `BaseURL` addressing.
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use regex::Regex;
+use serde::Serialize;
+use url::Url;
+
+use crate::Event;
+
+static ADAPTATION_SET: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<AdaptationSet\b.*?</AdaptationSet>").unwrap());
+static REPRESENTATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<Representation\b([^>]*?)(?:/>|>(.*?)</Representation>)").unwrap()
+});
+static SEGMENT_URL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"<(?:SegmentURL|Initialization)\b[^>]*?(?:media|sourceURL)="([^"]*)""#).unwrap()
+});
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\$(?:Number|Time|SubNumber)(?:%0?\d*d)?\$").unwrap());
+// Any start tag, and any attribute, by name.
+static START_TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<([A-Za-z_][-\w:.]*)([^>]*)").unwrap());
+static ATTRIBUTE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"([A-Za-z_:][-\w:.]*)="([^"]*)""#).unwrap());
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ManifestKind {
+    /// An HLS playlist listing variant streams.
+    HlsMaster,
+    /// An HLS playlist listing segments.
+    HlsMedia,
+    Dash,
+}
+
+/// One rendition of the stream.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Variant {
+    /// HLS variant playlist URL; empty for DASH.
+    pub uri: String,
+    /// DASH `Representation@id`.
+    pub id: Option<String>,
+    /// Peak bits per second, from `BANDWIDTH` / `@bandwidth`.
+    pub bandwidth: Option<u64>,
+    /// `WIDTHxHEIGHT`.
+    pub resolution: Option<String>,
+    pub codecs: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum MediaTag {
+    Manifest {
+        kind: ManifestKind,
+        /// Variants listed by a master playlist or MPD; empty for HLS media playlists.
+        variants: Vec<Variant>,
+        /// Segment URLs listed explicitly (HLS media playlists, DASH `SegmentList`).
+        segments: usize,
+    },
+    Segment {
+        /// The playlist or MPD that listed this segment.
+        manifest_url: String,
+        /// `None` when the segment's playlist was captured without its master playlist.
+        variant: Option<Variant>,
+    },
+}
+
+/// Tracks manifests seen so far and recognises the segments they list.
+#[derive(Debug, Default)]
+pub struct MediaTracker {
+    // HLS variant playlist URL (without query) -> variant.
+    variant_playlists: HashMap<String, Variant>,
+    // Segment URL (without query) -> (manifest URL, variant).
+    segments: HashMap<String, (String, Option<Variant>)>,
+    // DASH templates expanded into URL patterns.
+    templates: Vec<(Regex, String, Variant)>,
+}
+
+impl MediaTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `event` if it is a manifest, or tag it if it is a known segment.
+    pub fn observe(&mut self, event: &Event) -> Option<MediaTag> {
+        if let Some(kind) = manifest_kind(event) {
+            return Some(self.record_manifest(kind, event));
+        }
+        let key = without_query(&event.url);
+        if let Some((manifest_url, variant)) = self.segments.get(key) {
+            return Some(MediaTag::Segment {
+                manifest_url: manifest_url.clone(),
+                variant: variant.clone(),
+            });
+        }
+        self.templates
+            .iter()
+            .find(|(pattern, _, _)| pattern.is_match(key))
+            .map(|(_, manifest_url, variant)| MediaTag::Segment {
+                manifest_url: manifest_url.clone(),
+                variant: Some(variant.clone()),
+            })
+    }
+
+    fn record_manifest(&mut self, kind: ManifestKind, event: &Event) -> MediaTag {
+        // Live streams refetch their manifests; only the latest copy of each counts.
+        self.forget_manifest(without_query(&event.url));
+        let Ok(base) = Url::parse(&event.url) else {
+            return MediaTag::Manifest {
+                kind,
+                variants: Vec::new(),
+                segments: 0,
+            };
+        };
+        match kind {
+            ManifestKind::HlsMaster => {
+                let variants = parse_hls_master(&base, &event.body);
+                for variant in &variants {
+                    self.variant_playlists
+                        .insert(without_query(&variant.uri).to_string(), variant.clone());
+                }
+                MediaTag::Manifest {
+                    kind,
+                    variants,
+                    segments: 0,
+                }
+            }
+            ManifestKind::HlsMedia => {
+                let variant = self
+                    .variant_playlists
+                    .get(without_query(&event.url))
+                    .cloned();
+                let segments = parse_hls_segments(&base, &event.body);
+                let count = segments.len();
+                for segment in segments {
+                    self.segments.insert(
+                        without_query(&segment).to_string(),
+                        (event.url.clone(), variant.clone()),
+                    );
+                }
+                MediaTag::Manifest {
+                    kind,
+                    variants: Vec::new(),
+                    segments: count,
+                }
+            }
+            ManifestKind::Dash => {
+                let representations = parse_dash(&base, &event.body);
+                let mut count = 0;
+                for rep in &representations {
+                    for url in &rep.segments {
+                        self.segments.insert(
+                            without_query(url).to_string(),
+                            (event.url.clone(), Some(rep.variant.clone())),
+                        );
+                        count += 1;
+                    }
+                    if let Some(pattern) = &rep.template {
+                        self.templates.push((
+                            pattern.clone(),
+                            event.url.clone(),
+                            rep.variant.clone(),
+                        ));
+                    }
+                }
+                MediaTag::Manifest {
+                    kind,
+                    variants: representations.into_iter().map(|r| r.variant).collect(),
+                    segments: count,
+                }
+            }
+        }
+    }
+
+    fn forget_manifest(&mut self, url: &str) {
+        self.segments
+            .retain(|_, (manifest_url, _)| without_query(manifest_url) != url);
+        self.templates
+            .retain(|(_, manifest_url, _)| without_query(manifest_url) != url);
+    }
+}
+
+/// Forward every event from `rx` together with its [`MediaTag`], if any.
+pub fn tag_media(
+    mut rx: mpsc::UnboundedReceiver<Event>,
+) -> mpsc::UnboundedReceiver<(Event, Option<MediaTag>)> {
+    let (tx, out) = mpsc::unbounded();
+    tokio::spawn(async move {
+        let mut tracker = MediaTracker::new();
+        while let Some(event) = rx.next().await {
+            let tag = tracker.observe(&event);
+            if tx.unbounded_send((event, tag)).is_err() {
+                return; // receiver dropped
+            }
+        }
+    });
+    out
+}
+
+fn manifest_kind(event: &Event) -> Option<ManifestKind> {
+    let ct = event
+        .content_type
+        .as_deref()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let path = without_query(&event.url).to_ascii_lowercase();
+    if ct.contains("mpegurl") || path.ends_with(".m3u8") {
+        let master = event.body.contains("#EXT-X-STREAM-INF");
+        Some(if master {
+            ManifestKind::HlsMaster
+        } else {
+            ManifestKind::HlsMedia
+        })
+    } else if ct.contains("dash+xml") || path.ends_with(".mpd") {
+        Some(ManifestKind::Dash)
+    } else {
+        None
+    }
+}
+
+fn without_query(url: &str) -> &str {
+    url.split(['?', '#']).next().unwrap_or(url)
+}
+
+fn resolve(base: &Url, reference: &str) -> Option<String> {
+    base.join(reference.trim()).ok().map(String::from)
+}
+
+fn parse_hls_master(base: &Url, body: &str) -> Vec<Variant> {
+    let mut variants = Vec::new();
+    let mut pending: Option<Variant> = None;
+    for line in body.lines().map(str::trim) {
+        if let Some(attrs) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+            let attrs = parse_attributes(attrs);
+            pending = Some(Variant {
+                bandwidth: attrs.get("BANDWIDTH").and_then(|b| b.parse().ok()),
+                resolution: attrs.get("RESOLUTION").cloned(),
+                codecs: attrs.get("CODECS").cloned(),
+                ..Variant::default()
+            });
+        } else if !line.is_empty()
+            && !line.starts_with('#')
+            && let Some(mut variant) = pending.take()
+            && let Some(uri) = resolve(base, line)
+        {
+            variant.uri = uri;
+            variants.push(variant);
+        }
+    }
+    variants
+}
+
+fn parse_hls_segments(base: &Url, body: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    for line in body.lines().map(str::trim) {
+        if let Some(attrs) = line.strip_prefix("#EXT-X-MAP:") {
+            // The initialization segment.
+            if let Some(uri) = parse_attributes(attrs).get("URI") {
+                segments.extend(resolve(base, uri));
+            }
+        } else if !line.is_empty() && !line.starts_with('#') {
+            segments.extend(resolve(base, line));
+        }
+    }
+    segments
+}
+
+// `KEY=value,KEY="quoted, value"` attribute lists.
+fn parse_attributes(attrs: &str) -> HashMap<String, String> {
+    let mut out = HashMap::new();
+    let mut rest = attrs;
+    while let Some((key, tail)) = rest.split_once('=') {
+        let (value, tail) = match tail.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                let after = quoted.get(end + 1..).unwrap_or("");
+                (&quoted[..end], after.strip_prefix(',').unwrap_or(after))
+            }
+            None => tail.split_once(',').unwrap_or((tail, "")),
+        };
+        out.insert(key.trim().to_string(), value.to_string());
+        rest = tail;
+    }
+    out
+}
+
+struct Representation {
+    variant: Variant,
+    segments: Vec<String>,
+    template: Option<Regex>,
+}
+
+// Scan an MPD for representations and how their segments are addressed.
+fn parse_dash(mpd_url: &Url, body: &str) -> Vec<Representation> {
+    let base = tag_text_before(body, "BaseURL", "<AdaptationSet")
+        .and_then(|b| mpd_url.join(b).ok())
+        .unwrap_or_else(|| mpd_url.clone());
+    let mut out = Vec::new();
+    for set in ADAPTATION_SET.find_iter(body).map(|m| m.as_str()) {
+        let set_base = tag_text_before(set, "BaseURL", "<Representation")
+            .and_then(|b| base.join(b).ok())
+            .unwrap_or_else(|| base.clone());
+        let set_template = set
+            .find("<Representation")
+            .and_then(|i| tag_attrs(&set[..i], "SegmentTemplate"));
+
+        for caps in REPRESENTATION.captures_iter(set) {
+            let attrs = caps.get(1).map_or("", |m| m.as_str());
+            let inner = caps.get(2).map_or("", |m| m.as_str());
+            let id = xml_attr(attrs, "id");
+            let bandwidth = xml_attr(attrs, "bandwidth").and_then(|b| b.parse().ok());
+            let resolution = xml_attr(attrs, "width")
+                .zip(xml_attr(attrs, "height"))
+                .map(|(w, h)| format!("{w}x{h}"));
+            let variant = Variant {
+                uri: String::new(),
+                id: id.clone(),
+                bandwidth,
+                resolution,
+                codecs: xml_attr(attrs, "codecs"),
+            };
+
+            let rep_base = tag_text(inner, "BaseURL")
+                .and_then(|b| set_base.join(b).ok())
+                .unwrap_or_else(|| set_base.clone());
+            let mut segments: Vec<String> = SEGMENT_URL
+                .captures_iter(inner)
+                .filter_map(|c| resolve(&rep_base, &c[1]))
+                .collect();
+            if tag_text(inner, "BaseURL").is_some() && segments.is_empty() {
+                // A single-segment representation addressed by its BaseURL alone.
+                segments.push(rep_base.to_string());
+            }
+
+            let template_attrs = tag_attrs(inner, "SegmentTemplate").or(set_template.clone());
+            let template = template_attrs.and_then(|t| {
+                let media = xml_attr(&t, "media")?;
+                let init = xml_attr(&t, "initialization");
+                let patterns: Vec<String> = std::iter::once(media)
+                    .chain(init)
+                    .filter_map(|m| template_pattern(&rep_base, &m, id.as_deref(), bandwidth))
+                    .collect();
+                Regex::new(&format!("^(?:{})$", patterns.join("|"))).ok()
+            });
+
+            out.push(Representation {
+                variant,
+                segments,
+                template,
+            });
+        }
+    }
+    out
+}
+
+// Turn a `SegmentTemplate` URL into a regex over absolute segment URLs.
+fn template_pattern(
+    base: &Url,
+    template: &str,
+    id: Option<&str>,
+    bandwidth: Option<u64>,
+) -> Option<String> {
+    let mut expanded = template.replace("$$", "$");
+    if let Some(id) = id {
+        expanded = expanded.replace("$RepresentationID$", id);
+    }
+    if let Some(bandwidth) = bandwidth {
+        expanded = expanded.replace("$Bandwidth$", &bandwidth.to_string());
+    }
+    // Placeholders such as `$Number$`, `$Number%05d$` and `$Time$` become a marker that
+    // survives URL resolution and escaping.
+    let marked = PLACEHOLDER.replace_all(&expanded, "SEGMENTNUMBER");
+    let absolute = without_query(base.join(&marked).ok()?.as_str()).to_string();
+    Some(regex::escape(&absolute).replace("SEGMENTNUMBER", r"\d+"))
+}
+
+fn xml_attr(attrs: &str, name: &str) -> Option<String> {
+    ATTRIBUTE
+        .captures_iter(attrs)
+        .find(|c| &c[1] == name)
+        .map(|c| c[2].to_string())
+}
+
+fn tag_attrs(xml: &str, tag: &str) -> Option<String> {
+    START_TAG
+        .captures_iter(xml)
+        .find(|c| &c[1] == tag)
+        .map(|c| c[2].to_string())
+}
+
+fn tag_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{tag}>"))? + tag.len() + 2;
+    let end = xml[start..].find(&format!("</{tag}>"))? + start;
+    Some(xml[start..end].trim())
+}
+
+// `tag_text`, but only if the tag appears before `boundary`.
+fn tag_text_before<'a>(xml: &'a str, tag: &str, boundary: &str) -> Option<&'a str> {
+    let limit = xml.find(boundary).unwrap_or(xml.len());
+    tag_text(&xml[..limit], tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(url: &str, content_type: &str, body: &str) -> Event {
+        Event::builder()
+            .url(url)
+            .content_type(content_type)
+            .body(body)
+            .build()
+    }
+
+    const MASTER: &str = "#EXTM3U\n\
+        #EXT-X-STREAM-INF:BANDWIDTH=1280000,RESOLUTION=1280x720,CODECS=\"avc1.4d401f,mp4a.40.2\"\n\
+        hd/index.m3u8\n\
+        #EXT-X-STREAM-INF:BANDWIDTH=640000\n\
+        sd/index.m3u8?token=1\n";
+
+    #[test]
+    fn hls_master_playlist_lists_variants() {
+        let mut tracker = MediaTracker::new();
+        let tag = tracker.observe(&response(
+            "https://cdn.example/live/master.m3u8",
+            "application/vnd.apple.mpegurl",
+            MASTER,
+        ));
+        let Some(MediaTag::Manifest { kind, variants, .. }) = tag else {
+            panic!("not a manifest: {tag:?}");
+        };
+        assert_eq!(kind, ManifestKind::HlsMaster);
+        assert_eq!(variants.len(), 2);
+        assert_eq!(variants[0].uri, "https://cdn.example/live/hd/index.m3u8");
+        assert_eq!(variants[0].bandwidth, Some(1_280_000));
+        assert_eq!(variants[0].resolution.as_deref(), Some("1280x720"));
+        assert_eq!(variants[0].codecs.as_deref(), Some("avc1.4d401f,mp4a.40.2"));
+        assert_eq!(variants[1].bandwidth, Some(640_000));
+    }
+
+    #[test]
+    fn hls_segments_are_tagged_with_their_variant() {
+        let mut tracker = MediaTracker::new();
+        tracker.observe(&response(
+            "https://cdn.example/live/master.m3u8",
+            "application/vnd.apple.mpegurl",
+            MASTER,
+        ));
+        let tag = tracker.observe(&response(
+            "https://cdn.example/live/hd/index.m3u8",
+            "application/vnd.apple.mpegurl",
+            "#EXTM3U\n#EXT-X-MAP:URI=\"init.mp4\"\n#EXTINF:4.0,\nseg1.ts\n#EXTINF:4.0,\nseg2.ts\n",
+        ));
+        assert!(matches!(tag, Some(MediaTag::Manifest { segments: 3, .. })));
+
+        let tag = tracker.observe(&response(
+            "https://cdn.example/live/hd/seg2.ts?sig=abc",
+            "video/mp2t",
+            "",
+        ));
+        let Some(MediaTag::Segment {
+            manifest_url,
+            variant,
+        }) = tag
+        else {
+            panic!("not a segment: {tag:?}");
+        };
+        assert_eq!(manifest_url, "https://cdn.example/live/hd/index.m3u8");
+        assert_eq!(variant.unwrap().bandwidth, Some(1_280_000));
+    }
+
+    #[test]
+    fn refetched_playlists_replace_their_segments() {
+        let mut tracker = MediaTracker::new();
+        let playlist = |body| {
+            response(
+                "https://cdn.example/live.m3u8?_=1",
+                "application/x-mpegurl",
+                body,
+            )
+        };
+        tracker.observe(&playlist("#EXTM3U\nseg1.ts\nseg2.ts\n"));
+        tracker.observe(&playlist("#EXTM3U\nseg2.ts\nseg3.ts\n"));
+        assert_eq!(tracker.segments.len(), 2);
+        assert!(
+            tracker
+                .observe(&response("https://cdn.example/seg1.ts", "video/mp2t", ""))
+                .is_none()
+        );
+        assert!(
+            tracker
+                .observe(&response("https://cdn.example/seg3.ts", "video/mp2t", ""))
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn dash_templates_match_numbered_segments() {
+        let mpd = r#"<MPD>
+  <AdaptationSet mimeType="video/mp4">
+    <SegmentTemplate initialization="$RepresentationID$/init.mp4" media="$RepresentationID$/seg-$Number%05d$.m4s"/>
+    <Representation id="v720" bandwidth="3000000" width="1280" height="720" codecs="avc1.64001f"/>
+    <Representation id="v360" bandwidth="800000" width="640" height="360"/>
+  </AdaptationSet>
+</MPD>"#;
+        let mut tracker = MediaTracker::new();
+        let tag = tracker.observe(&response(
+            "https://cdn.example/vod/stream.mpd",
+            "application/dash+xml",
+            mpd,
+        ));
+        let Some(MediaTag::Manifest { kind, variants, .. }) = tag else {
+            panic!("not a manifest: {tag:?}");
+        };
+        assert_eq!(kind, ManifestKind::Dash);
+        assert_eq!(variants[0].id.as_deref(), Some("v720"));
+        assert_eq!(variants[0].resolution.as_deref(), Some("1280x720"));
+        assert_eq!(variants[1].bandwidth, Some(800_000));
+
+        let tag = tracker.observe(&response(
+            "https://cdn.example/vod/v360/seg-00042.m4s",
+            "video/mp4",
+            "",
+        ));
+        let Some(MediaTag::Segment { variant, .. }) = tag else {
+            panic!("not a segment: {tag:?}");
+        };
+        assert_eq!(variant.unwrap().id.as_deref(), Some("v360"));
+        assert!(
+            tracker
+                .observe(&response(
+                    "https://cdn.example/vod/v360/other.m4s",
+                    "video/mp4",
+                    ""
+                ))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn dash_segment_lists_resolve_against_base_urls() {
+        let mpd = r#"<MPD><BaseURL>media/</BaseURL>
+  <AdaptationSet>
+    <Representation id="a" bandwidth="128000">
+      <SegmentList>
+        <Initialization sourceURL="a-init.mp4"/>
+        <SegmentURL media="a-1.m4s"/>
+      </SegmentList>
+    </Representation>
+  </AdaptationSet>
+</MPD>"#;
+        let mut tracker = MediaTracker::new();
+        let tag = tracker.observe(&response(
+            "https://cdn.example/x/manifest.mpd",
+            "application/dash+xml",
+            mpd,
+        ));
+        assert!(matches!(tag, Some(MediaTag::Manifest { segments: 2, .. })));
+        let tag = tracker.observe(&response(
+            "https://cdn.example/x/media/a-1.m4s",
+            "audio/mp4",
+            "",
+        ));
+        assert!(matches!(tag, Some(MediaTag::Segment { .. })));
+    }
+}
@@ -0,0 +1,154 @@
+//! Prometheus metrics for captured traffic.
+//!
+//! Share a [`Metrics`] between the capture pipeline and your HTTP endpoint (it is `Sync`), feed
+//! it with [`Metrics::observe`] or [`Metrics::instrument`], and serve [`Metrics::render`] as
+//! the response body of a `/metrics` route.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+
+use crate::Event;
+
+/// Upper bounds, in seconds, of the response time histogram buckets.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    events: AtomicU64,
+    bytes: AtomicU64,
+    body_failures: AtomicU64,
+    statuses: Mutex<BTreeMap<u16, u64>>,
+    // Cumulative counts per bucket, plus `+Inf` as the last slot.
+    duration_buckets: [AtomicU64; BUCKETS.len() + 1],
+    duration_count: AtomicU64,
+    // Sum of observed durations in microseconds, to keep it an integer counter.
+    duration_sum_micros: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count one captured event.
+    pub fn observe(&self, event: &Event) {
+        self.events.fetch_add(1, Ordering::Relaxed);
+        self.bytes
+            .fetch_add(event.body.len() as u64, Ordering::Relaxed);
+        if let Some(status) = event.status {
+            let mut statuses = self.statuses.lock().unwrap_or_else(|e| e.into_inner());
+            *statuses.entry(status).or_default() += 1;
+        }
+        if let Some(ms) = event.duration_ms {
+            let secs = ms / 1000.0;
+            for (bound, bucket) in BUCKETS.iter().zip(&self.duration_buckets) {
+                if secs <= *bound {
+                    bucket.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            self.duration_buckets[BUCKETS.len()].fetch_add(1, Ordering::Relaxed);
+            self.duration_count.fetch_add(1, Ordering::Relaxed);
+            self.duration_sum_micros
+                .fetch_add((ms * 1000.0) as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Count a response whose body could not be read.
+    pub fn record_body_failure(&self) {
+        self.body_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Pass every event from `rx` through [`observe`](Self::observe) on its way to the returned receiver.
+    pub fn instrument(
+        self: &Arc<Self>,
+        mut rx: mpsc::UnboundedReceiver<Event>,
+    ) -> mpsc::UnboundedReceiver<Event> {
+        let (tx, out) = mpsc::unbounded();
+        let metrics = Arc::clone(self);
+        tokio::spawn(async move {
+            while let Some(event) = rx.next().await {
+                metrics.observe(&event);
+                if tx.unbounded_send(event).is_err() {
+                    return; // receiver dropped
+                }
+            }
+        });
+        out
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let counter = |out: &mut String, name: &str, help: &str, value: u64| {
+            let _ = writeln!(
+                out,
+                "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}"
+            );
+        };
+        counter(
+            &mut out,
+            "event_stream_events_total",
+            "Captured events.",
+            self.events.load(Ordering::Relaxed),
+        );
+        counter(
+            &mut out,
+            "event_stream_body_bytes_total",
+            "Bytes of captured response bodies.",
+            self.bytes.load(Ordering::Relaxed),
+        );
+        counter(
+            &mut out,
+            "event_stream_body_failures_total",
+            "Responses whose body could not be read.",
+            self.body_failures.load(Ordering::Relaxed),
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP event_stream_responses_total Captured responses by HTTP status.\n# TYPE event_stream_responses_total counter"
+        );
+        let statuses = self.statuses.lock().unwrap_or_else(|e| e.into_inner());
+        for (status, count) in statuses.iter() {
+            let _ = writeln!(
+                out,
+                "event_stream_responses_total{{status=\"{status}\"}} {count}"
+            );
+        }
+        drop(statuses);
+
+        let name = "event_stream_response_time_seconds";
+        let _ = writeln!(
+            out,
+            "# HELP {name} Time from request start until the body was read.\n# TYPE {name} histogram"
+        );
+        for (bound, bucket) in BUCKETS.iter().zip(&self.duration_buckets) {
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{le=\"{bound}\"}} {}",
+                bucket.load(Ordering::Relaxed)
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{le=\"+Inf\"}} {}",
+            self.duration_buckets[BUCKETS.len()].load(Ordering::Relaxed)
+        );
+        let sum = self.duration_sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{name}_sum {sum}");
+        let _ = writeln!(
+            out,
+            "{name}_count {}",
+            self.duration_count.load(Ordering::Relaxed)
+        );
+        out
+    }
+}
@@ -0,0 +1,175 @@
+//! Serve recorded responses to the page instead of the network.
+//!
+//! Record a session once with [`fixture::record`](crate::fixture::record), or save a HAR file
+//! from DevTools, then load it into [`Mocks`] and set [`EventStreamConfig::mocks`](crate::EventStreamConfig::mocks).
+//! Fetch and XHR requests matching a recorded event by method and URL are fulfilled through the
+//! CDP Fetch domain with the recorded status, headers and body; the rest go to the network or
+//! fail, see [`MockMiss`]. Headers the recording's redaction masked, such as `Set-Cookie` by
+//! default, are left out of the response, see [`Event::redacted_headers`].
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chromiumoxide::Binary;
+use chromiumoxide::cdp::browser_protocol::fetch::{FulfillRequestParams, HeaderEntry, RequestId};
+
+use crate::har::Har;
+use crate::{Error, ErrorReason, Event, fixture};
+
+/// What to do with a fetch or XHR request that has no recording.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum MockMiss {
+    /// Send it to the network as usual.
+    #[default]
+    Network,
+    /// Fail it with a network error, keeping the test hermetic.
+    Fail(ErrorReason),
+}
+
+/// Recorded responses to serve, see [`EventStreamConfig::mocks`](crate::EventStreamConfig::mocks).
+#[derive(Clone, Debug, Default)]
+pub struct Mocks {
+    responses: Vec<Event>,
+    on_miss: MockMiss,
+}
+
+impl Mocks {
+    /// Serve `events`. Events without a status, i.e. failed requests, are skipped.
+    pub fn new(events: impl IntoIterator<Item = Event>) -> Self {
+        Self {
+            responses: events
+                .into_iter()
+                .filter(|event| event.status.is_some())
+                .collect(),
+            on_miss: MockMiss::default(),
+        }
+    }
+
+    /// Serve the events of a fixture file.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(Self::new(fixture::load(path).await?))
+    }
+
+    /// Serve the entries of a HAR log, e.g. one saved from DevTools.
+    pub fn from_har(har: &Har) -> Self {
+        Self::new(har.to_events())
+    }
+
+    /// Serve the entries of a HAR file.
+    pub async fn load_har(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(Self::from_har(&Har::read_from_file(path).await?))
+    }
+
+    pub fn on_miss(mut self, on_miss: MockMiss) -> Self {
+        self.on_miss = on_miss;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.responses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.responses.is_empty()
+    }
+}
+
+// The mocks of a running stream, with how often each request was answered.
+pub(crate) struct MockServer {
+    mocks: Mocks,
+    served: Mutex<HashMap<String, usize>>,
+}
+
+impl MockServer {
+    pub(crate) fn new(mocks: Mocks) -> Self {
+        Self {
+            mocks,
+            served: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn on_miss(&self) -> &MockMiss {
+        &self.mocks.on_miss
+    }
+
+    /// The recording to answer `method url` with. A request made again gets the next recording
+    /// of it in capture order, and the last one once they run out.
+    pub(crate) fn lookup(&self, method: &str, url: &str) -> Option<&Event> {
+        let key = request_key(method, url);
+        let recorded: Vec<_> = self
+            .mocks
+            .responses
+            .iter()
+            .filter(|event| {
+                request_key(event.method.as_deref().unwrap_or("GET"), &event.url) == key
+            })
+            .collect();
+        let last = recorded.len().checked_sub(1)?;
+        let mut served = self.served.lock().unwrap_or_else(|e| e.into_inner());
+        let count = served.entry(key).or_default();
+        let event = recorded[(*count).min(last)];
+        *count += 1;
+        Some(event)
+    }
+}
+
+// Requests match on method and URL, ignoring the fragment the browser never sends.
+fn request_key(method: &str, url: &str) -> String {
+    let url = url.split_once('#').map_or(url, |(url, _)| url);
+    format!("{} {url}", method.to_ascii_uppercase())
+}
+
+/// Answer the paused request `request_id` with the response recorded in `event`.
+pub(crate) fn fulfill_params(request_id: RequestId, event: &Event) -> FulfillRequestParams {
+    let mut params = FulfillRequestParams::new(request_id, i64::from(event.status.unwrap_or(200)));
+    // Captured bodies are decoded, whatever the server compressed them with. HAR files from
+    // HTTP/2 sessions also list pseudo-headers, which cannot be fulfilled. A redacted
+    // `Set-Cookie` would set a cookie named after the mask.
+    params.response_headers = Some(
+        event
+            .response_headers
+            .iter()
+            .filter(|h| {
+                !h.name.starts_with(':')
+                    && !h.name.eq_ignore_ascii_case("content-length")
+                    && !h.name.eq_ignore_ascii_case("content-encoding")
+                    && !event.is_redacted(h)
+            })
+            .map(|h| HeaderEntry::new(h.name.clone(), h.value.clone()))
+            .collect(),
+    );
+    params.body = Some(Binary::from(if event.base64_encoded {
+        event.body.clone()
+    } else {
+        STANDARD.encode(&event.body)
+    }));
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Redaction;
+
+    #[test]
+    fn fulfill_drops_redacted_headers() {
+        let mut event = Event::builder()
+            .url("https://example.com/")
+            .response_header("set-cookie", "sid=abc")
+            .response_header("content-type", "text/plain")
+            .body("hi")
+            .build();
+        Redaction::default().mask("***").apply(&mut event);
+        let params = fulfill_params(RequestId::new("1"), &event);
+        let names: Vec<_> = params
+            .response_headers
+            .unwrap()
+            .into_iter()
+            .map(|h| h.name)
+            .collect();
+        assert_eq!(names, ["content-type"]);
+    }
+}
@@ -0,0 +1,194 @@
+//! `multipart/form-data` request bodies.
+//!
+//! The page records `FormData` bodies part by part in [`Event::request_parts`], since it never
+//! sees them serialized; bodies sent as a pre-built string are parsed from
+//! [`Event::request_body`] using the boundary in the request `Content-Type`. Either way
+//! [`Event::multipart_parts`] returns the same [`Part`]s.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Event;
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Part {
+    /// The `name` of the form field.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Set for file uploads.
+    #[serde(default)]
+    pub filename: Option<String>,
+    #[serde(rename = "contentType", default)]
+    pub content_type: Option<String>,
+    /// Size of the part body in bytes.
+    #[serde(default)]
+    pub size: u64,
+    /// The part body, when it is text no longer than the limit it was captured with. File
+    /// contents are never recorded by the page.
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+impl Part {
+    pub fn is_file(&self) -> bool {
+        self.filename.is_some()
+    }
+}
+
+impl Event {
+    /// Whether the request body is `multipart/form-data`, by its `Content-Type` or because the
+    /// page recorded `FormData` parts.
+    pub fn is_multipart(&self) -> bool {
+        self.request_parts.is_some()
+            || self.request_header("content-type").is_some_and(|ct| {
+                ct.trim_start()
+                    .to_ascii_lowercase()
+                    .starts_with("multipart/form-data")
+            })
+    }
+
+    /// The parts of a multipart request body, keeping part bodies of at most `body_limit` bytes.
+    /// `None` if the request is not multipart or its body was not recorded.
+    pub fn multipart_parts(&self, body_limit: usize) -> Option<Vec<Part>> {
+        if let Some(parts) = &self.request_parts {
+            let mut parts = parts.clone();
+            for part in &mut parts {
+                part.body = part.body.take().filter(|b| b.len() <= body_limit);
+            }
+            return Some(parts);
+        }
+        let boundary = boundary(self.request_header("content-type")?)?;
+        Some(parse(self.request_body.as_deref()?, &boundary, body_limit))
+    }
+}
+
+/// The `boundary` parameter of a `multipart/*` content type.
+pub fn boundary(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"').to_string())
+            .filter(|b| !b.is_empty())
+    })
+}
+
+/// Split a multipart body on `boundary`, keeping part bodies of at most `body_limit` bytes.
+/// Tolerates bare `\n` line endings and a missing closing delimiter.
+pub fn parse(body: &str, boundary: &str, body_limit: usize) -> Vec<Part> {
+    let delimiter = format!("--{boundary}");
+    let mut sections = body.split(delimiter.as_str());
+    sections.next(); // preamble
+    sections
+        .take_while(|section| !section.starts_with("--"))
+        .filter_map(|section| parse_part(section, body_limit))
+        .collect()
+}
+
+fn parse_part(section: &str, body_limit: usize) -> Option<Part> {
+    let section = strip_newline_start(section);
+    let (head, content) = section
+        .split_once("\r\n\r\n")
+        .or_else(|| section.split_once("\n\n"))?;
+    // The line break before the next delimiter belongs to the delimiter.
+    let content = content
+        .strip_suffix("\r\n")
+        .or_else(|| content.strip_suffix('\n'))
+        .unwrap_or(content);
+
+    let mut part = Part {
+        name: None,
+        filename: None,
+        content_type: None,
+        size: content.len() as u64,
+        body: (content.len() <= body_limit).then(|| content.to_string()),
+    };
+    for line in head.lines() {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-disposition" => {
+                part.name = disposition_param(value, "name");
+                part.filename = disposition_param(value, "filename");
+            }
+            "content-type" => part.content_type = Some(value.trim().to_string()),
+            _ => {}
+        }
+    }
+    Some(part)
+}
+
+fn strip_newline_start(section: &str) -> &str {
+    section
+        .strip_prefix("\r\n")
+        .or_else(|| section.strip_prefix('\n'))
+        .unwrap_or(section)
+}
+
+fn disposition_param(value: &str, key: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|param| {
+        let (k, v) = param.split_once('=')?;
+        k.trim()
+            .eq_ignore_ascii_case(key)
+            .then(|| v.trim().trim_matches('"').to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &str = "preamble\r\n--XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+        Hello\r\n--XyZ\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+        Content-Type: text/plain\r\n\r\n\
+        0123456789\r\n--XyZ--\r\nepilogue";
+
+    #[test]
+    fn parse_splits_on_the_boundary() {
+        let parts = parse(BODY, "XyZ", 5);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name.as_deref(), Some("title"));
+        assert_eq!(parts[0].body.as_deref(), Some("Hello"));
+        assert!(!parts[0].is_file());
+        assert_eq!(parts[1].filename.as_deref(), Some("a.txt"));
+        assert_eq!(parts[1].content_type.as_deref(), Some("text/plain"));
+        assert_eq!(parts[1].size, 10);
+        assert_eq!(parts[1].body, None);
+    }
+
+    #[test]
+    fn parse_tolerates_bare_newlines_and_no_closing_delimiter() {
+        let parts = parse(
+            "--b\nContent-Disposition: form-data; name=x\n\n1\n--b\nContent-Type: text/plain\n\n2",
+            "b",
+            usize::MAX,
+        );
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name.as_deref(), Some("x"));
+        assert_eq!(parts[0].body.as_deref(), Some("1"));
+        assert_eq!(parts[1].body.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn boundary_reads_quoted_values() {
+        assert_eq!(
+            boundary("multipart/form-data; Boundary=\"----abc\"").as_deref(),
+            Some("----abc")
+        );
+        assert_eq!(boundary("multipart/form-data; boundary="), None);
+        assert_eq!(boundary("multipart/form-data"), None);
+    }
+
+    #[test]
+    fn multipart_parts_reads_the_request_body() {
+        let event = Event::builder()
+            .request_header("Content-Type", "multipart/form-data; boundary=XyZ")
+            .request_body(BODY)
+            .build();
+        assert!(event.is_multipart());
+        assert_eq!(event.multipart_parts(usize::MAX).unwrap().len(), 2);
+        assert_eq!(Event::builder().build().multipart_parts(0), None);
+    }
+}
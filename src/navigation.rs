@@ -0,0 +1,139 @@
+//! Grouping events by page load.
+//!
+//! The hooks number the navigations of each document: the load itself is navigation 0, and
+//! every same-document navigation a single-page app makes with `history.pushState`, back and
+//! forward, or a fragment change starts the next one. The stream turns these into a
+//! [`Event::navigation_id`] that keeps counting across documents, so a session can be sliced
+//! per page with [`page_loads`] whether the site reloads or routes in place. New documents are
+//! only hooked, and so only counted, on streams started with
+//! [`start_paused`](crate::EventStreamConfig::start_paused).
+
+use crate::Event;
+
+/// The events of one navigation, see [`page_loads`].
+#[derive(Clone, Debug)]
+pub struct PageLoad {
+    /// `None` for events without one, e.g. built by hand.
+    pub navigation_id: Option<u64>,
+    /// The [`Event::page_url`] of the first event.
+    pub page_url: Option<String>,
+    pub events: Vec<Event>,
+}
+
+/// Group `events` by [`Event::navigation_id`], in navigation order; events keep their order
+/// within a group. Events without a navigation id come first.
+pub fn page_loads(events: impl IntoIterator<Item = Event>) -> Vec<PageLoad> {
+    let mut loads: Vec<PageLoad> = Vec::new();
+    for event in events {
+        match loads
+            .iter_mut()
+            .find(|load| load.navigation_id == event.navigation_id)
+        {
+            Some(load) => load.events.push(event),
+            None => loads.push(PageLoad {
+                navigation_id: event.navigation_id,
+                page_url: event.page_url.clone(),
+                events: vec![event],
+            }),
+        }
+    }
+    loads.sort_by_key(|load| load.navigation_id);
+    loads
+}
+
+// Numbers navigations across documents. Each document counts its own from 0, so a new document
+// starts past every navigation id handed out so far.
+#[derive(Debug, Default)]
+pub(crate) struct Navigations {
+    document: Option<u64>,
+    base: u64,
+    next: u64,
+}
+
+impl Navigations {
+    pub(crate) fn assign(&mut self, event: &mut Event) {
+        let Some(navigation) = event.navigation_id else {
+            return;
+        };
+        if let Some(document) = event.document_started_at.map(f64::to_bits) {
+            if self.document.is_some_and(|current| current != document) {
+                self.base = self.next;
+            }
+            self.document = Some(document);
+        }
+        let id = self.base + navigation;
+        self.next = self.next.max(id + 1);
+        event.navigation_id = Some(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(url: &str, navigation_id: Option<u64>) -> Event {
+        let mut builder = Event::builder().url(url).page_url(format!("{url}#page"));
+        if let Some(id) = navigation_id {
+            builder = builder.navigation_id(id);
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn page_loads_group_by_navigation_in_order() {
+        let loads = page_loads([
+            event("https://example.com/b1", Some(1)),
+            event("https://example.com/a1", Some(0)),
+            event("https://example.com/b2", Some(1)),
+            event("https://example.com/manual", None),
+            event("https://example.com/a2", Some(0)),
+        ]);
+        let summary: Vec<_> = loads
+            .iter()
+            .map(|load| {
+                let urls: Vec<_> = load.events.iter().map(|e| e.url.as_str()).collect();
+                (load.navigation_id, urls)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (None, vec!["https://example.com/manual"]),
+                (
+                    Some(0),
+                    vec!["https://example.com/a1", "https://example.com/a2"]
+                ),
+                (
+                    Some(1),
+                    vec!["https://example.com/b1", "https://example.com/b2"]
+                ),
+            ]
+        );
+        assert_eq!(
+            loads[2].page_url.as_deref(),
+            Some("https://example.com/b1#page")
+        );
+        assert!(page_loads(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn navigations_keep_counting_across_documents() {
+        let mut navigations = Navigations::default();
+        let mut assign = |document: Option<f64>, navigation: Option<u64>| {
+            let mut event = event("https://example.com/", navigation);
+            event.document_started_at = document;
+            navigations.assign(&mut event);
+            event.navigation_id
+        };
+        assert_eq!(assign(Some(1.0), Some(0)), Some(0));
+        assert_eq!(assign(Some(1.0), Some(2)), Some(2));
+        // Without a document, the current one is assumed.
+        assert_eq!(assign(None, Some(1)), Some(1));
+        // A reload starts past everything handed out so far.
+        assert_eq!(assign(Some(2.0), Some(0)), Some(3));
+        assert_eq!(assign(Some(2.0), Some(1)), Some(4));
+        // Events without a navigation id are left alone.
+        assert_eq!(assign(Some(3.0), None), None);
+        assert_eq!(assign(Some(2.0), Some(2)), Some(5));
+    }
+}
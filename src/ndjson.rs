@@ -0,0 +1,73 @@
+//! Splitting NDJSON / JSON-lines responses into per-line events.
+//!
+//! With [`split_ndjson_lines`](crate::EventStreamConfig::split_ndjson_lines) the page already
+//! delivers one event per line as it arrives; for bodies captured whole (XHR, or the option
+//! off), [`split`] and [`split_ndjson`] do the same after the fact.
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use serde::de::DeserializeOwned;
+
+use crate::{Error, Event};
+
+const CONTENT_TYPES: [&str; 4] = [
+    "application/x-ndjson",
+    "application/ndjson",
+    "application/jsonl",
+    "application/x-jsonlines",
+];
+
+/// Whether `event` is an NDJSON / JSON-lines response.
+pub fn is_ndjson(event: &Event) -> bool {
+    event.content_type.as_deref().is_some_and(|ct| {
+        let ct = ct.trim_start().to_ascii_lowercase();
+        CONTENT_TYPES.iter().any(|t| ct.starts_with(t))
+    })
+}
+
+/// The non-blank lines of `event`'s body.
+pub fn lines(event: &Event) -> impl Iterator<Item = &str> {
+    non_blank(&event.body)
+}
+
+fn non_blank(body: &str) -> impl Iterator<Item = &str> {
+    body.lines().filter(|line| !line.trim().is_empty())
+}
+
+/// Deserialize each line of `event`'s body.
+pub fn parse_lines<T: DeserializeOwned>(event: &Event) -> Vec<Result<T, Error>> {
+    lines(event)
+        .map(|line| serde_json::from_str(line).map_err(Error::ParseJson))
+        .collect()
+}
+
+/// One event per line of `event`'s body, each a copy of `event` with `body` set to the line.
+/// Events that are not NDJSON are returned unchanged.
+pub fn split(mut event: Event) -> Vec<Event> {
+    if !is_ndjson(&event) {
+        return vec![event];
+    }
+    // Take the body out so the copies do not each clone the whole aggregate.
+    let body = std::mem::take(&mut event.body);
+    non_blank(&body)
+        .map(|line| Event {
+            body: line.to_string(),
+            ..event.clone()
+        })
+        .collect()
+}
+
+/// Forward every event from `rx`, with NDJSON responses split into one event per line.
+pub fn split_ndjson(mut rx: mpsc::UnboundedReceiver<Event>) -> mpsc::UnboundedReceiver<Event> {
+    let (tx, out) = mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some(event) = rx.next().await {
+            for line in split(event) {
+                if tx.unbounded_send(line).is_err() {
+                    return; // receiver dropped
+                }
+            }
+        }
+    });
+    out
+}
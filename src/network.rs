@@ -0,0 +1,67 @@
+// `emulateNetworkConditions` is deprecated in favour of the experimental
+// `emulateNetworkConditionsByRule`, which older browsers lack.
+#![allow(deprecated)]
+
+use chromiumoxide::cdp::browser_protocol::network::EmulateNetworkConditionsParams;
+use serde::Serialize;
+
+/// Throttling applied with [`EventStreamHandle::emulate_network`](crate::EventStreamHandle::emulate_network).
+///
+/// The presets match the ones in Chrome DevTools. Throughputs are in bytes per second, and `-1`
+/// leaves that direction unthrottled.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NetworkConditions {
+    /// Recorded on events captured under these conditions, see
+    /// [`Event::network_condition`](crate::Event::network_condition).
+    pub label: String,
+    pub offline: bool,
+    pub latency_ms: f64,
+    pub download_bytes_per_sec: f64,
+    pub upload_bytes_per_sec: f64,
+}
+
+impl NetworkConditions {
+    pub fn slow_3g() -> Self {
+        Self::custom("Slow 3G", 2000.0, 50_000.0, 50_000.0)
+    }
+
+    pub fn fast_3g() -> Self {
+        Self::custom("Fast 3G", 562.5, 180_000.0, 84_375.0)
+    }
+
+    pub fn offline() -> Self {
+        Self {
+            offline: true,
+            ..Self::custom("Offline", 0.0, -1.0, -1.0)
+        }
+    }
+
+    pub fn custom(
+        label: impl Into<String>,
+        latency_ms: f64,
+        download_bytes_per_sec: f64,
+        upload_bytes_per_sec: f64,
+    ) -> Self {
+        Self {
+            label: label.into(),
+            offline: false,
+            latency_ms,
+            download_bytes_per_sec,
+            upload_bytes_per_sec,
+        }
+    }
+
+    pub(crate) fn params(&self) -> EmulateNetworkConditionsParams {
+        EmulateNetworkConditionsParams::new(
+            self.offline,
+            self.latency_ms,
+            self.download_bytes_per_sec,
+            self.upload_bytes_per_sec,
+        )
+    }
+
+    // What the browser does without emulation.
+    pub(crate) fn unthrottled_params() -> EmulateNetworkConditionsParams {
+        EmulateNetworkConditionsParams::new(false, 0.0, -1.0, -1.0)
+    }
+}
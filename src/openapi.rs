@@ -0,0 +1,343 @@
+//! Draft OpenAPI 3.0 inference from captured JSON traffic.
+//!
+//! The result is a starting point for documentation, not a contract: path parameters are
+//! guessed from numeric, UUID and long hex segments, and JSON schemas are the union of every
+//! observed payload. Fields whose types disagree between samples are left untyped.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::{Map, Value, json};
+
+use crate::Event;
+
+#[derive(Default)]
+struct Operation {
+    query: BTreeSet<String>,
+    request_schema: Option<Value>,
+    // status -> (content-type, merged schema of JSON bodies)
+    responses: BTreeMap<u16, (String, Option<Value>)>,
+}
+
+/// Infer an OpenAPI document titled `title` from captured events.
+pub fn infer(title: &str, events: &[Event]) -> Value {
+    let mut servers = BTreeSet::new();
+    let mut paths: BTreeMap<String, BTreeMap<String, Operation>> = BTreeMap::new();
+
+    for event in events {
+        let Ok(url) = url::Url::parse(&event.url) else {
+            continue;
+        };
+        servers.insert(url.origin().ascii_serialization());
+
+        let method = event.method.as_deref().unwrap_or("GET").to_lowercase();
+        let op = paths
+            .entry(template_path(url.path()))
+            .or_default()
+            .entry(method)
+            .or_default();
+
+        op.query
+            .extend(url.query_pairs().map(|(k, _)| k.into_owned()));
+
+        if let Some(body) = &event.request_body
+            && let Ok(value) = serde_json::from_str::<Value>(body)
+        {
+            op.request_schema = Some(merge_into(op.request_schema.take(), &value));
+        }
+
+        let status = event.status.unwrap_or(0);
+        let content_type = event
+            .content_type
+            .as_deref()
+            .and_then(|ct| ct.split(';').next())
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        let entry = op
+            .responses
+            .entry(status)
+            .or_insert_with(|| (content_type, None));
+        if let Ok(value) = serde_json::from_str::<Value>(&event.body) {
+            entry.1 = Some(merge_into(entry.1.take(), &value));
+        }
+    }
+
+    let paths: Map<String, Value> = paths
+        .into_iter()
+        .map(|(path, ops)| {
+            let params = path_parameters(&path);
+            let ops: Map<String, Value> = ops
+                .into_iter()
+                .map(|(method, op)| (method, operation_json(op, &params)))
+                .collect();
+            (path, Value::Object(ops))
+        })
+        .collect();
+
+    json!({
+        "openapi": "3.0.3",
+        "info": { "title": title, "version": "0.0.0" },
+        "servers": servers.into_iter().map(|url| json!({ "url": url })).collect::<Vec<_>>(),
+        "paths": paths,
+    })
+}
+
+fn operation_json(op: Operation, path_params: &[String]) -> Value {
+    let mut parameters: Vec<Value> = path_params
+        .iter()
+        .map(|name| json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } }))
+        .collect();
+    parameters.extend(op.query.into_iter().map(
+        |name| json!({ "name": name, "in": "query", "required": false, "schema": { "type": "string" } }),
+    ));
+
+    let responses: Map<String, Value> = op
+        .responses
+        .into_iter()
+        .map(|(status, (content_type, schema))| {
+            let key = if status == 0 {
+                "default".to_string()
+            } else {
+                status.to_string()
+            };
+            let mut response = json!({ "description": "Observed response" });
+            if !content_type.is_empty() {
+                response["content"] =
+                    json!({ content_type: { "schema": schema.unwrap_or_else(|| json!({})) } });
+            }
+            (key, response)
+        })
+        .collect();
+
+    let mut operation = json!({ "responses": responses });
+    if !parameters.is_empty() {
+        operation["parameters"] = Value::Array(parameters);
+    }
+    if let Some(schema) = op.request_schema {
+        operation["requestBody"] =
+            json!({ "content": { "application/json": { "schema": schema } } });
+    }
+    operation
+}
+
+// Replace identifier-looking segments with `{id}`, `{id2}`, ...
+fn template_path(path: &str) -> String {
+    let mut count = 0;
+    let segments: Vec<String> = path
+        .split('/')
+        .map(|segment| {
+            if looks_like_id(segment) {
+                count += 1;
+                if count == 1 {
+                    "{id}".to_string()
+                } else {
+                    format!("{{id{count}}}")
+                }
+            } else {
+                segment.to_string()
+            }
+        })
+        .collect();
+    segments.join("/")
+}
+
+fn looks_like_id(segment: &str) -> bool {
+    let is_numeric = !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit());
+    let is_uuid = segment.len() == 36
+        && segment.bytes().enumerate().all(|(i, b)| {
+            matches!(i, 8 | 13 | 18 | 23) == (b == b'-') && (b == b'-' || b.is_ascii_hexdigit())
+        });
+    let is_hex = segment.len() >= 16 && segment.bytes().all(|b| b.is_ascii_hexdigit());
+    is_numeric || is_uuid || is_hex
+}
+
+fn path_parameters(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter_map(|s| s.strip_prefix('{').and_then(|s| s.strip_suffix('}')))
+        .map(str::to_string)
+        .collect()
+}
+
+fn merge_into(existing: Option<Value>, sample: &Value) -> Value {
+    let inferred = infer_schema(sample);
+    match existing {
+        Some(existing) => merge_schemas(existing, inferred),
+        None => inferred,
+    }
+}
+
+fn infer_schema(value: &Value) -> Value {
+    match value {
+        Value::Null => json!({ "nullable": true }),
+        Value::Bool(_) => json!({ "type": "boolean" }),
+        Value::Number(n) if n.is_i64() || n.is_u64() => json!({ "type": "integer" }),
+        Value::Number(_) => json!({ "type": "number" }),
+        Value::String(_) => json!({ "type": "string" }),
+        Value::Array(items) => match items.iter().map(infer_schema).reduce(merge_schemas) {
+            Some(items) => json!({ "type": "array", "items": items }),
+            // Empty sample: the item type is unknown until a non-empty array is seen.
+            None => json!({ "type": "array" }),
+        },
+        Value::Object(map) => {
+            let properties: Map<String, Value> = map
+                .iter()
+                .map(|(k, v)| (k.clone(), infer_schema(v)))
+                .collect();
+            json!({ "type": "object", "properties": properties })
+        }
+    }
+}
+
+fn merge_schemas(a: Value, b: Value) -> Value {
+    let type_of = |s: &Value| s.get("type").and_then(Value::as_str).map(str::to_string);
+    let nullable = |s: &Value| s.get("nullable").and_then(Value::as_bool).unwrap_or(false);
+    let is_null_only = |s: &Value| type_of(s).is_none() && nullable(s);
+
+    // A null sample only marks the other side as nullable.
+    if is_null_only(&a) || is_null_only(&b) {
+        let mut other = if is_null_only(&a) { b } else { a };
+        if let Value::Object(map) = &mut other {
+            map.insert("nullable".to_string(), Value::Bool(true));
+        }
+        return other;
+    }
+
+    let nullable = nullable(&a) || nullable(&b);
+    let mut merged = match (type_of(&a).as_deref(), type_of(&b).as_deref()) {
+        (Some("object"), Some("object")) => {
+            let mut properties = a["properties"].as_object().cloned().unwrap_or_default();
+            for (key, schema) in b["properties"].as_object().cloned().unwrap_or_default() {
+                let merged = match properties.remove(&key) {
+                    Some(existing) => merge_schemas(existing, schema),
+                    None => schema,
+                };
+                properties.insert(key, merged);
+            }
+            json!({ "type": "object", "properties": properties })
+        }
+        (Some("array"), Some("array")) => match (a.get("items"), b.get("items")) {
+            (Some(x), Some(y)) => {
+                json!({ "type": "array", "items": merge_schemas(x.clone(), y.clone()) })
+            }
+            (Some(items), None) | (None, Some(items)) => {
+                json!({ "type": "array", "items": items })
+            }
+            (None, None) => json!({ "type": "array" }),
+        },
+        (Some("integer"), Some("number")) | (Some("number"), Some("integer")) => {
+            json!({ "type": "number" })
+        }
+        (Some(x), Some(y)) if x == y => json!({ "type": x }),
+        // Conflicting or unknown types: leave the field untyped.
+        _ => json!({}),
+    };
+    if nullable && let Value::Object(map) = &mut merged {
+        map.insert("nullable".to_string(), Value::Bool(true));
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json_event(method: &str, url: &str, status: u16, body: Value) -> Event {
+        Event::builder()
+            .url(url)
+            .method(method)
+            .status(status)
+            .content_type("application/json; charset=utf-8")
+            .json_body(&body)
+            .build()
+    }
+
+    #[test]
+    fn template_path_replaces_identifier_segments() {
+        assert_eq!(
+            template_path("/users/42/orders/3f2b8a1c-0d4e-4b7a-9c1e-2a6f5b8d9e01"),
+            "/users/{id}/orders/{id2}"
+        );
+        assert_eq!(template_path("/blobs/0123456789abcdef0123"), "/blobs/{id}");
+        // Short hex words and versions stay literal.
+        assert_eq!(template_path("/v2/cafe/beef"), "/v2/cafe/beef");
+        assert_eq!(path_parameters("/users/{id}/orders/{id2}"), ["id", "id2"]);
+    }
+
+    #[test]
+    fn schemas_merge_across_samples() {
+        let merged = [
+            json!({"id": 1, "name": "a", "tags": [], "score": 1, "note": null}),
+            json!({"id": 2, "name": null, "tags": ["x"], "score": 1.5, "note": 3}),
+            json!({"id": "3"}),
+        ]
+        .iter()
+        .fold(None, |schema, sample| Some(merge_into(schema, sample)))
+        .unwrap();
+        let properties = &merged["properties"];
+        assert_eq!(properties["id"], json!({}));
+        assert_eq!(
+            properties["name"],
+            json!({"type": "string", "nullable": true})
+        );
+        assert_eq!(
+            properties["tags"],
+            json!({"type": "array", "items": {"type": "string"}})
+        );
+        assert_eq!(properties["score"], json!({"type": "number"}));
+        assert_eq!(
+            properties["note"],
+            json!({"type": "integer", "nullable": true})
+        );
+    }
+
+    #[test]
+    fn infer_groups_operations_by_templated_path() {
+        let mut posted = json_event(
+            "POST",
+            "https://api.example.com/users",
+            201,
+            json!({"id": 7}),
+        );
+        posted.request_body = Some(json!({"name": "Ann"}).to_string());
+        let events = [
+            json_event(
+                "GET",
+                "https://api.example.com/users/1?expand=roles",
+                200,
+                json!({"id": 1}),
+            ),
+            json_event(
+                "GET",
+                "https://api.example.com/users/2",
+                404,
+                json!({"error": "gone"}),
+            ),
+            posted,
+            Event::builder().url("not a url").build(),
+        ];
+        let doc = infer("Users", &events);
+        assert_eq!(doc["info"]["title"], "Users");
+        assert_eq!(doc["servers"], json!([{"url": "https://api.example.com"}]));
+
+        let get = &doc["paths"]["/users/{id}"]["get"];
+        let names: Vec<_> = get["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| (p["name"].as_str().unwrap(), p["in"].as_str().unwrap()))
+            .collect();
+        assert_eq!(names, [("id", "path"), ("expand", "query")]);
+        assert_eq!(
+            get["responses"]["200"]["content"]["application/json"]["schema"],
+            json!({"type": "object", "properties": {"id": {"type": "integer"}}})
+        );
+        assert!(get["responses"]["404"].is_object());
+
+        let post = &doc["paths"]["/users"]["post"];
+        assert_eq!(
+            post["requestBody"]["content"]["application/json"]["schema"]["properties"]["name"],
+            json!({"type": "string"})
+        );
+        assert!(post.get("parameters").is_none());
+    }
+}
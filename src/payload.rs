@@ -0,0 +1,165 @@
+//! Decoding binary payloads into JSON.
+//!
+//! Register a decoder per URL / content type in a [`DecoderRegistry`] and run the stream through
+//! [`decode_payloads`]: matching events get [`Event::decoded`] set, so protobuf and other binary
+//! responses arrive as structured values instead of base64. For gRPC-Web responses the decoder
+//! is called once per message frame and `decoded` holds an array of the results.
+//!
+//! Decoders are plain functions from bytes to a [`serde_json::Value`]; wrap your generated
+//! protobuf types in one, or use [`wire_decoder`] to inspect messages without a schema.
+
+use std::fmt;
+use std::sync::Arc;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use futures::StreamExt;
+use futures::channel::mpsc;
+use serde_json::{Map, Value};
+
+use crate::grpc_web::{self, Frame, WireValue};
+use crate::{Error, Event};
+
+type DecodeFn = dyn Fn(&[u8]) -> Result<Value, String> + Send + Sync;
+
+struct Entry {
+    url: Option<String>,
+    content_type: Option<String>,
+    decode: Arc<DecodeFn>,
+}
+
+/// Decoders keyed by URL and content-type substrings. The first matching entry wins.
+#[derive(Clone, Default)]
+pub struct DecoderRegistry {
+    entries: Vec<Arc<Entry>>,
+}
+
+impl fmt::Debug for DecoderRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(
+                self.entries
+                    .iter()
+                    .map(|e| (e.url.as_deref(), e.content_type.as_deref())),
+            )
+            .finish()
+    }
+}
+
+impl DecoderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode events whose URL contains `url` and whose content type contains `content_type`
+    /// with `decode`. `None` matches anything.
+    pub fn register<F>(mut self, url: Option<&str>, content_type: Option<&str>, decode: F) -> Self
+    where
+        F: Fn(&[u8]) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.entries.push(Arc::new(Entry {
+            url: url.map(str::to_string),
+            content_type: content_type.map(str::to_string),
+            decode: Arc::new(decode),
+        }));
+        self
+    }
+
+    fn find(&self, event: &Event) -> Option<&Entry> {
+        let ct = event.content_type.as_deref().unwrap_or_default();
+        self.entries
+            .iter()
+            .find(|e| {
+                e.url.as_deref().is_none_or(|u| event.url.contains(u))
+                    && e.content_type.as_deref().is_none_or(|c| ct.contains(c))
+            })
+            .map(Arc::as_ref)
+    }
+
+    /// Decode `event`'s body with the first matching decoder. `Ok(None)` if none matches.
+    pub fn decode(&self, event: &Event) -> Result<Option<Value>, Error> {
+        let Some(entry) = self.find(event) else {
+            return Ok(None);
+        };
+        let decode = |bytes: &[u8]| (entry.decode)(bytes).map_err(Error::DecodePayload);
+        if grpc_web::is_grpc_web(event) {
+            let messages = grpc_web::decode_frames(event)?
+                .into_iter()
+                .filter_map(|frame| match frame {
+                    Frame::Message { data, .. } => Some(decode(&data)),
+                    Frame::Trailers(_) => None,
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(Some(Value::Array(messages)));
+        }
+        decode(&event.bytes()?).map(Some)
+    }
+
+    /// Set `event.decoded` from the matching decoder, leaving it as it was if none matches.
+    pub fn apply(&self, event: &mut Event) -> Result<(), Error> {
+        if let Some(value) = self.decode(event)? {
+            event.decoded = Some(value);
+        }
+        Ok(())
+    }
+}
+
+/// Forward every event from `rx` with [`Event::decoded`] filled in by `registry`. Events that
+/// fail to decode are forwarded undecoded and the failure is logged.
+pub fn decode_payloads(
+    mut rx: mpsc::UnboundedReceiver<Event>,
+    registry: DecoderRegistry,
+) -> mpsc::UnboundedReceiver<Event> {
+    let (tx, out) = mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some(mut event) = rx.next().await {
+            if let Err(e) = registry.apply(&mut event) {
+                tracing::debug!(url = %event.url, error = %e, "payload decoding failed");
+            }
+            if tx.unbounded_send(event).is_err() {
+                return; // receiver dropped
+            }
+        }
+    });
+    out
+}
+
+/// A schema-less protobuf decoder: an object keyed by field number, with repeated fields as
+/// arrays. Length-delimited fields become a nested object when they parse as a message, a string
+/// when they are UTF-8, and base64 otherwise, so expect the occasional wrong guess.
+pub fn wire_decoder(bytes: &[u8]) -> Result<Value, String> {
+    wire_to_json(bytes).map_err(|e| e.to_string())
+}
+
+fn wire_to_json(bytes: &[u8]) -> Result<Value, Error> {
+    let mut object = Map::new();
+    for field in grpc_web::decode_wire(bytes)? {
+        let value = match field.value {
+            WireValue::Varint(v) | WireValue::Fixed64(v) => Value::from(v),
+            WireValue::Fixed32(v) => Value::from(v),
+            WireValue::Bytes(data) => bytes_to_json(&data),
+        };
+        match object.entry(field.number.to_string()) {
+            serde_json::map::Entry::Vacant(slot) => {
+                slot.insert(value);
+            }
+            serde_json::map::Entry::Occupied(mut slot) => match slot.get_mut() {
+                Value::Array(values) => values.push(value),
+                first => *first = Value::Array(vec![first.take(), value]),
+            },
+        }
+    }
+    Ok(Value::Object(object))
+}
+
+fn bytes_to_json(data: &[u8]) -> Value {
+    if let Ok(text) = std::str::from_utf8(data)
+        && !text.chars().any(|c| c.is_control() && !c.is_whitespace())
+    {
+        return Value::from(text);
+    }
+    match wire_to_json(data) {
+        Ok(nested) if !data.is_empty() => nested,
+        _ => Value::from(STANDARD.encode(data)),
+    }
+}
@@ -0,0 +1,179 @@
+//! Postman collection (v2.1) export.
+//!
+//! Requests are de-duplicated by method and URL and grouped into one folder per host, with a
+//! sub-folder per first path segment. Insomnia can import the same file.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::{Value, json};
+
+use crate::Event;
+
+const SCHEMA: &str = "https://schema.getpostman.com/json/collection/v2.1.0/collection.json";
+
+/// Build a Postman collection named `name` from captured events.
+pub fn collection(name: &str, events: &[Event]) -> Value {
+    // host -> first path segment -> requests
+    let mut folders: BTreeMap<String, BTreeMap<String, Vec<Value>>> = BTreeMap::new();
+    let mut seen = BTreeSet::new();
+
+    for event in events {
+        let method = event.method.as_deref().unwrap_or("GET").to_uppercase();
+        if !seen.insert((method.clone(), event.url.clone())) {
+            continue;
+        }
+        let Ok(url) = url::Url::parse(&event.url) else {
+            continue;
+        };
+        let host = url.host_str().unwrap_or_default().to_string();
+        let segment = url
+            .path_segments()
+            .and_then(|mut s| s.next())
+            .filter(|s| !s.is_empty())
+            .unwrap_or("/")
+            .to_string();
+        folders
+            .entry(host)
+            .or_default()
+            .entry(segment)
+            .or_default()
+            .push(request_item(&method, &url, event));
+    }
+
+    let items: Vec<Value> = folders
+        .into_iter()
+        .map(|(host, segments)| {
+            let children: Vec<Value> = segments
+                .into_iter()
+                .map(|(segment, item)| json!({ "name": segment, "item": item }))
+                .collect();
+            json!({ "name": host, "item": children })
+        })
+        .collect();
+
+    json!({
+        "info": { "name": name, "schema": SCHEMA },
+        "item": items,
+    })
+}
+
+fn request_item(method: &str, url: &url::Url, event: &Event) -> Value {
+    let headers: Vec<Value> = event
+        .request_headers
+        .iter()
+        .map(|h| json!({ "key": h.name, "value": h.value }))
+        .collect();
+    let query: Vec<Value> = url
+        .query_pairs()
+        .map(|(k, v)| json!({ "key": k, "value": v }))
+        .collect();
+    let path: Vec<&str> = url.path_segments().map(|s| s.collect()).unwrap_or_default();
+    let host: Vec<&str> = url.host_str().unwrap_or_default().split('.').collect();
+
+    let mut request = json!({
+        "method": method,
+        "header": headers,
+        "url": {
+            "raw": event.url,
+            "protocol": url.scheme(),
+            "host": host,
+            "path": path,
+            "query": query,
+        },
+    });
+    if let Some(port) = url.port() {
+        request["url"]["port"] = json!(port.to_string());
+    }
+    if let Some(body) = &event.request_body {
+        let is_json = event
+            .request_header("content-type")
+            .is_some_and(|ct| ct.contains("json"));
+        request["body"] = json!({
+            "mode": "raw",
+            "raw": body,
+            "options": { "raw": { "language": if is_json { "json" } else { "text" } } },
+        });
+    }
+
+    json!({
+        "name": format!("{method} {}", url.path()),
+        "request": request,
+        "response": [],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collection_groups_by_host_and_first_segment() {
+        let events = [
+            Event::builder()
+                .url("https://api.example.com/users/1")
+                .build(),
+            // Same method and URL, de-duplicated case-insensitively by method.
+            Event::builder()
+                .url("https://api.example.com/users/1")
+                .method("get")
+                .build(),
+            Event::builder()
+                .url("https://api.example.com/users/1")
+                .method("DELETE")
+                .build(),
+            Event::builder()
+                .url("https://api.example.com/orders")
+                .build(),
+            Event::builder().url("https://cdn.example.com/").build(),
+        ];
+        let collection = collection("Shop", &events);
+        assert_eq!(collection["info"]["name"], "Shop");
+        assert_eq!(collection["info"]["schema"], SCHEMA);
+
+        let hosts = collection["item"].as_array().unwrap();
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0]["name"], "api.example.com");
+        let folders: Vec<_> = hosts[0]["item"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| {
+                (
+                    f["name"].as_str().unwrap(),
+                    f["item"].as_array().unwrap().len(),
+                )
+            })
+            .collect();
+        assert_eq!(folders, [("orders", 1), ("users", 2)]);
+        assert_eq!(hosts[1]["item"][0]["name"], "/");
+        assert_eq!(hosts[1]["item"][0]["item"][0]["name"], "GET /");
+    }
+
+    #[test]
+    fn request_items_carry_url_parts_headers_and_body() {
+        let event = Event::builder()
+            .url("http://localhost:8080/api/items?page=2&q=a%20b")
+            .method("POST")
+            .request_header("content-type", "application/json")
+            .request_body(r#"{"name":"x"}"#)
+            .build();
+        let item = &collection("Local", &[event])["item"][0]["item"][0]["item"][0];
+        assert_eq!(item["name"], "POST /api/items");
+        let request = &item["request"];
+        assert_eq!(
+            request["header"],
+            json!([{"key": "content-type", "value": "application/json"}])
+        );
+        let url = &request["url"];
+        assert_eq!(url["protocol"], "http");
+        assert_eq!(url["host"], json!(["localhost"]));
+        assert_eq!(url["port"], "8080");
+        assert_eq!(url["path"], json!(["api", "items"]));
+        assert_eq!(
+            url["query"],
+            json!([{"key": "page", "value": "2"}, {"key": "q", "value": "a b"}])
+        );
+        assert_eq!(request["body"]["raw"], r#"{"name":"x"}"#);
+        assert_eq!(request["body"]["options"]["raw"]["language"], "json");
+    }
+}
@@ -0,0 +1,101 @@
+//! Fetch priorities of captured requests.
+//!
+//! With [`EventStreamConfig::track_priority`](crate::EventStreamConfig::track_priority) set, the
+//! stream follows CDP `Network.requestWillBeSent` and `Network.resourceChangedPriority` and
+//! records the priority each request started with and the one it finished with. As with cache
+//! sources, the priorities are matched to events by URL, in the order the requests finished,
+//! skipping requests that started before the event's own. A redirected request counts as one,
+//! under its final URL, with the priority its first hop started with.
+
+use std::sync::Arc;
+
+use crate::Event;
+use crate::watch::{Follower, Notice, Request, UrlQueue};
+
+// The initial and final priority of a finished request.
+#[derive(Debug)]
+pub(crate) struct Priority {
+    initial: String,
+    last: String,
+}
+
+// Priorities of finished requests, waiting for their events.
+pub(crate) type Priorities = UrlQueue<Priority>;
+
+impl Priorities {
+    /// Fill in the priorities of the request behind `event`, if it was seen.
+    pub(crate) fn annotate(&self, event: &mut Event) {
+        if let Some(priority) = self.take(&event.url, event.started_at) {
+            event.initial_priority = Some(priority.initial);
+            event.final_priority = Some(priority.last);
+        }
+    }
+}
+
+/// Records the priorities of requests as they finish.
+pub(crate) struct PriorityFollower(pub(crate) Arc<Priorities>);
+
+impl Follower for PriorityFollower {
+    fn follow(&mut self, notice: &Notice, request: Option<&Request>) {
+        if !matches!(notice, Notice::Finished(_) | Notice::Failed(_)) {
+            return;
+        }
+        let Some(request) = request else {
+            return;
+        };
+        let priority = Priority {
+            initial: request.initial_priority.clone(),
+            last: request.priority.clone(),
+        };
+        self.0
+            .push(request.url.clone(), Some(request.started_at), priority);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watch::{Requests, events};
+
+    fn priority(initial: &str) -> Priority {
+        Priority {
+            initial: initial.to_string(),
+            last: "High".to_string(),
+        }
+    }
+
+    #[test]
+    fn annotate_skips_requests_that_started_earlier() {
+        let priorities = Priorities::default();
+        let url = "https://example.com/app.js";
+        priorities.push(url.to_string(), Some(1_000.0), priority("Low"));
+        priorities.push(url.to_string(), Some(2_000.5), priority("Medium"));
+
+        let mut event = Event::builder().url(url).started_at(2_000.0).build();
+        priorities.annotate(&mut event);
+        assert_eq!(event.initial_priority.as_deref(), Some("Medium"));
+        assert_eq!(event.final_priority.as_deref(), Some("High"));
+
+        let mut later = Event::builder().url(url).build();
+        priorities.annotate(&mut later);
+        assert_eq!(later.initial_priority, None);
+    }
+
+    #[test]
+    fn redirected_requests_keep_their_first_priority_under_the_last_url() {
+        let priorities = Arc::new(Priorities::default());
+        let follower = PriorityFollower(Arc::clone(&priorities));
+        let mut requests = Requests::new(vec![Box::new(follower)]);
+        let first = "https://example.com/old";
+        let last = "https://cdn.example.com/new";
+        requests.handle(&events::sent("7", first, 2.0, "High", None));
+        requests.handle(&events::sent("7", last, 2.1, "Low", Some(first)));
+        requests.handle(&events::finished("7"));
+
+        // The hooks saw the final URL and the time the fetch started.
+        let mut event = Event::builder().url(last).started_at(1_999.0).build();
+        priorities.annotate(&mut event);
+        assert_eq!(event.initial_priority.as_deref(), Some("High"));
+        assert_eq!(event.final_priority.as_deref(), Some("Low"));
+    }
+}
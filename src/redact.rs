@@ -0,0 +1,348 @@
+//! Masking sensitive data in captured events before they are emitted.
+//!
+//! Every stream applies [`EventStreamConfig::redaction`](crate::EventStreamConfig::redaction)
+//! just before sending an event, so captures can be stored and shared without leaking
+//! credentials. URLs in everything else the stream reports go through
+//! [`Redaction::redact_url`] too: request starts, blocked requests, WebSocket handshakes,
+//! certificate warnings, capture errors and its own log lines. The default masks the
+//! `authorization`, `cookie` and `set-cookie` headers; use [`Redaction::none`] to capture them
+//! as-is. Each event lists the headers it had masked in [`Event::redacted_headers`]. The
+//! stream's [cookie jar](crate::EventStreamHandle::cookies) is fed from CDP, so redaction does
+//! not apply to it.
+//!
+//! [`Scrubber`]s go further and rewrite text bodies, e.g. to mask email addresses and card
+//! numbers for teams that may not persist raw payloads.
+
+use std::fmt;
+use std::sync::Arc;
+
+use regex::Regex;
+use serde::Deserialize;
+use url::form_urlencoded;
+
+use crate::{Error, Event, Header};
+
+const DEFAULT_MASK: &str = "[REDACTED]";
+
+impl Event {
+    /// Whether redaction masked `header`, by [`Event::redacted_headers`]. Replays, mocks and
+    /// curl commands drop such headers rather than send the mask as a credential or cookie.
+    pub(crate) fn is_redacted(&self, header: &Header) -> bool {
+        self.redacted_headers
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(&header.name))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Redaction {
+    /// Request and response headers whose values are replaced with `mask`. Case-insensitive.
+    pub headers: Vec<String>,
+    pub mask: String,
+    /// Query parameters whose values are replaced with `mask` in [`Event::url`], the rewritten
+    /// and page URLs and `referer` / `location` headers, e.g. `access_token` or `api_key`.
+    /// Case-insensitive. Everything else in the URL is left byte for byte.
+    pub query_params: Vec<String>,
+    /// Applied in order to the request body, text response bodies and text multipart parts.
+    /// Not read from config files.
+    #[serde(skip)]
+    pub scrubbers: Vec<Scrubber>,
+}
+
+impl Default for Redaction {
+    fn default() -> Self {
+        Self {
+            headers: vec![
+                "authorization".to_string(),
+                "cookie".to_string(),
+                "set-cookie".to_string(),
+            ],
+            mask: DEFAULT_MASK.to_string(),
+            query_params: Vec::new(),
+            scrubbers: Vec::new(),
+        }
+    }
+}
+
+impl Redaction {
+    /// Redact nothing.
+    pub fn none() -> Self {
+        Self {
+            headers: Vec::new(),
+            mask: DEFAULT_MASK.to_string(),
+            query_params: Vec::new(),
+            scrubbers: Vec::new(),
+        }
+    }
+
+    /// Also redact the header `name`.
+    pub fn header(mut self, name: impl Into<String>) -> Self {
+        self.headers.push(name.into());
+        self
+    }
+
+    pub fn mask(mut self, mask: impl Into<String>) -> Self {
+        self.mask = mask.into();
+        self
+    }
+
+    /// Also redact the query parameter `name`.
+    pub fn query_param(mut self, name: impl Into<String>) -> Self {
+        self.query_params.push(name.into());
+        self
+    }
+
+    pub fn scrubber(mut self, scrubber: Scrubber) -> Self {
+        self.scrubbers.push(scrubber);
+        self
+    }
+
+    /// Mask `event` in place. Streams call this for you; use it on events loaded from fixtures
+    /// or other sources.
+    pub fn apply(&self, event: &mut Event) {
+        let request = self.redact_headers(&mut event.request_headers);
+        let response = self.redact_headers(&mut event.response_headers);
+        for name in request.into_iter().chain(response) {
+            if !event.redacted_headers.contains(&name) {
+                event.redacted_headers.push(name);
+            }
+        }
+        event.url = self.redact_url(&event.url);
+        for url in [&mut event.rewritten_url, &mut event.page_url]
+            .into_iter()
+            .flatten()
+        {
+            *url = self.redact_url(url);
+        }
+
+        if self.scrubbers.is_empty() {
+            return;
+        }
+        let mut bodies: Vec<&mut String> = Vec::new();
+        bodies.extend(event.request_body.as_mut());
+        for part in event.request_parts.iter_mut().flatten() {
+            bodies.extend(part.body.as_mut());
+        }
+        // Base64 bodies are binary, and a text pattern could only corrupt them.
+        if !event.base64_encoded {
+            bodies.push(&mut event.body);
+            bodies.extend(event.original_body.as_mut());
+        }
+        for body in bodies {
+            for scrubber in &self.scrubbers {
+                *body = scrubber.scrub(body);
+            }
+        }
+    }
+
+    // Mask credential headers, and query parameters in URL-valued ones. Returns the names of
+    // the masked headers, lower-cased.
+    pub(crate) fn redact_headers(&self, headers: &mut [Header]) -> Vec<String> {
+        let mut masked = Vec::new();
+        for header in headers {
+            if self
+                .headers
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&header.name))
+            {
+                header.value.clone_from(&self.mask);
+                masked.push(header.name.to_ascii_lowercase());
+            } else if header.name.eq_ignore_ascii_case("referer")
+                || header.name.eq_ignore_ascii_case("location")
+            {
+                header.value = self.redact_url(&header.value);
+            }
+        }
+        masked
+    }
+
+    /// `url` with the values of [`query_params`](Self::query_params) masked.
+    pub fn redact_url(&self, url: &str) -> String {
+        if self.query_params.is_empty() {
+            return url.to_string();
+        }
+        let Some((base, rest)) = url.split_once('?') else {
+            return url.to_string();
+        };
+        let (query, fragment) = match rest.split_once('#') {
+            Some((query, fragment)) => (query, Some(fragment)),
+            None => (rest, None),
+        };
+        let mask: String = form_urlencoded::byte_serialize(self.mask.as_bytes()).collect();
+        let pairs: Vec<String> = query
+            .split('&')
+            .map(|pair| {
+                let name = pair.split_once('=').map_or(pair, |(name, _)| name);
+                let decoded: String = form_urlencoded::parse(name.as_bytes())
+                    .next()
+                    .map(|(name, _)| name.into_owned())
+                    .unwrap_or_default();
+                if self
+                    .query_params
+                    .iter()
+                    .any(|param| param.eq_ignore_ascii_case(&decoded))
+                {
+                    format!("{name}={mask}")
+                } else {
+                    pair.to_string()
+                }
+            })
+            .collect();
+        let mut redacted = format!("{base}?{}", pairs.join("&"));
+        if let Some(fragment) = fragment {
+            redacted.push('#');
+            redacted.push_str(fragment);
+        }
+        redacted
+    }
+}
+
+/// A rewrite of body text, see [`Redaction::scrubbers`].
+#[derive(Clone)]
+pub struct Scrubber(ScrubberKind);
+
+#[derive(Clone)]
+enum ScrubberKind {
+    Regex { pattern: Regex, replacement: String },
+    Callback(Arc<ScrubFn>),
+}
+
+type ScrubFn = dyn Fn(&str) -> String + Send + Sync;
+
+impl Scrubber {
+    /// Replace every match of `pattern` with `replacement`, which may refer to capture groups
+    /// as `$1` or `$name`.
+    pub fn regex(pattern: &str, replacement: impl Into<String>) -> Result<Self, Error> {
+        Ok(Self(ScrubberKind::Regex {
+            pattern: Regex::new(pattern).map_err(Error::InvalidRegex)?,
+            replacement: replacement.into(),
+        }))
+    }
+
+    /// Rewrite the text with a callback.
+    pub fn new(scrub: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        Self(ScrubberKind::Callback(Arc::new(scrub)))
+    }
+
+    /// Mask email addresses.
+    pub fn emails() -> Self {
+        Self::regex(
+            r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}",
+            DEFAULT_MASK,
+        )
+        .unwrap()
+    }
+
+    /// Mask runs of 13 to 19 digits, optionally grouped by spaces or dashes, as card numbers
+    /// are written.
+    pub fn card_numbers() -> Self {
+        Self::regex(r"\b\d(?:[ -]?\d){12,18}\b", DEFAULT_MASK).unwrap()
+    }
+
+    pub fn scrub(&self, text: &str) -> String {
+        match &self.0 {
+            ScrubberKind::Regex {
+                pattern,
+                replacement,
+            } => pattern.replace_all(text, replacement.as_str()).into_owned(),
+            ScrubberKind::Callback(scrub) => scrub(text),
+        }
+    }
+}
+
+impl fmt::Debug for Scrubber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            ScrubberKind::Regex {
+                pattern,
+                replacement,
+            } => f
+                .debug_struct("Scrubber")
+                .field("pattern", &pattern.as_str())
+                .field("replacement", replacement)
+                .finish(),
+            ScrubberKind::Callback(_) => f.write_str("Scrubber(..)"),
+        }
+    }
+}
+
+// Patterns compare by their source; callbacks only equal themselves.
+impl PartialEq for Scrubber {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (
+                ScrubberKind::Regex {
+                    pattern: a,
+                    replacement: ra,
+                },
+                ScrubberKind::Regex {
+                    pattern: b,
+                    replacement: rb,
+                },
+            ) => a.as_str() == b.as_str() && ra == rb,
+            (ScrubberKind::Callback(a), ScrubberKind::Callback(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Scrubber {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_url_masks_listed_query_params() {
+        let redaction = Redaction::default()
+            .query_param("access_token")
+            .query_param("KEY");
+        assert_eq!(
+            redaction.redact_url("https://example.com/a?Access_Token=abc&page=2&key=x#top"),
+            "https://example.com/a?Access_Token=%5BREDACTED%5D&page=2&key=%5BREDACTED%5D#top"
+        );
+        // Names are compared decoded; flag-style params are masked too.
+        assert_eq!(
+            redaction.redact_url("https://example.com/?access%5Ftoken=abc&key"),
+            "https://example.com/?access%5Ftoken=%5BREDACTED%5D&key=%5BREDACTED%5D"
+        );
+        assert_eq!(
+            redaction.redact_url("https://example.com/#key=x"),
+            "https://example.com/#key=x"
+        );
+    }
+
+    #[test]
+    fn redact_url_without_params_is_unchanged() {
+        let url = "https://example.com/?access_token=abc";
+        assert_eq!(Redaction::default().redact_url(url), url);
+        assert_eq!(
+            Redaction::default()
+                .query_param("access_token")
+                .mask("***")
+                .redact_url(url),
+            "https://example.com/?access_token=***"
+        );
+    }
+
+    #[test]
+    fn apply_records_masked_headers_once() {
+        let mut event = Event::builder()
+            .url("https://example.com/")
+            .request_header("Authorization", "Bearer abc")
+            .request_header("cookie", "sid=1")
+            .response_header("set-cookie", "sid=2")
+            .response_header("content-type", "text/plain")
+            .build();
+        let redaction = Redaction::default().mask("***");
+        redaction.apply(&mut event);
+        redaction.apply(&mut event);
+        assert_eq!(event.request_header("authorization"), Some("***"));
+        assert_eq!(
+            event.redacted_headers,
+            ["authorization", "cookie", "set-cookie"]
+        );
+        assert!(!event.is_redacted(&event.response_headers[1]));
+    }
+}
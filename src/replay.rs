@@ -0,0 +1,74 @@
+use chromiumoxide::page::Page;
+use serde_json::json;
+
+use crate::{Error, Event};
+
+// Re-issues a request with the page's un-hooked `fetch` and serializes the response as an event.
+// Bodies that are not valid UTF-8 come back base64-encoded.
+const REPLAY_JS: &str = r#"
+async function(req) {
+  const fetchFn = window.__event_orig_fetch || window.fetch;
+  const init = { method: req.method, headers: req.headers.map(function(h) { return [h.name, h.value]; }) };
+  if (req.body !== null && req.method !== 'GET' && req.method !== 'HEAD') init.body = req.body;
+  const startedAt = Date.now();
+  const t0 = performance.now();
+  const res = await fetchFn(req.url, init);
+  const headersAt = performance.now();
+  const buf = await res.arrayBuffer();
+  let body, base64Encoded = false;
+  try {
+    body = new TextDecoder('utf-8', { fatal: true }).decode(buf);
+  } catch(e) {
+    const bytes = new Uint8Array(buf);
+    let s = '';
+    for (let i = 0; i < bytes.length; i += 0x8000) {
+      s += String.fromCharCode.apply(null, bytes.subarray(i, i + 0x8000));
+    }
+    body = btoa(s);
+    base64Encoded = true;
+  }
+  const responseHeaders = [];
+  res.headers.forEach(function(value, name) { responseHeaders.push({ name: name, value: value }); });
+  return JSON.stringify({
+    url: res.url || req.url, body: body, base64Encoded: base64Encoded,
+    contentType: res.headers.get('content-type') || '', status: res.status,
+    method: req.method, requestHeaders: req.headers,
+    requestBody: init.body === undefined ? null : init.body,
+    responseHeaders: responseHeaders,
+    startedAt: startedAt, timeToHeadersMs: headersAt - t0, durationMs: performance.now() - t0
+  });
+}
+"#;
+
+impl Event {
+    /// Send this request again from `page`, with the same method, headers and body, and return
+    /// the fresh response as a new event.
+    ///
+    /// The request goes through the page's own `fetch`, so it carries the page's cookies and is
+    /// subject to CORS like any page request. It bypasses the capture hooks: a running stream
+    /// does not see replays. Headers the browser controls (`Cookie`, `Host`, ...) are dropped,
+    /// and a body the page did not record as text (`FormData`, `Blob`) is not resent. The
+    /// [`redacted_headers`](Self::redacted_headers) are dropped too, e.g. `Authorization` under
+    /// the default settings: the replay goes out without them rather than with the mask.
+    pub async fn replay(&self, page: &Page) -> Result<Event, Error> {
+        let headers: Vec<_> = self
+            .request_headers
+            .iter()
+            .filter(|h| !self.is_redacted(h))
+            .collect();
+        let request = json!({
+            "url": self.url,
+            "method": self.method.as_deref().unwrap_or("GET"),
+            "headers": headers,
+            "body": self.request_body,
+        });
+        let js = format!("({REPLAY_JS})({request})");
+        let s: String = page
+            .evaluate_expression(js)
+            .await
+            .map_err(Error::Replay)?
+            .into_value()
+            .unwrap_or_default();
+        serde_json::from_str(&s).map_err(Error::ParseJson)
+    }
+}
@@ -0,0 +1,105 @@
+use std::future::Future;
+use std::time::Duration;
+
+use chromiumoxide::error::CdpError;
+use serde::Deserialize;
+
+/// How the crate retries browser calls (hook installation, buffer drains) that fail.
+///
+/// A failed call is retried up to `attempts - 1` times, sleeping `backoff` before the first retry
+/// and doubling the delay after each one. The default makes a single attempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first. `0` is treated as `1`.
+    pub attempts: u32,
+    #[serde(rename = "backoff_ms", deserialize_with = "crate::config::millis")]
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 1,
+            backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub(crate) async fn run<T, F, Fut>(&self, mut call: F) -> Result<T, CdpError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, CdpError>>,
+    {
+        let mut delay = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match call().await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.attempts => {
+                    tracing::debug!(error = %e, attempt, "browser call failed, retrying");
+                    tokio::time::sleep(delay).await;
+                    delay = delay.saturating_mul(2);
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn run_retries_until_success_or_attempts_run_out() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            backoff: Duration::from_millis(1),
+        };
+        let calls = Cell::new(0);
+        let result = policy
+            .run(|| {
+                calls.set(calls.get() + 1);
+                let n = calls.get();
+                async move {
+                    if n < 3 {
+                        Err(CdpError::NoResponse)
+                    } else {
+                        Ok(n)
+                    }
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 3);
+
+        calls.set(0);
+        let result: Result<(), _> = policy
+            .run(|| {
+                calls.set(calls.get() + 1);
+                async { Err(CdpError::NoResponse) }
+            })
+            .await;
+        assert!(matches!(result, Err(CdpError::NoResponse)));
+        assert_eq!(calls.get(), 3);
+
+        // `0` attempts still makes one.
+        calls.set(0);
+        let once = RetryPolicy {
+            attempts: 0,
+            ..policy
+        };
+        let result: Result<(), _> = once
+            .run(|| {
+                calls.set(calls.get() + 1);
+                async { Err(CdpError::NoResponse) }
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+}
@@ -0,0 +1,242 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::time::Instant;
+
+use crate::{Error, Event};
+
+/// When [`RotatingJsonlWriter`] starts a new file. Limits left as `None` are not applied;
+/// with both unset everything goes to the first file.
+#[derive(Clone, Debug, Default)]
+pub struct RotationPolicy {
+    /// Rotate before a write would push the current file past this many bytes.
+    pub max_bytes: Option<u64>,
+    /// Rotate once the current file has been open this long.
+    pub max_age: Option<Duration>,
+}
+
+/// JSON-lines writer that spreads events over numbered files (`capture-0001.ndjson`, ...).
+///
+/// Rotated files are left uncompressed. Every file but [`current_path`](Self::current_path) is
+/// closed, so an external job can gzip them.
+pub struct RotatingJsonlWriter {
+    dir: PathBuf,
+    prefix: String,
+    policy: RotationPolicy,
+    index: u32,
+    file: BufWriter<File>,
+    bytes: u64,
+    opened_at: Instant,
+}
+
+impl RotatingJsonlWriter {
+    /// Create `dir` if needed and open `<prefix>-0001.ndjson` in it.
+    pub async fn create(
+        dir: impl AsRef<Path>,
+        prefix: impl Into<String>,
+        policy: RotationPolicy,
+    ) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        let prefix = prefix.into();
+        tokio::fs::create_dir_all(&dir).await.map_err(Error::Io)?;
+        let file = open_part(&dir, &prefix, 1).await?;
+        Ok(Self {
+            dir,
+            prefix,
+            policy,
+            index: 1,
+            file,
+            bytes: 0,
+            opened_at: Instant::now(),
+        })
+    }
+
+    /// Path of the file currently being written.
+    pub fn current_path(&self) -> PathBuf {
+        part_path(&self.dir, &self.prefix, self.index)
+    }
+
+    pub async fn write_event(&mut self, event: &Event) -> Result<(), Error> {
+        let mut line = serde_json::to_vec(event).map_err(Error::SerializeJson)?;
+        line.push(b'\n');
+        if self.should_rotate(line.len() as u64) {
+            self.rotate().await?;
+        }
+        self.file.write_all(&line).await.map_err(Error::Io)?;
+        self.file.flush().await.map_err(Error::Io)?;
+        self.bytes += line.len() as u64;
+        Ok(())
+    }
+
+    /// Flush and close the current file.
+    pub async fn finish(mut self) -> Result<(), Error> {
+        self.file.shutdown().await.map_err(Error::Io)
+    }
+
+    fn should_rotate(&self, next_len: u64) -> bool {
+        // Never rotate away from an empty file, even if a single line exceeds the size limit.
+        if self.bytes == 0 {
+            return false;
+        }
+        let too_big = self
+            .policy
+            .max_bytes
+            .is_some_and(|max| self.bytes + next_len > max);
+        let too_old = self
+            .policy
+            .max_age
+            .is_some_and(|max| self.opened_at.elapsed() >= max);
+        too_big || too_old
+    }
+
+    async fn rotate(&mut self) -> Result<(), Error> {
+        self.file.shutdown().await.map_err(Error::Io)?;
+        self.index += 1;
+        self.file = open_part(&self.dir, &self.prefix, self.index).await?;
+        self.bytes = 0;
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+}
+
+/// Stream every event from the receiver into rotating JSON-lines files until the stream closes.
+/// Returns the number of events written.
+pub async fn stream_to_rotating_jsonl(
+    mut rx: mpsc::UnboundedReceiver<Event>,
+    mut writer: RotatingJsonlWriter,
+) -> Result<usize, Error> {
+    let mut written = 0;
+    while let Some(event) = rx.next().await {
+        writer.write_event(&event).await?;
+        written += 1;
+    }
+    writer.finish().await?;
+    Ok(written)
+}
+
+fn part_path(dir: &Path, prefix: &str, index: u32) -> PathBuf {
+    dir.join(format!("{prefix}-{index:04}.ndjson"))
+}
+
+async fn open_part(dir: &Path, prefix: &str, index: u32) -> Result<BufWriter<File>, Error> {
+    let file = File::create(part_path(dir, prefix, index))
+        .await
+        .map_err(Error::Io)?;
+    Ok(BufWriter::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "chromiumoxide_event_stream-{name}-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn line_counts(dir: &Path) -> Vec<(String, usize)> {
+        let mut files: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let lines = std::fs::read_to_string(&path).unwrap().lines().count();
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, lines)
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn event(i: usize) -> Event {
+        Event::builder()
+            .url(format!("https://example.com/{i}"))
+            .build()
+    }
+
+    #[tokio::test]
+    async fn rotates_before_a_write_would_pass_max_bytes() {
+        let dir = scratch_dir("bytes");
+        let line_len = serde_json::to_vec(&event(0)).unwrap().len() as u64 + 1;
+        let policy = RotationPolicy {
+            max_bytes: Some(line_len * 2),
+            max_age: None,
+        };
+        let mut writer = RotatingJsonlWriter::create(&dir, "capture", policy)
+            .await
+            .unwrap();
+        for i in 0..5 {
+            writer.write_event(&event(i)).await.unwrap();
+        }
+        assert_eq!(writer.current_path(), dir.join("capture-0003.ndjson"));
+        writer.finish().await.unwrap();
+        assert_eq!(
+            line_counts(&dir),
+            [
+                ("capture-0001.ndjson".to_string(), 2),
+                ("capture-0002.ndjson".to_string(), 2),
+                ("capture-0003.ndjson".to_string(), 1),
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn oversized_first_line_stays_in_its_file() {
+        let dir = scratch_dir("oversized");
+        let policy = RotationPolicy {
+            max_bytes: Some(1),
+            max_age: None,
+        };
+        let (tx, rx) = mpsc::unbounded();
+        for i in 0..2 {
+            tx.unbounded_send(event(i)).unwrap();
+        }
+        drop(tx);
+        let writer = RotatingJsonlWriter::create(&dir, "big", policy)
+            .await
+            .unwrap();
+        assert_eq!(stream_to_rotating_jsonl(rx, writer).await.unwrap(), 2);
+        assert_eq!(
+            line_counts(&dir),
+            [
+                ("big-0001.ndjson".to_string(), 1),
+                ("big-0002.ndjson".to_string(), 1),
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn rotates_once_the_file_is_max_age_old() {
+        let dir = scratch_dir("age");
+        let policy = RotationPolicy {
+            max_bytes: None,
+            max_age: Some(Duration::from_millis(50)),
+        };
+        let mut writer = RotatingJsonlWriter::create(&dir, "capture", policy)
+            .await
+            .unwrap();
+        writer.write_event(&event(0)).await.unwrap();
+        writer.write_event(&event(1)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        writer.write_event(&event(2)).await.unwrap();
+        writer.finish().await.unwrap();
+        assert_eq!(
+            line_counts(&dir),
+            [
+                ("capture-0001.ndjson".to_string(), 2),
+                ("capture-0002.ndjson".to_string(), 1),
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,147 @@
+use serde::Serialize;
+
+use crate::Event;
+
+/// One metric of a `Server-Timing` response header.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ServerTiming {
+    pub name: String,
+    /// The `dur` parameter, in milliseconds.
+    pub duration_ms: Option<f64>,
+    /// The `desc` parameter.
+    pub description: Option<String>,
+}
+
+impl Event {
+    /// The metrics of every `Server-Timing` response header, in order. Unknown parameters are
+    /// ignored and a malformed `dur` is treated as absent.
+    pub fn server_timings(&self) -> Vec<ServerTiming> {
+        self.response_headers
+            .iter()
+            .filter(|h| h.name.eq_ignore_ascii_case("server-timing"))
+            .flat_map(|h| parse(&h.value))
+            .collect()
+    }
+}
+
+/// Parse a `Server-Timing` header value.
+fn parse(value: &str) -> Vec<ServerTiming> {
+    split_unquoted(value, ',')
+        .into_iter()
+        .filter_map(|metric| {
+            let mut params = split_unquoted(metric, ';').into_iter();
+            let name = params.next()?.trim();
+            if name.is_empty() {
+                return None;
+            }
+            let mut timing = ServerTiming {
+                name: name.to_string(),
+                duration_ms: None,
+                description: None,
+            };
+            for param in params {
+                let (key, value) = param.split_once('=').unwrap_or((param, ""));
+                let value = unquote(value.trim());
+                match key.trim().to_ascii_lowercase().as_str() {
+                    // The first occurrence of a parameter wins.
+                    "dur" if timing.duration_ms.is_none() => {
+                        timing.duration_ms = value.parse().ok()
+                    }
+                    "desc" if timing.description.is_none() => timing.description = Some(value),
+                    _ => {}
+                }
+            }
+            Some(timing)
+        })
+        .collect()
+}
+
+// Split on `sep` outside double-quoted strings.
+fn split_unquoted(value: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == sep && !quoted => {
+                parts.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(inner) => {
+            let mut out = String::with_capacity(inner.len());
+            let mut chars = inner.chars();
+            while let Some(c) = chars.next() {
+                out.push(if c == '\\' {
+                    chars.next().unwrap_or(c)
+                } else {
+                    c
+                });
+            }
+            out
+        }
+        None => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(name: &str, duration_ms: Option<f64>, description: Option<&str>) -> ServerTiming {
+        ServerTiming {
+            name: name.to_string(),
+            duration_ms,
+            description: description.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn parse_reads_every_metric_and_its_params() {
+        assert_eq!(
+            parse(r#"db;dur=53.2;desc="Query, cached", cache;desc=hit, total;dur=120"#),
+            [
+                timing("db", Some(53.2), Some("Query, cached")),
+                timing("cache", None, Some("hit")),
+                timing("total", Some(120.0), None),
+            ]
+        );
+        // Escapes inside quotes, case-insensitive keys, unknown params and blank metrics.
+        assert_eq!(
+            parse(r#"app;DESC="say \"hi\"; bye";Dur=1;x=2, ,"#),
+            [timing("app", Some(1.0), Some(r#"say "hi"; bye"#))]
+        );
+    }
+
+    #[test]
+    fn parse_treats_bad_or_repeated_durations_as_absent_or_first() {
+        assert_eq!(parse("db;dur=abc"), [timing("db", None, None)]);
+        assert_eq!(parse("db;dur"), [timing("db", None, None)]);
+        assert_eq!(parse("db;dur=5;dur=7"), [timing("db", Some(5.0), None)]);
+        assert_eq!(parse(r#"db;dur="2.5""#), [timing("db", Some(2.5), None)]);
+    }
+
+    #[test]
+    fn server_timings_collects_every_header() {
+        let event = Event::builder()
+            .url("https://example.com/")
+            .response_header("Server-Timing", "edge;dur=4")
+            .response_header("content-type", "text/plain")
+            .response_header("server-timing", "origin;dur=40")
+            .build();
+        let names: Vec<_> = event.server_timings().into_iter().map(|t| t.name).collect();
+        assert_eq!(names, ["edge", "origin"]);
+    }
+}
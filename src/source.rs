@@ -0,0 +1,286 @@
+//! Where the capture loop reads its events from.
+//!
+//! Every stream runs the same loop: drain a [`SourceBatch`] from an [`EventSource`], filter,
+//! annotate and redact its events, forward them, sleep [`poll_interval_ms`](crate::EventStreamConfig::poll_interval_ms)
+//! and repeat until the source closes. For a live stream the source is the page, drained through
+//! the installed hooks, see [`PageSource`]. Implement the trait, or use [`ScriptedSource`], to
+//! feed the loop synthetic captures with [`run_event_source`](crate::run_event_source) and check
+//! filters and redaction without launching a browser. Batches serialize as the page returns
+//! them, so they can be recorded and replayed, see [`fixture::record_source`](crate::fixture::record_source).
+
+use std::collections::VecDeque;
+use std::future::{self, Future};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chromiumoxide::cdp::browser_protocol::inspector::EventDetached;
+use chromiumoxide::listeners::EventStream;
+use chromiumoxide::page::Page;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Event, EventStreamConfig, RequestStarted, RetryPolicy, StreamEndReason};
+
+/// Everything captured since the previous drain.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SourceBatch {
+    #[serde(default)]
+    pub events: Vec<Event>,
+    /// Requests started but not yet captured.
+    #[serde(rename = "inFlight", default)]
+    pub in_flight: u64,
+    /// Responses whose body could not be read.
+    #[serde(rename = "bodyFailures", default)]
+    pub body_failures: u64,
+    /// Events dropped from a full buffer before they could be drained.
+    #[serde(default)]
+    pub evicted: u64,
+    /// Details of some of the `body_failures`, reported as
+    /// [`CaptureError::BodyFetchFailed`](crate::CaptureError::BodyFetchFailed).
+    #[serde(default)]
+    pub errors: Vec<BodyFailure>,
+    /// Requests that went out, when [`EventStreamConfig::emit_request_starts`] is set.
+    #[serde(default)]
+    pub starts: Vec<RequestStarted>,
+}
+
+impl SourceBatch {
+    pub fn new(events: impl IntoIterator<Item = Event>) -> Self {
+        Self {
+            events: events.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+}
+
+/// A body that could not be read, see [`SourceBatch::errors`].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BodyFailure {
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub message: String,
+}
+
+/// A supply of raw captures for the capture loop, see [`run_event_source`](crate::run_event_source).
+pub trait EventSource: Send {
+    /// Take everything captured since the last call. An error ends the loop with
+    /// [`StreamEndReason::DrainFailed`].
+    fn drain(&mut self) -> impl Future<Output = Result<SourceBatch, Error>> + Send;
+
+    /// Resolves once the source has nothing more to give, with the reason. The loop waits on it
+    /// between drains, so it should stay pending while more captures may come.
+    fn closed(&mut self) -> impl Future<Output = StreamEndReason> + Send;
+}
+
+/// A source that yields the given batches one per drain, then closes.
+#[derive(Clone, Debug, Default)]
+pub struct ScriptedSource {
+    batches: VecDeque<SourceBatch>,
+    end: Option<StreamEndReason>,
+}
+
+impl ScriptedSource {
+    pub fn new(batches: impl IntoIterator<Item = SourceBatch>) -> Self {
+        Self {
+            batches: batches.into_iter().collect(),
+            end: None,
+        }
+    }
+
+    /// A single batch holding `events`.
+    pub fn from_events(events: impl IntoIterator<Item = Event>) -> Self {
+        Self::new([SourceBatch::new(events)])
+    }
+
+    /// Close with `reason` once the batches run out. Defaults to [`StreamEndReason::PageClosed`].
+    pub fn end_with(mut self, reason: StreamEndReason) -> Self {
+        self.end = Some(reason);
+        self
+    }
+
+    /// Batches not drained yet.
+    pub fn len(&self) -> usize {
+        self.batches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.batches.is_empty()
+    }
+}
+
+impl EventSource for ScriptedSource {
+    async fn drain(&mut self) -> Result<SourceBatch, Error> {
+        Ok(self.batches.pop_front().unwrap_or_default())
+    }
+
+    async fn closed(&mut self) -> StreamEndReason {
+        if !self.batches.is_empty() {
+            future::pending::<()>().await;
+        }
+        self.end.clone().unwrap_or(StreamEndReason::PageClosed)
+    }
+}
+
+// Releases timed-out ordered slots, then takes the buffered events, errors and starts and resets the
+// failure/eviction counters, so each poll reports deltas. Taking is not idempotent, so each drain
+// carries an id and the page keeps its last non-empty result until `ACK_JS` or the next drain: a
+// retried drain whose first attempt did run gets the same batch back instead of an empty one.
+const DRAIN_JS: &str = "(function(id) { try { const last = window.__event_last_drain; if (last && last.id === id) return last.result; delete window.__event_last_drain; if (window.__event_flush_ordered) window.__event_flush_ordered(); const a = (window.__event_stream || []).splice(0); const e = (window.__event_errors || []).splice(0); const f = window.__event_body_failures || 0; const v = window.__event_evicted || 0; window.__event_body_failures = 0; window.__event_evicted = 0; const s = (window.__event_starts || []).splice(0); const result = JSON.stringify({ events: a, inFlight: window.__event_in_flight || 0, bodyFailures: f, evicted: v, errors: e, starts: s }); if (a.length || e.length || s.length || f || v) window.__event_last_drain = { id: id, result: result }; return result; } catch(e) { return '{}'; } })";
+
+// Frees the page's copy of drain `id` once its batch was parsed.
+const ACK_JS: &str = "(function(id) { const last = window.__event_last_drain; if (last && last.id === id) delete window.__event_last_drain; })";
+
+// Drain ids, unique across every source in the process so two streams on one page never
+// mistake each other's drains for retries.
+static NEXT_DRAIN: AtomicU64 = AtomicU64::new(1);
+
+/// The page buffer filled by the capture hooks, closed when the target detaches.
+pub struct PageSource {
+    page: Page,
+    retry: RetryPolicy,
+    detached: EventStream<EventDetached>,
+}
+
+impl PageSource {
+    /// Install the hooks for `config` on `page` and drain them. Run it with
+    /// [`run_event_source`](crate::run_event_source) and the same config, e.g. to wrap it in a
+    /// recorder; [`start_event_stream`](crate::start_event_stream) does all this for you.
+    pub async fn attach(page: Page, config: &EventStreamConfig) -> Result<Self, Error> {
+        crate::install_event_hooks(&page, config).await?;
+        crate::configure_network(&page, config).await?;
+        let detached = page
+            .event_listener::<EventDetached>()
+            .await
+            .map_err(Error::EventListener)?;
+        Ok(Self::new(page, config.retry, detached))
+    }
+
+    pub(crate) fn new(
+        page: Page,
+        retry: RetryPolicy,
+        detached: EventStream<EventDetached>,
+    ) -> Self {
+        Self {
+            page,
+            retry,
+            detached,
+        }
+    }
+}
+
+impl EventSource for PageSource {
+    async fn drain(&mut self) -> Result<SourceBatch, Error> {
+        let id = NEXT_DRAIN.fetch_add(1, Ordering::Relaxed);
+        let js = format!("{DRAIN_JS}({id})");
+        let mut s: String = self
+            .retry
+            .run(|| self.page.evaluate_expression(js.as_str()))
+            .await
+            .map_err(Error::DrainJs)?
+            .into_value()
+            .unwrap_or_default();
+        if s.is_empty() {
+            s = "{}".to_string();
+        }
+        let batch: SourceBatch = serde_json::from_str(&s).map_err(Error::ParseJson)?;
+        if !batch.events.is_empty() {
+            tracing::trace!(count = batch.events.len(), "drained page buffer");
+        }
+        if kept_by_page(&batch) {
+            // Best effort: the next drain frees it otherwise.
+            let _ = self
+                .page
+                .evaluate_expression(format!("{ACK_JS}({id})"))
+                .await;
+        }
+        Ok(batch)
+    }
+
+    async fn closed(&mut self) -> StreamEndReason {
+        match self.detached.next().await {
+            Some(ev) => StreamEndReason::TargetDetached(ev.reason.clone()),
+            // The page's event channel closed with it.
+            None => StreamEndReason::PageClosed,
+        }
+    }
+}
+
+// Whether `DRAIN_JS` kept a copy of `batch`, i.e. it carries anything but the in-flight count.
+fn kept_by_page(batch: &SourceBatch) -> bool {
+    !batch.events.is_empty()
+        || !batch.errors.is_empty()
+        || !batch.starts.is_empty()
+        || batch.body_failures > 0
+        || batch.evicted > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::mpsc;
+
+    use crate::Redaction;
+
+    fn event(url: &str, content_type: &str) -> Event {
+        Event::builder()
+            .url(url)
+            .content_type(content_type)
+            .request_header("Authorization", "Bearer secret")
+            .build()
+    }
+
+    #[tokio::test]
+    async fn run_event_source_filters_labels_and_redacts() {
+        let source = ScriptedSource::new([
+            SourceBatch::new([
+                event(
+                    "https://example.com/api/users?token=abc",
+                    "application/json",
+                ),
+                event("https://example.com/api/logo.png", "image/png"),
+                event("https://example.com/static/app.json", "application/json"),
+            ]),
+            SourceBatch::new([event("https://tracker.example/api/hit", "application/json")]),
+            SourceBatch::new([event("https://example.com/api/orders", "application/json")]),
+        ])
+        .end_with(StreamEndReason::TargetDetached("crashed".to_string()));
+        let config = EventStreamConfig {
+            url_substring_filter: Some("/api/".to_string()),
+            content_type_substring_filter: Some("json".to_string()),
+            url_exclude_filters: vec!["tracker.".to_string()],
+            label: Some("tab-1".to_string()),
+            redaction: Redaction::default().query_param("token"),
+            ..Default::default()
+        };
+        let (tx, rx) = mpsc::unbounded();
+
+        let reason = crate::run_event_source(source, config, tx).await.unwrap();
+        assert_eq!(
+            reason,
+            StreamEndReason::TargetDetached("crashed".to_string())
+        );
+        let events: Vec<Event> = rx.collect().await;
+        let urls: Vec<_> = events.iter().map(|e| e.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://example.com/api/users?token=%5BREDACTED%5D",
+                "https://example.com/api/orders",
+            ]
+        );
+        for event in &events {
+            assert_eq!(event.label.as_deref(), Some("tab-1"));
+            assert_eq!(event.request_header("authorization"), Some("[REDACTED]"));
+        }
+    }
+
+    #[tokio::test]
+    async fn scripted_source_closes_after_its_batches() {
+        let mut source = ScriptedSource::from_events([Event::default()]);
+        assert_eq!(source.len(), 1);
+        assert_eq!(source.drain().await.unwrap().events.len(), 1);
+        assert!(source.is_empty());
+        assert!(source.drain().await.unwrap().events.is_empty());
+        assert_eq!(source.closed().await, StreamEndReason::PageClosed);
+    }
+}
@@ -0,0 +1,193 @@
+//! Server-sent events parsing.
+//!
+//! A `text/event-stream` body is either one event for the whole connection, delivered when it
+//! closes, or, with [`split_sse_messages`](crate::EventStreamConfig::split_sse_messages), one
+//! event per message as it arrives. [`parse`] handles both and follows the WHATWG parsing rules:
+//! `data:` lines are joined with newlines, comments and unknown fields are ignored, and a
+//! message without data is not dispatched.
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use serde::Serialize;
+
+use crate::Event;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SseMessage {
+    /// The `event:` field, or `"message"` when absent.
+    pub event: String,
+    pub data: String,
+    /// The last event ID seen so far in the body, as the browser would report it.
+    pub id: Option<String>,
+    /// Reconnection time in milliseconds, if this message set one.
+    pub retry: Option<u64>,
+}
+
+/// Whether `event` is a `text/event-stream` response.
+pub fn is_event_stream(event: &Event) -> bool {
+    event.content_type.as_deref().is_some_and(|ct| {
+        ct.trim_start()
+            .to_ascii_lowercase()
+            .starts_with("text/event-stream")
+    })
+}
+
+/// Parse an event-stream body into its messages. A trailing message that is not terminated by
+/// a blank line is dispatched too, since a captured body has no more data to wait for.
+pub fn parse(body: &str) -> Vec<SseMessage> {
+    let body = body.strip_prefix('\u{feff}').unwrap_or(body);
+    let mut messages = Vec::new();
+    let mut pending = Pending::default();
+    let mut last_id: Option<String> = None;
+
+    for line in lines(body) {
+        if line.is_empty() {
+            messages.extend(pending.dispatch(&last_id));
+            continue;
+        }
+        if line.starts_with(':') {
+            continue; // comment
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => pending.event = value.to_string(),
+            "data" => pending.data.push(value),
+            "id" if !value.contains('\0') => last_id = Some(value.to_string()),
+            "retry" => {
+                if let Ok(ms) = value.parse() {
+                    pending.retry = Some(ms);
+                }
+            }
+            _ => {}
+        }
+    }
+    messages.extend(pending.dispatch(&last_id));
+    messages
+}
+
+// Fields of the message being parsed.
+#[derive(Default)]
+struct Pending<'a> {
+    event: String,
+    data: Vec<&'a str>,
+    retry: Option<u64>,
+}
+
+impl Pending<'_> {
+    // Finish the current message, returning it unless it had no data.
+    fn dispatch(&mut self, id: &Option<String>) -> Option<SseMessage> {
+        let pending = std::mem::take(self);
+        if pending.data.is_empty() {
+            return None;
+        }
+        Some(SseMessage {
+            event: if pending.event.is_empty() {
+                "message".to_string()
+            } else {
+                pending.event
+            },
+            data: pending.data.join("\n"),
+            id: id.clone(),
+            retry: pending.retry,
+        })
+    }
+}
+
+/// The SSE messages in `event`'s body. Empty unless it is a `text/event-stream` response.
+pub fn messages(event: &Event) -> Vec<SseMessage> {
+    if is_event_stream(event) {
+        parse(&event.body)
+    } else {
+        Vec::new()
+    }
+}
+
+/// Forward the SSE messages of every event-stream response in `rx`, each paired with the URL
+/// it came from, dropping all other traffic.
+pub fn sse_messages(
+    mut rx: mpsc::UnboundedReceiver<Event>,
+) -> mpsc::UnboundedReceiver<(String, SseMessage)> {
+    let (tx, out) = mpsc::unbounded();
+    tokio::spawn(async move {
+        while let Some(event) = rx.next().await {
+            for message in messages(&event) {
+                if tx.unbounded_send((event.url.clone(), message)).is_err() {
+                    return; // receiver dropped
+                }
+            }
+        }
+    });
+    out
+}
+
+// Lines end with CRLF, LF or a lone CR.
+fn lines(body: &str) -> impl Iterator<Item = &str> {
+    let mut rest = body;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest.find(['\r', '\n']).unwrap_or(rest.len());
+        let line = &rest[..end];
+        let tail = &rest[end..];
+        rest = tail
+            .strip_prefix("\r\n")
+            .or_else(|| tail.strip_prefix('\r'))
+            .or_else(|| tail.strip_prefix('\n'))
+            .unwrap_or(tail);
+        Some(line)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_follows_the_whatwg_rules() {
+        let body = "\u{feff}: keep-alive\r\nevent: tick\r\ndata: a\r\ndata:b\r\nid: 7\r\nretry: 3000\r\n\r\n\
+                    data\nunknown: x\n\nid: 8\n\nevent: empty\n\ndata: tail";
+        let messages = parse(body);
+        assert_eq!(
+            messages,
+            [
+                SseMessage {
+                    event: "tick".to_string(),
+                    data: "a\nb".to_string(),
+                    id: Some("7".to_string()),
+                    retry: Some(3000),
+                },
+                SseMessage {
+                    event: "message".to_string(),
+                    data: String::new(),
+                    id: Some("7".to_string()),
+                    retry: None,
+                },
+                SseMessage {
+                    event: "message".to_string(),
+                    data: "tail".to_string(),
+                    id: Some("8".to_string()),
+                    retry: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn messages_only_reads_event_streams() {
+        let event = Event::builder()
+            .content_type("Text/Event-Stream; charset=utf-8")
+            .body("data: hi\n\n")
+            .build();
+        assert!(is_event_stream(&event));
+        assert_eq!(messages(&event)[0].data, "hi");
+        let json = Event::builder()
+            .content_type("application/json")
+            .body("data: hi\n\n")
+            .build();
+        assert!(messages(&json).is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+//! Captured events indexed for lookups and reports.
+//!
+//! [`CaptureStore`] indexes events by host, path, status and content type as they are
+//! inserted, so assertion helpers and reports can find and count them without scanning or
+//! building their own indexes. Content types are indexed by media type, without parameters:
+//! `application/json; charset=utf-8` is found under `application/json`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use url::Url;
+
+use crate::Event;
+
+#[derive(Clone, Debug, Default)]
+pub struct CaptureStore {
+    events: Vec<Event>,
+    by_host: HashMap<String, Vec<usize>>,
+    by_path: HashMap<String, Vec<usize>>,
+    by_status: HashMap<u16, Vec<usize>>,
+    by_content_type: HashMap<String, Vec<usize>>,
+}
+
+impl CaptureStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, event: Event) {
+        let i = self.events.len();
+        if let Ok(url) = Url::parse(&event.url) {
+            if let Some(host) = url.host_str() {
+                self.by_host
+                    .entry(host.to_ascii_lowercase())
+                    .or_default()
+                    .push(i);
+            }
+            self.by_path
+                .entry(url.path().to_string())
+                .or_default()
+                .push(i);
+        }
+        if let Some(status) = event.status {
+            self.by_status.entry(status).or_default().push(i);
+        }
+        if let Some(media_type) = event.content_type.as_deref().map(media_type) {
+            self.by_content_type.entry(media_type).or_default().push(i);
+        }
+        self.events.push(event);
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Every event, in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = &Event> {
+        self.events.iter()
+    }
+
+    /// Events to `host`, case-insensitive.
+    pub fn with_host(&self, host: &str) -> impl Iterator<Item = &Event> {
+        self.lookup(self.by_host.get(&host.to_ascii_lowercase()))
+    }
+
+    /// Events whose URL path is exactly `path`, e.g. `/api/users`.
+    pub fn with_path(&self, path: &str) -> impl Iterator<Item = &Event> {
+        self.lookup(self.by_path.get(path))
+    }
+
+    pub fn with_status(&self, status: u16) -> impl Iterator<Item = &Event> {
+        self.lookup(self.by_status.get(&status))
+    }
+
+    /// Events of the media type `content_type`; parameters on either side are ignored.
+    pub fn with_content_type(&self, content_type: &str) -> impl Iterator<Item = &Event> {
+        self.lookup(self.by_content_type.get(&media_type(content_type)))
+    }
+
+    fn lookup<'a>(&'a self, indices: Option<&'a Vec<usize>>) -> impl Iterator<Item = &'a Event> {
+        indices.into_iter().flatten().map(move |&i| &self.events[i])
+    }
+
+    pub fn count_by_host(&self) -> BTreeMap<&str, usize> {
+        counts(&self.by_host)
+    }
+
+    pub fn count_by_path(&self) -> BTreeMap<&str, usize> {
+        counts(&self.by_path)
+    }
+
+    pub fn count_by_status(&self) -> BTreeMap<u16, usize> {
+        self.by_status
+            .iter()
+            .map(|(status, indices)| (*status, indices.len()))
+            .collect()
+    }
+
+    pub fn count_by_content_type(&self) -> BTreeMap<&str, usize> {
+        counts(&self.by_content_type)
+    }
+}
+
+impl Extend<Event> for CaptureStore {
+    fn extend<I: IntoIterator<Item = Event>>(&mut self, events: I) {
+        for event in events {
+            self.insert(event);
+        }
+    }
+}
+
+impl FromIterator<Event> for CaptureStore {
+    fn from_iter<I: IntoIterator<Item = Event>>(events: I) -> Self {
+        let mut store = Self::new();
+        store.extend(events);
+        store
+    }
+}
+
+/// Insert every event from the receiver until the stream closes and return the store.
+pub async fn collect_store(rx: &mut mpsc::UnboundedReceiver<Event>) -> CaptureStore {
+    let mut store = CaptureStore::new();
+    while let Some(event) = rx.next().await {
+        store.insert(event);
+    }
+    store
+}
+
+fn media_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+fn counts(index: &HashMap<String, Vec<usize>>) -> BTreeMap<&str, usize> {
+    index
+        .iter()
+        .map(|(key, indices)| (key.as_str(), indices.len()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> CaptureStore {
+        [
+            (
+                "https://API.example.com/users?page=1",
+                200,
+                "application/json; charset=utf-8",
+            ),
+            ("https://api.example.com/users", 404, "Application/JSON"),
+            ("https://cdn.example.com/app.js", 200, "text/javascript"),
+        ]
+        .into_iter()
+        .map(|(url, status, content_type)| {
+            Event::builder()
+                .url(url)
+                .status(status)
+                .content_type(content_type)
+                .build()
+        })
+        .chain([Event::builder().url("not a url").build()])
+        .collect()
+    }
+
+    #[test]
+    fn lookups_use_the_indexes() {
+        let store = store();
+        assert_eq!(store.len(), 4);
+        assert_eq!(store.with_host("Api.Example.com").count(), 2);
+        assert_eq!(store.with_path("/users").count(), 2);
+        assert_eq!(store.with_path("/users?page=1").count(), 0);
+        let found: Vec<_> = store.with_status(200).map(|ev| ev.url.as_str()).collect();
+        assert_eq!(
+            found,
+            [
+                "https://API.example.com/users?page=1",
+                "https://cdn.example.com/app.js"
+            ]
+        );
+        assert_eq!(
+            store
+                .with_content_type("application/json; charset=latin1")
+                .count(),
+            2
+        );
+        assert_eq!(store.with_host("missing.example.com").count(), 0);
+    }
+
+    #[test]
+    fn counts_are_sorted_by_key() {
+        let store = store();
+        assert_eq!(
+            store.count_by_host().into_iter().collect::<Vec<_>>(),
+            [("api.example.com", 2), ("cdn.example.com", 1)]
+        );
+        assert_eq!(
+            store.count_by_path().into_iter().collect::<Vec<_>>(),
+            [("/app.js", 1), ("/users", 2)]
+        );
+        assert_eq!(
+            store.count_by_status().into_iter().collect::<Vec<_>>(),
+            [(200, 2), (404, 1)]
+        );
+        assert_eq!(
+            store
+                .count_by_content_type()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [("application/json", 2), ("text/javascript", 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn collect_store_reads_until_the_stream_closes() {
+        let (tx, mut rx) = mpsc::unbounded();
+        for event in store().iter() {
+            tx.unbounded_send(event.clone()).unwrap();
+        }
+        drop(tx);
+        assert_eq!(collect_store(&mut rx).await.len(), 4);
+    }
+}
@@ -0,0 +1,558 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use regex::Regex;
+use tokio::time;
+
+use crate::{Error, Event};
+
+// Returned by value and matched immediately; boxing the event would only cost an allocation.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum EventResult {
+    Timeout,
+    StreamClosed,
+    Ok(Event),
+}
+
+impl EventResult {
+    pub fn is_ok(&self) -> bool {
+        matches!(self, EventResult::Ok(_))
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, EventResult::Timeout)
+    }
+
+    pub fn is_stream_closed(&self) -> bool {
+        matches!(self, EventResult::StreamClosed)
+    }
+
+    pub fn event(&self) -> Option<&Event> {
+        match self {
+            EventResult::Ok(event) => Some(event),
+            _ => None,
+        }
+    }
+
+    pub fn into_option(self) -> Option<Event> {
+        match self {
+            EventResult::Ok(event) => Some(event),
+            _ => None,
+        }
+    }
+
+    /// Convert into a `Result`, mapping the non-event outcomes to
+    /// [`Error::Timeout`] and [`Error::StreamClosed`].
+    pub fn into_result(self) -> Result<Event, Error> {
+        match self {
+            EventResult::Ok(event) => Ok(event),
+            EventResult::Timeout => Err(Error::Timeout),
+            EventResult::StreamClosed => Err(Error::StreamClosed),
+        }
+    }
+
+    /// Return the event, panicking with `msg` on timeout or closed stream. Intended for tests.
+    #[track_caller]
+    pub fn expect_event(self, msg: &str) -> Event {
+        match self {
+            EventResult::Ok(event) => event,
+            other => panic!("{msg}: {other:?}"),
+        }
+    }
+}
+
+impl From<EventResult> for Option<Event> {
+    fn from(result: EventResult) -> Self {
+        result.into_option()
+    }
+}
+
+impl From<EventResult> for Result<Event, Error> {
+    fn from(result: EventResult) -> Self {
+        result.into_result()
+    }
+}
+
+// About 30 years, as far as tokio itself ever sleeps.
+const FAR_FUTURE: Duration = Duration::from_secs(86400 * 365 * 30);
+
+// `start + duration`, saturating far in the future instead of overflowing for huge durations
+// such as `Duration::MAX`.
+pub(crate) fn later(start: time::Instant, duration: Duration) -> time::Instant {
+    start
+        .checked_add(duration)
+        .unwrap_or_else(|| start + FAR_FUTURE)
+}
+
+/// Point in time by which a wait must finish.
+///
+/// Every wait helper accepts `impl Into<Deadline>`: a `Duration` starts a fresh budget at the
+/// call, while passing the same `Deadline` to several calls makes them share one budget.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline(time::Instant);
+
+impl Deadline {
+    /// Deadline `timeout` from now.
+    pub fn after(timeout: Duration) -> Self {
+        Deadline(later(time::Instant::now(), timeout))
+    }
+
+    pub fn at(instant: time::Instant) -> Self {
+        Deadline(instant)
+    }
+
+    pub fn instant(&self) -> time::Instant {
+        self.0
+    }
+
+    /// Time left before the deadline, zero once it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(time::Instant::now())
+    }
+
+    pub fn has_elapsed(&self) -> bool {
+        time::Instant::now() >= self.0
+    }
+}
+
+impl From<Duration> for Deadline {
+    fn from(timeout: Duration) -> Self {
+        Deadline::after(timeout)
+    }
+}
+
+impl From<time::Instant> for Deadline {
+    fn from(instant: time::Instant) -> Self {
+        Deadline(instant)
+    }
+}
+
+impl From<std::time::Instant> for Deadline {
+    fn from(instant: std::time::Instant) -> Self {
+        Deadline(time::Instant::from_std(instant))
+    }
+}
+
+/// Outcome of [`wait_for_events`]; every variant carries the events received so far.
+#[derive(Debug)]
+pub enum EventsResult {
+    /// All `n` requested events arrived.
+    Complete(Vec<Event>),
+    /// The timeout expired after fewer than `n` events.
+    Timeout(Vec<Event>),
+    /// The stream closed after fewer than `n` events.
+    StreamClosed(Vec<Event>),
+}
+
+impl EventsResult {
+    pub fn is_complete(&self) -> bool {
+        matches!(self, EventsResult::Complete(_))
+    }
+
+    pub fn events(&self) -> &[Event] {
+        match self {
+            EventsResult::Complete(events)
+            | EventsResult::Timeout(events)
+            | EventsResult::StreamClosed(events) => events,
+        }
+    }
+
+    /// The received events, regardless of how the wait ended.
+    pub fn into_events(self) -> Vec<Event> {
+        match self {
+            EventsResult::Complete(events)
+            | EventsResult::Timeout(events)
+            | EventsResult::StreamClosed(events) => events,
+        }
+    }
+}
+
+/// Outcome of [`wait_until`]. Unlike [`EventResult`], non-matching events seen during the
+/// wait are kept, so a failed expectation can report what actually arrived.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum WaitOutcome {
+    Matched { event: Event, skipped: Vec<Event> },
+    TimedOut(Vec<Event>),
+    StreamClosed(Vec<Event>),
+}
+
+impl WaitOutcome {
+    pub fn is_matched(&self) -> bool {
+        matches!(self, WaitOutcome::Matched { .. })
+    }
+
+    pub fn matched(&self) -> Option<&Event> {
+        match self {
+            WaitOutcome::Matched { event, .. } => Some(event),
+            _ => None,
+        }
+    }
+
+    /// Events received before the match, or before the wait gave up.
+    pub fn skipped(&self) -> &[Event] {
+        match self {
+            WaitOutcome::Matched { skipped, .. }
+            | WaitOutcome::TimedOut(skipped)
+            | WaitOutcome::StreamClosed(skipped) => skipped,
+        }
+    }
+}
+
+impl From<WaitOutcome> for EventResult {
+    fn from(outcome: WaitOutcome) -> Self {
+        match outcome {
+            WaitOutcome::Matched { event, .. } => EventResult::Ok(event),
+            WaitOutcome::TimedOut(_) => EventResult::Timeout,
+            WaitOutcome::StreamClosed(_) => EventResult::StreamClosed,
+        }
+    }
+}
+
+/// URL matcher accepted by [`wait_for_url`]: a plain substring or a regular expression.
+#[derive(Clone, Debug)]
+pub enum UrlPattern {
+    Substring(String),
+    Regex(Regex),
+}
+
+impl UrlPattern {
+    pub fn matches(&self, url: &str) -> bool {
+        match self {
+            UrlPattern::Substring(s) => url.contains(s.as_str()),
+            UrlPattern::Regex(re) => re.is_match(url),
+        }
+    }
+}
+
+impl From<&str> for UrlPattern {
+    fn from(s: &str) -> Self {
+        UrlPattern::Substring(s.to_string())
+    }
+}
+
+impl From<String> for UrlPattern {
+    fn from(s: String) -> Self {
+        UrlPattern::Substring(s)
+    }
+}
+
+impl From<Regex> for UrlPattern {
+    fn from(re: Regex) -> Self {
+        UrlPattern::Regex(re)
+    }
+}
+
+/// Wait for the next event from the receiver with a timeout.
+/// Returns `EventResult::Ok(event)` if an event is received, `EventResult::StreamClosed` if the
+/// stream is closed, or `EventResult::Timeout` if the timeout expires before an event is received.
+pub async fn wait_for_event_with_timeout(
+    rx: &mut mpsc::UnboundedReceiver<Event>,
+    timeout: impl Into<Deadline>,
+) -> EventResult {
+    match time::timeout_at(timeout.into().instant(), rx.next()).await {
+        Ok(Some(event)) => EventResult::Ok(event),
+        Ok(None) => EventResult::StreamClosed,
+        Err(_) => EventResult::Timeout,
+    }
+}
+
+/// Wait for the first event whose URL matches `pattern`, skipping any others.
+/// The timeout covers the whole wait, not each received event.
+pub async fn wait_for_url(
+    rx: &mut mpsc::UnboundedReceiver<Event>,
+    pattern: impl Into<UrlPattern>,
+    timeout: impl Into<Deadline>,
+) -> EventResult {
+    let pattern = pattern.into();
+    wait_for_match(rx, timeout.into(), |ev| pattern.matches(&ev.url)).await
+}
+
+/// Wait for the first event whose content-type contains `content_type`, skipping any others.
+/// Useful when endpoint URLs are unstable but the payload type is not.
+pub async fn wait_for_content_type(
+    rx: &mut mpsc::UnboundedReceiver<Event>,
+    content_type: &str,
+    timeout: impl Into<Deadline>,
+) -> EventResult {
+    wait_for_match(rx, timeout.into(), |ev| {
+        ev.content_type
+            .as_deref()
+            .is_some_and(|ct| ct.contains(content_type))
+    })
+    .await
+}
+
+/// Wait for the first event whose body parses as JSON and satisfies `predicate`.
+/// Events with unparsable bodies are skipped like non-matching ones.
+pub async fn wait_for_json(
+    rx: &mut mpsc::UnboundedReceiver<Event>,
+    timeout: impl Into<Deadline>,
+    mut predicate: impl FnMut(&serde_json::Value) -> bool,
+) -> EventResult {
+    wait_for_match(rx, timeout.into(), |ev| {
+        serde_json::from_str::<serde_json::Value>(&ev.body)
+            .map(|v| predicate(&v))
+            .unwrap_or(false)
+    })
+    .await
+}
+
+/// Wait for the first event satisfying `predicate`, keeping every other event received meanwhile.
+/// On timeout or stream close the collected events are returned instead of being discarded.
+pub async fn wait_until(
+    rx: &mut mpsc::UnboundedReceiver<Event>,
+    timeout: impl Into<Deadline>,
+    mut predicate: impl FnMut(&Event) -> bool,
+) -> WaitOutcome {
+    let deadline = timeout.into().instant();
+    let mut skipped = Vec::new();
+    loop {
+        match time::timeout_at(deadline, rx.next()).await {
+            Ok(Some(event)) if predicate(&event) => {
+                return WaitOutcome::Matched { event, skipped };
+            }
+            Ok(Some(event)) => skipped.push(event),
+            Ok(None) => return WaitOutcome::StreamClosed(skipped),
+            Err(_) => return WaitOutcome::TimedOut(skipped),
+        }
+    }
+}
+
+// Consume events until one satisfies `predicate`, the stream closes or the timeout expires.
+async fn wait_for_match(
+    rx: &mut mpsc::UnboundedReceiver<Event>,
+    deadline: Deadline,
+    mut predicate: impl FnMut(&Event) -> bool,
+) -> EventResult {
+    let deadline = deadline.instant();
+    loop {
+        match time::timeout_at(deadline, rx.next()).await {
+            Ok(Some(event)) if predicate(&event) => return EventResult::Ok(event),
+            Ok(Some(_)) => continue,
+            Ok(None) => return EventResult::StreamClosed,
+            Err(_) => return EventResult::Timeout,
+        }
+    }
+}
+
+/// Collect the events satisfying `matches` until none has arrived for `idle` (network-idle
+/// detection) or until `timeout` expires, whichever comes first. Also returns early if the
+/// stream closes. Other events are dropped and do not restart the quiet period, so polling or
+/// analytics traffic does not keep the wait alive; pass `|_| true` to count every event.
+pub async fn collect_until_network_idle(
+    rx: &mut mpsc::UnboundedReceiver<Event>,
+    idle: Duration,
+    timeout: impl Into<Deadline>,
+    matches: impl Fn(&Event) -> bool,
+) -> Vec<Event> {
+    let deadline = timeout.into().instant();
+    let mut events = Vec::new();
+    let mut quiet_until = later(time::Instant::now(), idle).min(deadline);
+    loop {
+        match time::timeout_at(quiet_until, rx.next()).await {
+            Ok(Some(event)) if matches(&event) => {
+                events.push(event);
+                quiet_until = later(time::Instant::now(), idle).min(deadline);
+            }
+            Ok(Some(_)) => continue,
+            Ok(None) | Err(_) => return events,
+        }
+    }
+}
+
+/// Wait for `n` events, returning whatever arrived if the timeout expires or the stream closes first.
+pub async fn wait_for_events(
+    rx: &mut mpsc::UnboundedReceiver<Event>,
+    n: usize,
+    timeout: impl Into<Deadline>,
+) -> EventsResult {
+    let deadline = timeout.into().instant();
+    // `n` may be a "no limit" such as `usize::MAX`.
+    let mut events = Vec::with_capacity(n.min(1024));
+    while events.len() < n {
+        match time::timeout_at(deadline, rx.next()).await {
+            Ok(Some(event)) => events.push(event),
+            Ok(None) => return EventsResult::StreamClosed(events),
+            Err(_) => return EventsResult::Timeout(events),
+        }
+    }
+    EventsResult::Complete(events)
+}
+
+/// Take the next already-received event without waiting. Returns `None` if nothing is
+/// buffered or the stream is closed.
+pub fn try_next_event(rx: &mut mpsc::UnboundedReceiver<Event>) -> Option<Event> {
+    rx.try_next().ok().flatten()
+}
+
+/// Take every already-received event without waiting.
+pub fn drain(rx: &mut mpsc::UnboundedReceiver<Event>) -> Vec<Event> {
+    std::iter::from_fn(|| try_next_event(rx)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn huge_timeouts_saturate() {
+        let deadline = Deadline::after(Duration::MAX);
+        assert!(deadline.remaining() > Duration::from_secs(86400 * 365));
+    }
+
+    #[tokio::test]
+    async fn waiting_for_unbounded_counts_does_not_preallocate_them() {
+        let (tx, mut rx) = mpsc::unbounded();
+        drop(tx);
+        let result = wait_for_events(&mut rx, usize::MAX, Duration::from_secs(1)).await;
+        assert!(matches!(result, EventsResult::StreamClosed(events) if events.is_empty()));
+    }
+
+    fn event(url: &str, content_type: &str, body: &str) -> Event {
+        Event::builder()
+            .url(url)
+            .content_type(content_type)
+            .body(body)
+            .build()
+    }
+
+    fn stream(events: Vec<Event>) -> mpsc::UnboundedReceiver<Event> {
+        let (tx, rx) = mpsc::unbounded();
+        for event in events {
+            tx.unbounded_send(event).unwrap();
+        }
+        rx
+    }
+
+    const SHORT: Duration = Duration::from_millis(20);
+
+    #[tokio::test]
+    async fn matching_waits_skip_other_events() {
+        let events = || {
+            vec![
+                event("https://example.com/app.js", "text/javascript", "x"),
+                event("https://example.com/api/users", "application/json", "[1]"),
+                event(
+                    "https://example.com/api/ok",
+                    "application/json",
+                    r#"{"ok":true}"#,
+                ),
+            ]
+        };
+
+        let (tx, mut rx) = mpsc::unbounded();
+        for event in events() {
+            tx.unbounded_send(event).unwrap();
+        }
+        let found = wait_for_url(&mut rx, "/api/", SHORT).await;
+        assert_eq!(found.event().unwrap().url, "https://example.com/api/users");
+        let found = wait_for_url(&mut rx, Regex::new(r"/ok$").unwrap(), SHORT).await;
+        assert!(found.is_ok());
+        // Nothing left, and the sender is still alive.
+        assert!(wait_for_url(&mut rx, "/api/", SHORT).await.is_timeout());
+        drop(tx);
+        assert!(
+            wait_for_url(&mut rx, "/api/", SHORT)
+                .await
+                .is_stream_closed()
+        );
+
+        let mut rx = stream(events());
+        let found = wait_for_content_type(&mut rx, "json", SHORT).await;
+        assert_eq!(found.event().unwrap().body, "[1]");
+
+        // Bodies that are not JSON, or do not satisfy the predicate, are skipped.
+        let mut rx = stream(events());
+        let found = wait_for_json(&mut rx, SHORT, |v| v["ok"] == true).await;
+        assert_eq!(found.event().unwrap().url, "https://example.com/api/ok");
+
+        let mut rx = stream(events());
+        assert!(wait_for_event_with_timeout(&mut rx, SHORT).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn wait_until_keeps_what_it_skipped() {
+        let mut rx = stream(vec![
+            event("https://example.com/a", "text/plain", ""),
+            event("https://example.com/b", "text/plain", ""),
+        ]);
+        let outcome = wait_until(&mut rx, SHORT, |ev| ev.url.ends_with("/b")).await;
+        assert_eq!(outcome.matched().unwrap().url, "https://example.com/b");
+        assert_eq!(outcome.skipped().len(), 1);
+
+        let mut rx = stream(vec![event("https://example.com/a", "text/plain", "")]);
+        let outcome = wait_until(&mut rx, SHORT, |_| false).await;
+        assert!(matches!(outcome, WaitOutcome::StreamClosed(ref skipped) if skipped.len() == 1));
+        assert!(EventResult::from(outcome).is_stream_closed());
+    }
+
+    #[tokio::test]
+    async fn wait_for_events_reports_how_it_ended() {
+        let (tx, mut rx) = mpsc::unbounded();
+        for url in ["https://example.com/1", "https://example.com/2"] {
+            tx.unbounded_send(event(url, "text/plain", "")).unwrap();
+        }
+        let deadline = Deadline::after(SHORT);
+        assert!(wait_for_events(&mut rx, 1, deadline).await.is_complete());
+        let result = wait_for_events(&mut rx, 2, deadline).await;
+        assert!(matches!(result, EventsResult::Timeout(ref events) if events.len() == 1));
+        tx.unbounded_send(event("https://example.com/3", "text/plain", ""))
+            .unwrap();
+        assert_eq!(
+            try_next_event(&mut rx).unwrap().url,
+            "https://example.com/3"
+        );
+        assert!(drain(&mut rx).is_empty());
+    }
+
+    #[tokio::test]
+    async fn network_idle_ignores_events_that_do_not_match() {
+        let (tx, mut rx) = mpsc::unbounded();
+        tx.unbounded_send(event("https://example.com/api/a", "application/json", ""))
+            .unwrap();
+        // A heartbeat that never stops would keep any event-counting wait alive.
+        let heartbeat = tokio::spawn(async move {
+            loop {
+                let ping = event("https://example.com/heartbeat", "text/plain", "");
+                if tx.unbounded_send(ping).is_err() {
+                    return;
+                }
+                time::sleep(Duration::from_millis(2)).await;
+            }
+        });
+        let started = time::Instant::now();
+        let events = collect_until_network_idle(&mut rx, SHORT, Duration::from_secs(10), |ev| {
+            ev.url.contains("/api/")
+        })
+        .await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(events.len(), 1);
+        heartbeat.abort();
+    }
+
+    #[tokio::test]
+    async fn network_idle_stops_at_the_timeout_or_when_the_stream_closes() {
+        let (tx, mut rx) = mpsc::unbounded();
+        let sender = tokio::spawn(async move {
+            loop {
+                let ev = event("https://example.com/api/poll", "application/json", "");
+                if tx.unbounded_send(ev).is_err() {
+                    return;
+                }
+                time::sleep(Duration::from_millis(2)).await;
+            }
+        });
+        let events =
+            collect_until_network_idle(&mut rx, Duration::from_secs(10), SHORT * 5, |_| true).await;
+        assert!(!events.is_empty());
+        sender.abort();
+
+        let mut rx = stream(vec![event("https://example.com/", "text/html", "")]);
+        let idle = Duration::from_secs(10);
+        let events = collect_until_network_idle(&mut rx, idle, idle, |_| true).await;
+        assert_eq!(events.len(), 1);
+    }
+}
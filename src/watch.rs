@@ -0,0 +1,366 @@
+//! Following CDP network events the page hooks cannot see.
+//!
+//! Cache sources, priorities, blocked requests, certificate warnings and the cookie jar all
+//! need what the browser reports about requests. One [`NetworkWatcher`] per stream subscribes to
+//! the `Network.*` request events once, keeps a [`Request`] record for each request in flight,
+//! and hands every notice to the [`Follower`]s the config asked for.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use chromiumoxide::cdp::browser_protocol::network::{
+    EventLoadingFailed, EventLoadingFinished, EventRequestServedFromCache, EventRequestWillBeSent,
+    EventRequestWillBeSentExtraInfo, EventResourceChangedPriority, EventResponseReceived,
+    EventResponseReceivedExtraInfo, RequestId,
+};
+use chromiumoxide::page::Page;
+use futures::StreamExt;
+use futures::stream::{self, BoxStream};
+use tokio::task::JoinHandle;
+
+use crate::Error;
+
+// Requests and sockets that never finish would otherwise pile up: once this many are followed,
+// the lot is forgotten. A page in that state has bigger problems than a missed tag.
+const MAX_IN_FLIGHT: usize = 4096;
+
+// Finished requests remembered for events not drained yet; the oldest are forgotten first.
+const MAX_PENDING: usize = 256;
+
+/// A background CDP watch for one page. Stops when dropped.
+pub(crate) struct Watcher {
+    task: JoinHandle<()>,
+}
+
+impl Watcher {
+    pub(crate) fn spawn(task: impl Future<Output = ()> + Send + 'static) -> Self {
+        Self {
+            task: tokio::spawn(task),
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Things followed until they finish, bounded by `MAX_IN_FLIGHT`.
+#[derive(Debug)]
+pub(crate) struct InFlight<K, V>(HashMap<K, V>);
+
+impl<K: Eq + Hash, V> Default for InFlight<K, V> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+impl<K: Eq + Hash, V> InFlight<K, V> {
+    pub(crate) fn insert(&mut self, key: K, value: V) {
+        if self.0.len() == MAX_IN_FLIGHT && !self.0.contains_key(&key) {
+            self.0.clear();
+        }
+        self.0.insert(key, value);
+    }
+
+    pub(crate) fn get(&self, key: &K) -> Option<&V> {
+        self.0.get(key)
+    }
+
+    pub(crate) fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.0.get_mut(key)
+    }
+
+    pub(crate) fn remove(&mut self, key: &K) -> Option<V> {
+        self.0.remove(key)
+    }
+}
+
+/// What CDP told about finished requests, waiting to be matched to their events.
+///
+/// Page scripts and CDP share no request id, so values are matched by URL, in the order the
+/// requests finished. Requests to the URL that started before the event's own are skipped and
+/// forgotten: they are ones the stream did not capture, such as the document itself or an
+/// image at the same URL.
+#[derive(Debug)]
+pub(crate) struct UrlQueue<T>(Mutex<VecDeque<Finished<T>>>);
+
+#[derive(Debug)]
+struct Finished<T> {
+    url: String,
+    started_at: Option<f64>,
+    value: T,
+}
+
+impl<T> Default for UrlQueue<T> {
+    fn default() -> Self {
+        Self(Mutex::new(VecDeque::new()))
+    }
+}
+
+impl<T> UrlQueue<T> {
+    /// Remember `value` for the request to `url` that started at `started_at` (wall clock,
+    /// milliseconds since the Unix epoch).
+    pub(crate) fn push(&self, url: String, started_at: Option<f64>, value: T) {
+        let mut finished = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if finished.len() == MAX_PENDING {
+            finished.pop_front();
+        }
+        finished.push_back(Finished {
+            url,
+            started_at,
+            value,
+        });
+    }
+
+    /// The value of the oldest request to `url` that started no earlier than `started_at`, the
+    /// event's own start.
+    pub(crate) fn take(&self, url: &str, started_at: Option<f64>) -> Option<T> {
+        let mut finished = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(started_at) = started_at {
+            finished.retain(|f| f.url != url || f.started_at.is_none_or(|s| s >= started_at));
+        }
+        let i = finished.iter().position(|f| f.url == url)?;
+        finished.remove(i).map(|f| f.value)
+    }
+}
+
+/// What the watcher knows about a request in flight.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Request {
+    pub(crate) url: String,
+    pub(crate) method: String,
+    /// URL of the document that made the request; empty if CDP reports none.
+    pub(crate) document_url: String,
+    /// Wall-clock time the request was issued, in milliseconds since the Unix epoch.
+    pub(crate) started_at: f64,
+    pub(crate) initial_priority: String,
+    /// The priority as of the latest `Network.resourceChangedPriority`.
+    pub(crate) priority: String,
+    /// `Network.requestServedFromCache` was seen; only memory-cache hits lack a flag of their
+    /// own on the response.
+    pub(crate) served_from_cache: bool,
+}
+
+impl Request {
+    fn new(event: &EventRequestWillBeSent) -> Self {
+        let priority = event.request.initial_priority.as_ref().to_string();
+        Self {
+            url: event.request.url.clone(),
+            method: event.request.method.clone(),
+            document_url: event.document_url.clone(),
+            started_at: *event.wall_time.inner() * 1000.0,
+            initial_priority: priority.clone(),
+            priority,
+            served_from_cache: false,
+        }
+    }
+
+    // The next hop of a redirect. The page sees the request under its last URL, but it still
+    // started when the first hop did, with the priority that one was given.
+    fn redirect(&mut self, event: &EventRequestWillBeSent) {
+        self.url.clone_from(&event.request.url);
+        self.method.clone_from(&event.request.method);
+        self.priority = event.request.initial_priority.as_ref().to_string();
+        self.served_from_cache = false;
+    }
+}
+
+pub(crate) enum Notice {
+    Sent(Arc<EventRequestWillBeSent>),
+    SentExtraInfo(Arc<EventRequestWillBeSentExtraInfo>),
+    ServedFromCache(Arc<EventRequestServedFromCache>),
+    PriorityChanged(Arc<EventResourceChangedPriority>),
+    Response(Arc<EventResponseReceived>),
+    ResponseExtraInfo(Arc<EventResponseReceivedExtraInfo>),
+    Finished(Arc<EventLoadingFinished>),
+    Failed(Arc<EventLoadingFailed>),
+}
+
+impl Notice {
+    fn request_id(&self) -> &RequestId {
+        match self {
+            Self::Sent(e) => &e.request_id,
+            Self::SentExtraInfo(e) => &e.request_id,
+            Self::ServedFromCache(e) => &e.request_id,
+            Self::PriorityChanged(e) => &e.request_id,
+            Self::Response(e) => &e.request_id,
+            Self::ResponseExtraInfo(e) => &e.request_id,
+            Self::Finished(e) => &e.request_id,
+            Self::Failed(e) => &e.request_id,
+        }
+    }
+}
+
+/// A feature fed by the [`NetworkWatcher`].
+pub(crate) trait Follower: Send {
+    /// Called with every notice, after the watcher updated its record of the request.
+    /// `request` is `None` for requests seen only after they started.
+    fn follow(&mut self, notice: &Notice, request: Option<&Request>);
+}
+
+/// The request records and followers of one stream.
+#[derive(Default)]
+pub(crate) struct Requests {
+    in_flight: InFlight<RequestId, Request>,
+    followers: Vec<Box<dyn Follower>>,
+}
+
+impl Requests {
+    pub(crate) fn new(followers: Vec<Box<dyn Follower>>) -> Self {
+        Self {
+            in_flight: InFlight::default(),
+            followers,
+        }
+    }
+
+    pub(crate) fn handle(&mut self, notice: &Notice) {
+        let id = notice.request_id();
+        match notice {
+            // Redirects reuse the request id for every hop.
+            Notice::Sent(event) => match self.in_flight.get_mut(id) {
+                Some(request) if event.redirect_response.is_some() => request.redirect(event),
+                _ => self.in_flight.insert(id.clone(), Request::new(event)),
+            },
+            Notice::ServedFromCache(_) => {
+                if let Some(request) = self.in_flight.get_mut(id) {
+                    request.served_from_cache = true;
+                }
+            }
+            Notice::PriorityChanged(event) => {
+                if let Some(request) = self.in_flight.get_mut(id) {
+                    request.priority = event.new_priority.as_ref().to_string();
+                }
+            }
+            _ => {}
+        }
+        let request = self.in_flight.get(id);
+        for follower in &mut self.followers {
+            follower.follow(notice, request);
+        }
+        if matches!(notice, Notice::Finished(_) | Notice::Failed(_)) {
+            self.in_flight.remove(id);
+        }
+    }
+}
+
+/// The shared network watch, see the [module docs](self).
+pub(crate) struct NetworkWatcher;
+
+impl NetworkWatcher {
+    /// Start feeding `followers`. `None` when there are none, so nothing is subscribed.
+    pub(crate) async fn start(
+        page: &Page,
+        followers: Vec<Box<dyn Follower>>,
+    ) -> Result<Option<Watcher>, Error> {
+        if followers.is_empty() {
+            return Ok(None);
+        }
+        let notices: Vec<BoxStream<'static, Notice>> = vec![
+            listen(page, Notice::Sent).await?,
+            listen(page, Notice::SentExtraInfo).await?,
+            listen(page, Notice::ServedFromCache).await?,
+            listen(page, Notice::PriorityChanged).await?,
+            listen(page, Notice::Response).await?,
+            listen(page, Notice::ResponseExtraInfo).await?,
+            listen(page, Notice::Finished).await?,
+            listen(page, Notice::Failed).await?,
+        ];
+        let mut notices = stream::select_all(notices);
+        let mut requests = Requests::new(followers);
+        Ok(Some(Watcher::spawn(async move {
+            while let Some(notice) = notices.next().await {
+                requests.handle(&notice);
+            }
+        })))
+    }
+}
+
+async fn listen<T>(
+    page: &Page,
+    notice: fn(Arc<T>) -> Notice,
+) -> Result<BoxStream<'static, Notice>, Error>
+where
+    T: chromiumoxide::cdp::IntoEventKind + Unpin + std::fmt::Debug + Send + Sync + 'static,
+{
+    let events = page
+        .event_listener::<T>()
+        .await
+        .map_err(Error::EventListener)?;
+    Ok(events.map(notice).boxed())
+}
+
+// CDP events as the browser would send them, for the followers' tests.
+#[cfg(test)]
+pub(crate) mod events {
+    use serde_json::json;
+
+    use super::*;
+
+    fn response_json(url: &str, status: u16, from_disk_cache: bool) -> serde_json::Value {
+        json!({
+            "url": url,
+            "status": status,
+            "statusText": "",
+            "headers": {},
+            "mimeType": "text/plain",
+            "charset": "",
+            "connectionReused": false,
+            "connectionId": 0,
+            "encodedDataLength": 0,
+            "securityState": "secure",
+            "fromDiskCache": from_disk_cache,
+        })
+    }
+
+    /// Request `id` to `url`, issued at `wall_time` seconds since the Unix epoch. With
+    /// `redirected_from`, a hop of a redirect from that URL.
+    pub(crate) fn sent(
+        id: &str,
+        url: &str,
+        wall_time: f64,
+        priority: &str,
+        redirected_from: Option<&str>,
+    ) -> Notice {
+        let mut event = json!({
+            "requestId": id,
+            "loaderId": "loader",
+            "documentURL": "https://example.com/",
+            "request": {
+                "url": url,
+                "method": "GET",
+                "headers": {},
+                "initialPriority": priority,
+                "referrerPolicy": "no-referrer",
+            },
+            "timestamp": 0,
+            "wallTime": wall_time,
+            "initiator": {"type": "script"},
+            "redirectHasExtraInfo": false,
+        });
+        if let Some(from) = redirected_from {
+            event["redirectResponse"] = response_json(from, 302, false);
+        }
+        Notice::Sent(Arc::new(serde_json::from_value(event).unwrap()))
+    }
+
+    pub(crate) fn response(id: &str, url: &str, from_disk_cache: bool) -> Notice {
+        let event = json!({
+            "requestId": id,
+            "loaderId": "loader",
+            "timestamp": 0,
+            "type": "Fetch",
+            "response": response_json(url, 200, from_disk_cache),
+            "hasExtraInfo": false,
+        });
+        Notice::Response(Arc::new(serde_json::from_value(event).unwrap()))
+    }
+
+    pub(crate) fn finished(id: &str) -> Notice {
+        let event = json!({"requestId": id, "timestamp": 0, "encodedDataLength": 0});
+        Notice::Finished(Arc::new(serde_json::from_value(event).unwrap()))
+    }
+}
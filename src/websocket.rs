@@ -0,0 +1,160 @@
+//! WebSocket handshakes.
+//!
+//! Sockets are opened outside fetch and XHR, so the hooks never see them. With
+//! [`EventStreamConfig::capture_websocket_handshakes`](crate::EventStreamConfig::capture_websocket_handshakes)
+//! set, the stream follows the CDP `Network.webSocket*` events and reports each socket the page
+//! opens as a [`WebSocketHandshake`] on the receiver from
+//! [`EventStreamHandle::take_websocket_handshakes`](crate::EventStreamHandle::take_websocket_handshakes):
+//! its URL, the upgrade request and response headers, and the status, `101` when the upgrade
+//! succeeded. Frames are not captured.
+
+use std::sync::Arc;
+
+use chromiumoxide::cdp::browser_protocol::network::{
+    EventWebSocketClosed, EventWebSocketCreated, EventWebSocketHandshakeResponseReceived,
+    EventWebSocketWillSendHandshakeRequest, Headers,
+};
+use chromiumoxide::page::Page;
+use futures::StreamExt;
+use futures::stream;
+use serde::{Deserialize, Serialize};
+
+use crate::watch::{InFlight, Watcher};
+use crate::{CaptureFilter, Error, EventStreamConfig, Header, handle};
+
+/// A WebSocket the page opened, reported with
+/// [`EventStreamConfig::capture_websocket_handshakes`](crate::EventStreamConfig::capture_websocket_handshakes) set.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WebSocketHandshake {
+    pub url: String,
+    /// Headers of the upgrade request, names lower-cased.
+    #[serde(rename = "requestHeaders", default)]
+    pub request_headers: Vec<Header>,
+    /// `101` for an accepted upgrade. `None` when the socket closed before a response arrived,
+    /// e.g. because the server could not be reached.
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// Headers of the upgrade response, names lower-cased.
+    #[serde(rename = "responseHeaders", default)]
+    pub response_headers: Vec<Header>,
+    /// Wall-clock time the upgrade request was sent, in milliseconds since the Unix epoch.
+    #[serde(rename = "startedAt", default)]
+    pub started_at: Option<f64>,
+    /// The [`EventStreamConfig::label`] of the stream.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+enum Notice {
+    Created(Arc<EventWebSocketCreated>),
+    Request(Arc<EventWebSocketWillSendHandshakeRequest>),
+    Response(Arc<EventWebSocketHandshakeResponseReceived>),
+    Closed(Arc<EventWebSocketClosed>),
+}
+
+/// Reports the handshakes of the sockets a page opens.
+pub(crate) struct WebSocketWatcher;
+
+impl WebSocketWatcher {
+    pub(crate) async fn start(
+        page: &Page,
+        config: Arc<EventStreamConfig>,
+        filter: Arc<CaptureFilter>,
+        shared: Arc<handle::Shared>,
+    ) -> Result<Watcher, Error> {
+        let created = page
+            .event_listener::<EventWebSocketCreated>()
+            .await
+            .map_err(Error::EventListener)?;
+        let requests = page
+            .event_listener::<EventWebSocketWillSendHandshakeRequest>()
+            .await
+            .map_err(Error::EventListener)?;
+        let responses = page
+            .event_listener::<EventWebSocketHandshakeResponseReceived>()
+            .await
+            .map_err(Error::EventListener)?;
+        let closed = page
+            .event_listener::<EventWebSocketClosed>()
+            .await
+            .map_err(Error::EventListener)?;
+        let mut notices = stream::select(
+            stream::select(created.map(Notice::Created), requests.map(Notice::Request)),
+            stream::select(responses.map(Notice::Response), closed.map(Notice::Closed)),
+        );
+        Ok(Watcher::spawn(async move {
+            let mut pending = InFlight::default();
+            while let Some(notice) = notices.next().await {
+                let (mut handshake, response) = match notice {
+                    Notice::Created(event) => {
+                        if filter.matches_url(&event.url, None) {
+                            let handshake = WebSocketHandshake {
+                                url: config.redaction.redact_url(&event.url),
+                                request_headers: Vec::new(),
+                                status: None,
+                                response_headers: Vec::new(),
+                                started_at: None,
+                                label: config.label.clone(),
+                            };
+                            pending.insert(event.request_id.clone(), handshake);
+                        }
+                        continue;
+                    }
+                    Notice::Request(event) => {
+                        if let Some(handshake) = pending.get_mut(&event.request_id) {
+                            handshake.request_headers = header_list(&event.request.headers);
+                            handshake.started_at = Some(*event.wall_time.inner() * 1000.0);
+                        }
+                        continue;
+                    }
+                    Notice::Response(event) => match pending.remove(&event.request_id) {
+                        Some(handshake) => (handshake, Some(event)),
+                        None => continue,
+                    },
+                    Notice::Closed(event) => match pending.remove(&event.request_id) {
+                        Some(handshake) => (handshake, None),
+                        None => continue,
+                    },
+                };
+                if let Some(event) = response {
+                    let response = &event.response;
+                    handshake.status = u16::try_from(response.status).ok();
+                    handshake.response_headers = header_list(&response.headers);
+                    // The headers as actually sent, cookies included, when the browser reports them.
+                    if let Some(headers) = &response.request_headers {
+                        handshake.request_headers = header_list(headers);
+                    }
+                }
+                config
+                    .redaction
+                    .redact_headers(&mut handshake.request_headers);
+                config
+                    .redaction
+                    .redact_headers(&mut handshake.response_headers);
+                tracing::debug!(url = %handshake.url, status = ?handshake.status, "websocket opened");
+                shared.send_websocket_handshake(handshake);
+            }
+        }))
+    }
+}
+
+// CDP joins repeated headers with newlines.
+pub(crate) fn header_list(headers: &Headers) -> Vec<Header> {
+    let Some(headers) = headers.inner().as_object() else {
+        return Vec::new();
+    };
+    headers
+        .iter()
+        .flat_map(|(name, value)| {
+            let name = name.to_ascii_lowercase();
+            value
+                .as_str()
+                .unwrap_or_default()
+                .split('\n')
+                .map(move |value| Header {
+                    name: name.clone(),
+                    value: value.to_string(),
+                })
+        })
+        .collect()
+}
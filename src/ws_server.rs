@@ -0,0 +1,114 @@
+//! Serve captured events over a local WebSocket, enabled by the `ws-server` feature.
+//!
+//! [`serve_websocket`] binds a TCP address and sends every event from a receiver to every
+//! connected client as a JSON text frame, in the same format as the JSONL export. Dashboards,
+//! notebooks and other non-Rust tooling can tail a live capture with any WebSocket client.
+//! Clients only get events published after they connect; a client that falls behind buffers in
+//! memory rather than slowing the capture down.
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use async_tungstenite::tungstenite::Message;
+use futures::StreamExt;
+use futures::channel::mpsc;
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::task::JoinHandle;
+
+use crate::{Error, Event};
+
+type Clients = Arc<Mutex<Vec<mpsc::UnboundedSender<String>>>>;
+
+/// A running WebSocket server. Stops accepting clients when dropped; connected clients are
+/// closed once the event receiver closes.
+pub struct WsServer {
+    local_addr: SocketAddr,
+    accept: JoinHandle<()>,
+}
+
+impl WsServer {
+    /// The address the server listens on, useful after binding port `0`.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for WsServer {
+    fn drop(&mut self) {
+        self.accept.abort();
+    }
+}
+
+/// Bind `addr` and broadcast every event from `rx` to the connected clients until it closes.
+pub async fn serve_websocket(
+    mut rx: mpsc::UnboundedReceiver<Event>,
+    addr: impl ToSocketAddrs,
+) -> Result<WsServer, Error> {
+    let listener = TcpListener::bind(addr).await.map_err(Error::Io)?;
+    let local_addr = listener.local_addr().map_err(Error::Io)?;
+    let clients = Clients::default();
+
+    let accept_clients = Arc::clone(&clients);
+    let accept = tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    let (tx, frames) = mpsc::unbounded();
+                    accept_clients
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .push(tx);
+                    tokio::spawn(serve_client(stream, peer, frames));
+                }
+                Err(e) => tracing::warn!(error = %e, "accepting websocket client failed"),
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        while let Some(event) = rx.next().await {
+            let frame = match serde_json::to_string(&event) {
+                Ok(frame) => frame,
+                Err(e) => {
+                    tracing::warn!(error = %e, url = %event.url, "serializing event failed");
+                    continue;
+                }
+            };
+            clients
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .retain(|tx| tx.unbounded_send(frame.clone()).is_ok());
+        }
+        // Dropping the senders closes every client connection.
+        clients.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    });
+
+    tracing::debug!(%local_addr, "serving events over websocket");
+    Ok(WsServer { local_addr, accept })
+}
+
+async fn serve_client(
+    stream: TcpStream,
+    peer: SocketAddr,
+    mut frames: mpsc::UnboundedReceiver<String>,
+) {
+    let ws = match async_tungstenite::tokio::accept_async(stream).await {
+        Ok(ws) => ws,
+        Err(e) => {
+            tracing::debug!(%peer, error = %e, "websocket handshake failed");
+            return;
+        }
+    };
+    tracing::debug!(%peer, "websocket client connected");
+    let (mut sink, mut incoming) = ws.split();
+    // Read what the client sends so pings are answered and a close is noticed.
+    let reader = tokio::spawn(async move { while let Some(Ok(_)) = incoming.next().await {} });
+    while let Some(frame) = frames.next().await {
+        if sink.send(Message::text(frame)).await.is_err() {
+            break; // client went away
+        }
+    }
+    let _ = sink.close(None).await;
+    reader.abort();
+    tracing::debug!(%peer, "websocket client disconnected");
+}